@@ -4,6 +4,17 @@ fn main() {
     // Rebuild if shader sources change
     println!("cargo:rerun-if-changed=shaders/triangle.vert");
     println!("cargo:rerun-if-changed=shaders/triangle.frag");
+    println!("cargo:rerun-if-changed=shaders/lit.vert");
+    println!("cargo:rerun-if-changed=shaders/lit.frag");
+    println!("cargo:rerun-if-changed=shaders/shadow.vert");
+    println!("cargo:rerun-if-changed=shaders/fullscreen.vert");
+    println!("cargo:rerun-if-changed=shaders/tonemap.frag");
+    println!("cargo:rerun-if-changed=shaders/fxaa.frag");
+    println!("cargo:rerun-if-changed=shaders/vignette.frag");
+    println!("cargo:rerun-if-changed=shaders/debug.vert");
+    println!("cargo:rerun-if-changed=shaders/debug.frag");
+    println!("cargo:rerun-if-changed=shaders/text.vert");
+    println!("cargo:rerun-if-changed=shaders/text.frag");
 
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
 
@@ -26,6 +37,94 @@ fn main() {
         shaderc::ShaderKind::Fragment,
         out_dir.join("triangle.frag.spv"),
     );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/lit.vert",
+        shaderc::ShaderKind::Vertex,
+        out_dir.join("lit.vert.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/lit.frag",
+        shaderc::ShaderKind::Fragment,
+        out_dir.join("lit.frag.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/shadow.vert",
+        shaderc::ShaderKind::Vertex,
+        out_dir.join("shadow.vert.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/fullscreen.vert",
+        shaderc::ShaderKind::Vertex,
+        out_dir.join("fullscreen.vert.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/tonemap.frag",
+        shaderc::ShaderKind::Fragment,
+        out_dir.join("tonemap.frag.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/fxaa.frag",
+        shaderc::ShaderKind::Fragment,
+        out_dir.join("fxaa.frag.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/vignette.frag",
+        shaderc::ShaderKind::Fragment,
+        out_dir.join("vignette.frag.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/debug.vert",
+        shaderc::ShaderKind::Vertex,
+        out_dir.join("debug.vert.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/debug.frag",
+        shaderc::ShaderKind::Fragment,
+        out_dir.join("debug.frag.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/text.vert",
+        shaderc::ShaderKind::Vertex,
+        out_dir.join("text.vert.spv"),
+    );
+
+    compile_one(
+        &mut compiler,
+        &options,
+        "shaders/text.frag",
+        shaderc::ShaderKind::Fragment,
+        out_dir.join("text.frag.spv"),
+    );
 }
 
 fn compile_one(
@@ -4,5 +4,15 @@ set -euo pipefail
 mkdir -p spirv
 glslc triangle.vert -o spirv/triangle.vert.spv
 glslc triangle.frag -o spirv/triangle.frag.spv
+glslc lit.vert -o spirv/lit.vert.spv
+glslc lit.frag -o spirv/lit.frag.spv
+glslc shadow.vert -o spirv/shadow.vert.spv
+glslc fullscreen.vert -o spirv/fullscreen.vert.spv
+glslc tonemap.frag -o spirv/tonemap.frag.spv
+glslc fxaa.frag -o spirv/fxaa.frag.spv
+glslc vignette.frag -o spirv/vignette.frag.spv
+glslc debug.vert -o spirv/debug.vert.spv
+glslc debug.frag -o spirv/debug.frag.spv
+glslc text.vert -o spirv/text.vert.spv
+glslc text.frag -o spirv/text.frag.spv
 echo "OK: compiled shaders to shaders/spirv/"
-
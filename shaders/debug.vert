@@ -0,0 +1,17 @@
+#version 450
+
+// world-space debug lines, see renderer/debug_draw.rs
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inColor;
+
+layout(location = 0) out vec3 vColor;
+
+layout(set = 0, binding = 0) uniform UBO {
+    mat4 view_proj;
+    mat4 light_view_proj;
+} ubo;
+
+void main() {
+    gl_Position = ubo.view_proj * vec4(inPos, 1.0);
+    vColor = inColor;
+}
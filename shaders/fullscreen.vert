@@ -0,0 +1,10 @@
+#version 450
+
+// Fullscreen triangle for post-processing passes; draw 3 vertices, no
+// vertex buffers. vUv is (0,0) at the top-left of the target.
+layout(location = 0) out vec2 vUv;
+
+void main() {
+    vUv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(vUv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,58 @@
+#version 450
+
+layout(location = 0) in vec2 vUv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform sampler2D inputImage;
+
+layout(push_constant) uniform PostParams {
+    vec2 texel;
+    float time;
+    float exposure;
+    float gamma;
+    uint tonemap;
+    float vignette;
+    float pad;
+} post;
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+
+// input is linear when the chain runs in sRGB images; sqrt is close
+// enough to perceptual luma for edge detection
+float luma(vec3 c) {
+    return sqrt(dot(c, vec3(0.299, 0.587, 0.114)));
+}
+
+// Lottes' FXAA, console variant: blur along the local edge direction.
+void main() {
+    vec3 rgbM = texture(inputImage, vUv).rgb;
+    float lumaNW = luma(texture(inputImage, vUv + vec2(-1.0, -1.0) * post.texel).rgb);
+    float lumaNE = luma(texture(inputImage, vUv + vec2(1.0, -1.0) * post.texel).rgb);
+    float lumaSW = luma(texture(inputImage, vUv + vec2(-1.0, 1.0) * post.texel).rgb);
+    float lumaSE = luma(texture(inputImage, vUv + vec2(1.0, 1.0) * post.texel).rgb);
+    float lumaM = luma(rgbM);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 dir = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE)
+    );
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * post.texel;
+
+    vec3 rgbA = 0.5 * (texture(inputImage, vUv + dir * (1.0 / 3.0 - 0.5)).rgb
+                     + texture(inputImage, vUv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (texture(inputImage, vUv - dir * 0.5).rgb
+                                   + texture(inputImage, vUv + dir * 0.5).rgb);
+
+    float lumaB = luma(rgbB);
+    vec3 color = (lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB;
+
+    outColor = vec4(color, 1.0);
+}
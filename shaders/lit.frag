@@ -0,0 +1,74 @@
+#version 450
+
+layout(location = 0) in vec3 vColor;
+layout(location = 1) in vec3 vNormal;
+layout(location = 2) in vec3 vWorldPos;
+layout(location = 3) in vec2 vUv;
+layout(location = 4) in float vOpacity;
+layout(location = 5) in vec4 vLightPos;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 1) uniform Lighting {
+    vec4 direction;  // xyz: direction the light travels, w: shadow bias
+    vec4 color;      // rgb: color * intensity, w: specular strength
+    vec4 ambient;    // rgb: ambient color
+    vec4 camera_pos; // xyz: camera position, w: shininess
+} light;
+
+layout(set = 0, binding = 2) uniform sampler2DShadow shadowMap;
+
+layout(set = 1, binding = 0) uniform MaterialParams {
+    vec4 base_color;
+} material;
+
+layout(set = 1, binding = 1) uniform sampler2D baseColorTex;
+
+// 3x3 PCF on top of the sampler's 2x2 hardware compare filtering.
+// 1.0 = fully lit.
+float shadowFactor(vec3 N, vec3 L) {
+    vec3 p = vLightPos.xyz / vLightPos.w;
+    if (p.z > 1.0) {
+        return 1.0; // past the far end of the light frustum
+    }
+
+    vec2 uv = p.xy * 0.5 + 0.5;
+    // more bias where the light grazes the surface
+    float bias = light.direction.w * (1.0 + 2.0 * (1.0 - max(dot(N, L), 0.0)));
+    vec2 texel = 1.0 / vec2(textureSize(shadowMap, 0));
+
+    float lit = 0.0;
+    for (int x = -1; x <= 1; ++x) {
+        for (int y = -1; y <= 1; ++y) {
+            lit += texture(shadowMap, vec3(uv + vec2(x, y) * texel, p.z - bias));
+        }
+    }
+    return lit / 9.0;
+}
+
+void main() {
+    vec3 N = normalize(vNormal);
+    vec3 V = normalize(light.camera_pos.xyz - vWorldPos);
+
+    // materials may disable culling: light the side we see
+    if (dot(N, V) < 0.0) {
+        N = -N;
+    }
+
+    vec3 L = normalize(-light.direction.xyz);
+    float diffuse = max(dot(N, L), 0.0);
+
+    // Blinn-Phong specular
+    vec3 H = normalize(L + V);
+    float specular = diffuse > 0.0 ? pow(max(dot(N, H), 0.0), light.camera_pos.w) : 0.0;
+
+    float shadow = shadowFactor(N, L);
+
+    vec4 base = material.base_color * texture(baseColorTex, vUv);
+    vec3 albedo = vColor * base.rgb;
+
+    vec3 rgb = albedo * (light.ambient.rgb + light.color.rgb * diffuse * shadow)
+             + light.color.rgb * light.color.w * specular * shadow;
+
+    outColor = vec4(rgb, base.a * vOpacity);
+}
@@ -0,0 +1,33 @@
+#version 450
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec3 inNormal;
+layout(location = 3) in vec2 inUv;
+layout(location = 4) in mat4 inModel; // per instance, locations 4..7
+layout(location = 8) in float inOpacity;
+
+layout(location = 0) out vec3 vColor;
+layout(location = 1) out vec3 vNormal;
+layout(location = 2) out vec3 vWorldPos;
+layout(location = 3) out vec2 vUv;
+layout(location = 4) out float vOpacity;
+layout(location = 5) out vec4 vLightPos;
+
+layout(set = 0, binding = 0) uniform UBO {
+    mat4 view_proj;
+    mat4 light_view_proj;
+} ubo;
+
+void main() {
+    vec4 world = inModel * vec4(inPos, 1.0);
+    gl_Position = ubo.view_proj * world;
+
+    vWorldPos = world.xyz;
+    // inverse-transpose keeps normals right under non-uniform scale
+    vNormal = transpose(inverse(mat3(inModel))) * inNormal;
+    vColor = inColor;
+    vUv = inUv;
+    vOpacity = inOpacity;
+    vLightPos = ubo.light_view_proj * world;
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec3 inPos;
+layout(location = 4) in mat4 inModel; // per instance, locations 4..7
+
+layout(set = 0, binding = 0) uniform UBO {
+    mat4 view_proj;
+    mat4 light_view_proj;
+} ubo;
+
+void main() {
+    gl_Position = ubo.light_view_proj * inModel * vec4(inPos, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec3 vColor;
+layout(location = 1) in vec2 vUV;
+layout(location = 0) out vec4 outColor;
+
+// font atlas, coverage in alpha
+layout(set = 0, binding = 0) uniform sampler2D uAtlas;
+
+void main() {
+    outColor = vec4(vColor, texture(uAtlas, vUV).a);
+}
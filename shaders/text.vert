@@ -0,0 +1,19 @@
+#version 450
+
+// screen-space text quads, see renderer/text.rs
+layout(location = 0) in vec3 inPos; // pixels, origin top-left
+layout(location = 1) in vec3 inColor;
+layout(location = 3) in vec2 inUV;
+
+layout(location = 0) out vec3 vColor;
+layout(location = 1) out vec2 vUV;
+
+layout(push_constant) uniform Push {
+    vec2 texel; // 1 / target size in pixels
+} pc;
+
+void main() {
+    gl_Position = vec4(inPos.xy * pc.texel * 2.0 - 1.0, 0.0, 1.0);
+    vColor = inColor;
+    vUV = inUV;
+}
@@ -0,0 +1,45 @@
+#version 450
+
+layout(location = 0) in vec2 vUv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform sampler2D inputImage;
+
+// Shared by every post-processing shader (see renderer/post.rs).
+layout(push_constant) uniform PostParams {
+    vec2 texel;      // 1 / target size
+    float time;      // seconds
+    float exposure;
+    float gamma;     // 0 = no encoding
+    uint tonemap;    // 0 none, 1 Reinhard, 2 ACES
+    float vignette;  // strength
+    float pad;
+} post;
+
+// Narkowicz's ACES filmic curve fit
+vec3 aces(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return (x * (a * x + b)) / (x * (c * x + d) + e);
+}
+
+void main() {
+    vec3 color = texture(inputImage, vUv).rgb * post.exposure;
+
+    if (post.tonemap == 1u) {
+        color = color / (1.0 + color);
+    } else if (post.tonemap == 2u) {
+        color = aces(color);
+    }
+    color = clamp(color, 0.0, 1.0);
+
+    if (post.gamma > 0.0) {
+        color = pow(color, vec3(1.0 / post.gamma));
+    }
+
+    outColor = vec4(color, 1.0);
+}
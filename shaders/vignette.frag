@@ -0,0 +1,27 @@
+#version 450
+
+layout(location = 0) in vec2 vUv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform sampler2D inputImage;
+
+layout(push_constant) uniform PostParams {
+    vec2 texel;
+    float time;
+    float exposure;
+    float gamma;
+    uint tonemap;
+    float vignette;
+    float pad;
+} post;
+
+void main() {
+    vec3 color = texture(inputImage, vUv).rgb;
+
+    // 0 at the center, 1 in the corners
+    float d = length(vUv - 0.5) * 1.41421356;
+    color *= 1.0 - post.vignette * smoothstep(0.4, 1.0, d);
+
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,367 @@
+use crate::assets::mesh::MeshData;
+use crate::resources::buffer::Vertex;
+use crate::scene::transform::Transform;
+use anyhow::{Context, Result};
+use base64::Engine as _;
+use glam::{Quat, Vec3};
+use gltf::mesh::Mode;
+use std::path::Path;
+
+/// CPU-side result of importing a glTF file. No GPU objects are created
+/// here; see `Scene::instantiate` for that.
+pub struct ImportedScene {
+    /// One entry per glTF mesh, one `MeshData` per primitive.
+    pub meshes: Vec<Vec<MeshData>>,
+    /// Nodes of the default scene in pre-order, so parents always come
+    /// before their children.
+    pub nodes: Vec<ImportedNode>,
+}
+
+pub struct ImportedNode {
+    /// Index into `ImportedScene::nodes`.
+    pub parent: Option<usize>,
+    /// Local transform (relative to `parent`).
+    pub transform: Transform,
+    /// Index into `ImportedScene::meshes`.
+    pub mesh: Option<usize>,
+}
+
+/// Loads `.gltf` (JSON, external or data-URI buffers) or `.glb`.
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<ImportedScene> {
+    let path = path.as_ref();
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Failed to read glTF: {}", path.display()))?;
+
+    parse_gltf(&bytes, path.parent())
+        .with_context(|| format!("Failed to import glTF: {}", path.display()))
+}
+
+/// Parses glTF/GLB bytes. External buffer URIs are resolved against
+/// `base_dir`; without one only GLB and data-URI buffers work.
+pub fn parse_gltf(bytes: &[u8], base_dir: Option<&Path>) -> Result<ImportedScene> {
+    let gltf = gltf::Gltf::from_slice(bytes)?;
+    let buffers = load_buffers(&gltf, base_dir)?;
+
+    let mut meshes = Vec::new();
+    for mesh in gltf.meshes() {
+        let mut primitives = Vec::new();
+        for prim in mesh.primitives() {
+            match read_primitive(&prim, &buffers).with_context(|| {
+                format!(
+                    "mesh {} ({}), primitive {}",
+                    mesh.index(),
+                    mesh.name().unwrap_or("unnamed"),
+                    prim.index()
+                )
+            })? {
+                Some(data) => primitives.push(data),
+                None => log::warn!(
+                    "glTF: skipping non-triangle primitive {} of mesh {}",
+                    prim.index(),
+                    mesh.index()
+                ),
+            }
+        }
+        meshes.push(primitives);
+    }
+
+    let scene = gltf
+        .default_scene()
+        .or_else(|| gltf.scenes().next())
+        .context("glTF has no scenes")?;
+
+    let mut nodes = Vec::new();
+    for root in scene.nodes() {
+        push_node(&root, None, &mut nodes);
+    }
+
+    Ok(ImportedScene { meshes, nodes })
+}
+
+fn push_node(node: &gltf::Node, parent: Option<usize>, out: &mut Vec<ImportedNode>) {
+    let (translation, rotation, scale) = node.transform().decomposed();
+
+    out.push(ImportedNode {
+        parent,
+        transform: Transform {
+            position: Vec3::from(translation),
+            rotation: Quat::from_array(rotation),
+            scale: Vec3::from(scale),
+        },
+        mesh: node.mesh().map(|m| m.index()),
+    });
+
+    let idx = out.len() - 1;
+    for child in node.children() {
+        push_node(&child, Some(idx), out);
+    }
+}
+
+fn load_buffers(gltf: &gltf::Gltf, base_dir: Option<&Path>) -> Result<Vec<Vec<u8>>> {
+    let mut out = Vec::new();
+
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => gltf
+                .blob
+                .clone()
+                .context("buffer refers to a missing GLB BIN chunk")?,
+            gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+                let (_, payload) = uri
+                    .split_once(";base64,")
+                    .context("only base64 data URIs are supported")?;
+                base64::engine::general_purpose::STANDARD.decode(payload)?
+            }
+            gltf::buffer::Source::Uri(uri) => {
+                let dir = base_dir.context("external buffer URI but no base directory")?;
+                let path = dir.join(uri);
+                std::fs::read(&path)
+                    .with_context(|| format!("Failed to read glTF buffer: {}", path.display()))?
+            }
+        };
+
+        if data.len() < buffer.length() {
+            anyhow::bail!(
+                "buffer {} is {} bytes, expected at least {}",
+                buffer.index(),
+                data.len(),
+                buffer.length()
+            );
+        }
+        out.push(data);
+    }
+
+    Ok(out)
+}
+
+/// Returns `None` for point / line primitives.
+fn read_primitive(prim: &gltf::Primitive, buffers: &[Vec<u8>]) -> Result<Option<MeshData>> {
+    let reader = prim.reader(|b| buffers.get(b.index()).map(|d| d.as_slice()));
+
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .context("primitive has no POSITION")?
+        .collect();
+
+    // glTF: final color = baseColorFactor * COLOR_0 (both linear)
+    let factor = prim.material().pbr_metallic_roughness().base_color_factor();
+    let colors: Option<Vec<[f32; 3]>> = reader.read_colors(0).map(|c| c.into_rgb_f32().collect());
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+    let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+
+    let vertices = positions
+        .iter()
+        .enumerate()
+        .map(|(i, &pos)| {
+            let c = colors
+                .as_ref()
+                .and_then(|c| c.get(i).copied())
+                .unwrap_or([1.0, 1.0, 1.0]);
+            Vertex {
+                pos,
+                color: [c[0] * factor[0], c[1] * factor[1], c[2] * factor[2]],
+                normal: normals
+                    .as_ref()
+                    .and_then(|n| n.get(i).copied())
+                    .unwrap_or([0.0; 3]),
+                uv: uvs
+                    .as_ref()
+                    .and_then(|t| t.get(i).copied())
+                    .unwrap_or([0.0; 2]),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let raw: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+
+    if let Some(&bad) = raw.iter().find(|&&i| i as usize >= vertices.len()) {
+        anyhow::bail!("index {} out of range ({} vertices)", bad, vertices.len());
+    }
+
+    let indices = match prim.mode() {
+        Mode::Triangles => raw,
+        Mode::TriangleStrip => (2..raw.len())
+            .flat_map(|i| {
+                // keep winding consistent on odd triangles
+                if i % 2 == 0 {
+                    [raw[i - 2], raw[i - 1], raw[i]]
+                } else {
+                    [raw[i - 1], raw[i - 2], raw[i]]
+                }
+            })
+            .collect(),
+        Mode::TriangleFan => (2..raw.len())
+            .flat_map(|i| [raw[0], raw[i - 1], raw[i]])
+            .collect(),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => return Ok(None),
+    };
+
+    if indices.is_empty() {
+        return Ok(None);
+    }
+
+    let mut mesh = MeshData { vertices, indices };
+    if normals.is_none() {
+        mesh.compute_normals();
+    }
+
+    Ok(Some(mesh))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // four vertices, white to black, shared by every primitive
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const COLORS: [[f32; 3]; 4] = [
+        [1.0, 1.0, 1.0],
+        [0.5, 0.5, 0.5],
+        [0.25, 0.25, 0.25],
+        [0.0, 0.0, 0.0],
+    ];
+    const INDICES: [u32; 4] = [0, 1, 2, 3];
+
+    fn buffer() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(bytemuck::cast_slice(&POSITIONS));
+        bytes.extend_from_slice(bytemuck::cast_slice(&COLORS));
+        bytes.extend_from_slice(bytemuck::cast_slice(&INDICES));
+        bytes
+    }
+
+    /// `root (mesh) -> child -> leaf`, plus a second root. The mesh has a
+    /// triangle list, a strip and a fan primitive. `uri` is `None` for the
+    /// GLB BIN chunk.
+    fn document(uri: Option<&str>) -> String {
+        let uri = uri
+            .map(|u| format!(r#", "uri": "{u}""#))
+            .unwrap_or_default();
+        let primitive = |mode: u32, indices: u32| {
+            format!(
+                r#"{{ "attributes": {{ "POSITION": 0, "COLOR_0": 1 }},
+                    "indices": {indices}, "material": 0, "mode": {mode} }}"#
+            )
+        };
+
+        format!(
+            r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0, 3] }}],
+            "nodes": [
+                {{ "name": "root", "mesh": 0, "translation": [1, 0, 0], "children": [1] }},
+                {{ "name": "child", "translation": [0, 2, 0], "children": [2] }},
+                {{ "name": "leaf", "scale": [2, 2, 2] }},
+                {{ "name": "second_root" }}
+            ],
+            "meshes": [{{ "primitives": [{}, {}, {}] }}],
+            "materials": [{{
+                "pbrMetallicRoughness": {{ "baseColorFactor": [0.5, 0.25, 1, 1] }}
+            }}],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                   "min": [0, 0, 0], "max": [1, 1, 0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3" }},
+                {{ "bufferView": 2, "componentType": 5125, "count": 4, "type": "SCALAR" }},
+                {{ "bufferView": 2, "componentType": 5125, "count": 3, "type": "SCALAR" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+                {{ "buffer": 0, "byteOffset": 48, "byteLength": 48 }},
+                {{ "buffer": 0, "byteOffset": 96, "byteLength": 16 }}
+            ],
+            "buffers": [{{ "byteLength": {}{uri} }}]
+            }}"#,
+            primitive(4, 3),
+            primitive(5, 2),
+            primitive(6, 2),
+            buffer().len(),
+        )
+    }
+
+    fn gltf_with_data_uri() -> Vec<u8> {
+        let payload = base64::engine::general_purpose::STANDARD.encode(buffer());
+        document(Some(&format!(
+            "data:application/octet-stream;base64,{payload}"
+        )))
+        .into_bytes()
+    }
+
+    fn glb() -> Vec<u8> {
+        let mut json = document(None).into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = buffer();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        let mut out = Vec::with_capacity(total);
+        out.extend_from_slice(b"glTF");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&(total as u32).to_le_bytes());
+        out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"JSON");
+        out.extend_from_slice(&json);
+        out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"BIN\0");
+        out.extend_from_slice(&bin);
+        out
+    }
+
+    fn check(scene: &ImportedScene) {
+        // pre-order: root, child, leaf, second_root
+        let parents: Vec<_> = scene.nodes.iter().map(|n| n.parent).collect();
+        assert_eq!(parents, [None, Some(0), Some(1), None]);
+        assert_eq!(scene.nodes[0].mesh, Some(0));
+        assert_eq!(scene.nodes[1].mesh, None);
+
+        assert_eq!(scene.nodes[0].transform.position, Vec3::X);
+        assert_eq!(scene.nodes[1].transform.position, Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(scene.nodes[2].transform.scale, Vec3::splat(2.0));
+        assert_eq!(scene.nodes[3].transform.position, Vec3::ZERO);
+
+        let [tris, strip, fan] = &scene.meshes[0][..] else {
+            panic!("expected 3 primitives, got {}", scene.meshes[0].len());
+        };
+        assert_eq!(tris.indices, [0, 1, 2]);
+        // odd strip triangles swap their first two indices
+        assert_eq!(strip.indices, [0, 1, 2, 2, 1, 3]);
+        assert_eq!(fan.indices, [0, 1, 2, 0, 2, 3]);
+
+        // baseColorFactor * COLOR_0
+        assert_eq!(tris.vertices[0].color, [0.5, 0.25, 1.0]);
+        assert_eq!(tris.vertices[1].color, [0.25, 0.125, 0.5]);
+        // no NORMAL: computed from the faces
+        assert_eq!(tris.vertices[0].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn parses_gltf_with_data_uri_buffer() {
+        check(&parse_gltf(&gltf_with_data_uri(), None).unwrap());
+    }
+
+    #[test]
+    fn parses_glb() {
+        check(&parse_gltf(&glb(), None).unwrap());
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        // the index accessors reach past the 3 vertices of a shorter
+        // POSITION/COLOR_0 (min/max still from the full buffer)
+        let doc = String::from_utf8(gltf_with_data_uri()).unwrap().replace(
+            r#""count": 4, "type": "VEC3""#,
+            r#""count": 3, "type": "VEC3""#,
+        );
+        let err = parse_gltf(doc.as_bytes(), None).err().unwrap();
+        assert!(format!("{err:#}").contains("out of range"), "{err:#}");
+    }
+}
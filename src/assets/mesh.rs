@@ -1,75 +1,127 @@
 use crate::resources::buffer::Vertex;
+use crate::scene::culling::{Aabb, BoundingSphere};
+use glam::Vec3;
 
 pub struct MeshData {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
 }
 
+/// Normal used for degenerate geometry (world up is -Y).
+const FALLBACK_NORMAL: [f32; 3] = [0.0, -1.0, 0.0];
+
+impl MeshData {
+    /// Overwrites every vertex normal with the area-weighted average of the
+    /// faces that use it. Shared vertices come out smooth, split vertices
+    /// (like the faces of `cube()`) come out flat.
+    pub fn compute_normals(&mut self) {
+        let mut acc = vec![Vec3::ZERO; self.vertices.len()];
+
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let pa = Vec3::from(self.vertices[a].pos);
+            let pb = Vec3::from(self.vertices[b].pos);
+            let pc = Vec3::from(self.vertices[c].pos);
+
+            // length is twice the triangle area -> area weighting for free
+            let n = (pb - pa).cross(pc - pa);
+            acc[a] += n;
+            acc[b] += n;
+            acc[c] += n;
+        }
+
+        for (v, n) in self.vertices.iter_mut().zip(acc) {
+            v.normal = n.try_normalize().map_or(FALLBACK_NORMAL, |n| n.to_array());
+        }
+    }
+
+    /// Object-space bounds of all vertices.
+    pub fn bounds(&self) -> (Aabb, BoundingSphere) {
+        let points: Vec<Vec3> = self.vertices.iter().map(|v| Vec3::from(v.pos)).collect();
+        (
+            Aabb::from_points(points.iter().copied()),
+            BoundingSphere::from_points(&points),
+        )
+    }
+}
+
 pub fn cube() -> MeshData {
-    let vertices = vec![
-        // Front
-        Vertex {
-            pos: [-0.5, -0.5, 0.5],
-            color: [1.0, 0.0, 0.0],
-        },
-        Vertex {
-            pos: [0.5, -0.5, 0.5],
-            color: [0.0, 1.0, 0.0],
-        },
-        Vertex {
-            pos: [0.5, 0.5, 0.5],
-            color: [0.0, 0.0, 1.0],
-        },
-        Vertex {
-            pos: [-0.5, 0.5, 0.5],
-            color: [1.0, 1.0, 0.0],
-        },
-        // Back
-        Vertex {
-            pos: [-0.5, -0.5, -0.5],
-            color: [1.0, 0.0, 1.0],
-        },
-        Vertex {
-            pos: [0.5, -0.5, -0.5],
-            color: [0.0, 1.0, 1.0],
-        },
-        Vertex {
-            pos: [0.5, 0.5, -0.5],
-            color: [1.0, 1.0, 1.0],
-        },
-        Vertex {
-            pos: [-0.5, 0.5, -0.5],
-            color: [0.2, 0.2, 0.2],
-        },
+    // Corner positions and colors (same as the old 8-vertex cube)
+    let corners: [([f32; 3], [f32; 3]); 8] = [
+        ([-0.5, -0.5, 0.5], [1.0, 0.0, 0.0]),
+        ([0.5, -0.5, 0.5], [0.0, 1.0, 0.0]),
+        ([0.5, 0.5, 0.5], [0.0, 0.0, 1.0]),
+        ([-0.5, 0.5, 0.5], [1.0, 1.0, 0.0]),
+        ([-0.5, -0.5, -0.5], [1.0, 0.0, 1.0]),
+        ([0.5, -0.5, -0.5], [0.0, 1.0, 1.0]),
+        ([0.5, 0.5, -0.5], [1.0, 1.0, 1.0]),
+        ([-0.5, 0.5, -0.5], [0.2, 0.2, 0.2]),
     ];
 
-    let indices = vec![
-        0, 1, 2, 2, 3, 0, 1, 5, 6, 6, 2, 1, 5, 4, 7, 7, 6, 5, 4, 0, 3, 3, 7, 4, 4, 5, 1, 1, 0, 4,
-        3, 2, 6, 6, 7, 3,
+    // 4 vertices per face so each face gets its own flat normal
+    let faces: [([usize; 4], [f32; 3]); 6] = [
+        ([0, 1, 2, 3], [0.0, 0.0, 1.0]),  // front
+        ([1, 5, 6, 2], [1.0, 0.0, 0.0]),  // right
+        ([5, 4, 7, 6], [0.0, 0.0, -1.0]), // back
+        ([4, 0, 3, 7], [-1.0, 0.0, 0.0]), // left
+        ([4, 5, 1, 0], [0.0, -1.0, 0.0]), // -Y (world up)
+        ([3, 2, 6, 7], [0.0, 1.0, 0.0]),  // +Y (faces the floor)
     ];
 
+    // same UV square on every face; quads start at the top-left (-Y is up)
+    let uvs = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+
+    for (quad, normal) in faces {
+        let base = vertices.len() as u32;
+        for (c, uv) in quad.into_iter().zip(uvs) {
+            let (pos, color) = corners[c];
+            vertices.push(Vertex {
+                pos,
+                color,
+                normal,
+                uv,
+            });
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+    }
+
     MeshData { vertices, indices }
 }
 
 pub fn plane(size: f32) -> MeshData {
     let h = size * 0.5;
+    let color = [0.3, 0.3, 0.35];
+    // faces the camera side of the floor (world up is -Y)
+    let normal = [0.0, -1.0, 0.0];
 
+    // one texture repeat per world unit
     let vertices = vec![
         Vertex {
             pos: [-h, 0.0, -h],
-            color: [0.3, 0.3, 0.35],
+            color,
+            normal,
+            uv: [0.0, 0.0],
         },
         Vertex {
             pos: [h, 0.0, -h],
-            color: [0.3, 0.3, 0.35],
+            color,
+            normal,
+            uv: [size, 0.0],
         },
         Vertex {
             pos: [h, 0.0, h],
-            color: [0.3, 0.3, 0.35],
+            color,
+            normal,
+            uv: [size, size],
         },
         Vertex {
             pos: [-h, 0.0, h],
-            color: [0.3, 0.3, 0.35],
+            color,
+            normal,
+            uv: [0.0, size],
         },
     ];
 
@@ -1,2 +1,7 @@
+pub mod font;
+pub mod gltf_import;
 pub mod mesh;
+pub mod obj;
+pub mod shader_reload;
 pub mod shaders;
+pub mod texture;
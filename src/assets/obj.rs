@@ -0,0 +1,517 @@
+use crate::assets::mesh::MeshData;
+use crate::resources::buffer::Vertex;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// Used when a vertex has no color of its own and no material applies.
+const DEFAULT_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+
+/// A malformed line in an OBJ or MTL file.
+#[derive(Debug, Error)]
+#[error("{file}:{line}: {message}")]
+pub struct ObjError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+/// Material name -> diffuse color (`Kd`).
+pub type MaterialColors = HashMap<String, [f32; 3]>;
+
+/// Loads a Wavefront OBJ file plus any `mtllib` it references (resolved
+/// relative to the OBJ file).
+pub fn load_obj(path: impl AsRef<Path>) -> Result<MeshData> {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let source =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read OBJ: {}", name))?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials = MaterialColors::new();
+
+    for (i, raw) in source.lines().enumerate() {
+        let mut tokens = strip_comment(raw).split_whitespace();
+        if tokens.next() != Some("mtllib") {
+            continue;
+        }
+
+        let lib = tokens.collect::<Vec<_>>().join(" ");
+        if lib.is_empty() {
+            return Err(obj_error(&name, i + 1, "mtllib without a file name").into());
+        }
+
+        let mtl_path = dir.join(&lib);
+        let mtl_source = std::fs::read_to_string(&mtl_path).with_context(|| {
+            format!(
+                "{}:{}: Failed to read MTL: {}",
+                name,
+                i + 1,
+                mtl_path.display()
+            )
+        })?;
+        materials.extend(parse_mtl(&mtl_source, &mtl_path.display().to_string())?);
+    }
+
+    Ok(parse_obj(&source, &name, &materials)?)
+}
+
+/// Parses the diffuse colors out of an MTL file. Everything except
+/// `newmtl` and `Kd` is ignored.
+pub fn parse_mtl(source: &str, file: &str) -> Result<MaterialColors, ObjError> {
+    let mut colors = MaterialColors::new();
+    let mut current: Option<String> = None;
+
+    for (i, raw) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let line = strip_comment(raw);
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("newmtl") => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                if name.is_empty() {
+                    return Err(obj_error(file, line_no, "newmtl without a name"));
+                }
+                colors.insert(name.clone(), DEFAULT_COLOR);
+                current = Some(name);
+            }
+            Some("Kd") => {
+                let name = current
+                    .as_ref()
+                    .ok_or_else(|| obj_error(file, line_no, "Kd before any newmtl"))?;
+                let kd = parse_floats::<3>(tokens, file, line_no, "Kd")?;
+                colors.insert(name.clone(), kd);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(colors)
+}
+
+/// Parses OBJ text into indexed `MeshData`.
+///
+/// Polygons are fan-triangulated, and each unique `v/vt/vn` (+ material)
+/// combination becomes one `Vertex`. Vertex colors come from the
+/// `v x y z r g b` extension when present, otherwise from the active
+/// material's `Kd`. If any corner lacks a `vn`, normals are generated for
+/// the whole mesh.
+pub fn parse_obj(
+    source: &str,
+    file: &str,
+    materials: &MaterialColors,
+) -> Result<MeshData, ObjError> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut colors: Vec<Option<[f32; 3]>> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    let mut dedup: HashMap<(FaceVertex, Option<usize>), u32> = HashMap::new();
+    let mut missing_normals = false;
+
+    // Index into `material_names` so the dedup key stays `Copy`.
+    let mut material_names: Vec<&str> = Vec::new();
+    let mut current_material: Option<usize> = None;
+
+    for (i, raw) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let line = strip_comment(raw);
+        let mut tokens = line.split_whitespace();
+
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => {
+                let values = tokens
+                    .map(|t| parse_float(t, file, line_no))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match values.len() {
+                    // x y z [w]
+                    3 | 4 => {
+                        positions.push([values[0], values[1], values[2]]);
+                        colors.push(None);
+                    }
+                    // x y z r g b
+                    6 => {
+                        positions.push([values[0], values[1], values[2]]);
+                        colors.push(Some([values[3], values[4], values[5]]));
+                    }
+                    n => {
+                        return Err(obj_error(
+                            file,
+                            line_no,
+                            &format!("v expects 3, 4 or 6 values, got {}", n),
+                        ));
+                    }
+                }
+            }
+            "vt" => {
+                let values = tokens
+                    .map(|t| parse_float(t, file, line_no))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if !(1..=3).contains(&values.len()) {
+                    return Err(obj_error(
+                        file,
+                        line_no,
+                        &format!("vt expects 1 to 3 values, got {}", values.len()),
+                    ));
+                }
+                uvs.push([values[0], values.get(1).copied().unwrap_or(0.0)]);
+            }
+            "vn" => {
+                normals.push(parse_floats::<3>(tokens, file, line_no, "vn")?);
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|t| {
+                        parse_face_vertex(
+                            t,
+                            positions.len(),
+                            uvs.len(),
+                            normals.len(),
+                            file,
+                            line_no,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if corners.len() < 3 {
+                    return Err(obj_error(
+                        file,
+                        line_no,
+                        &format!("face needs at least 3 vertices, got {}", corners.len()),
+                    ));
+                }
+
+                let mut resolve = |fv: FaceVertex| -> u32 {
+                    *dedup.entry((fv, current_material)).or_insert_with(|| {
+                        let color = colors[fv.position]
+                            .or_else(|| {
+                                current_material
+                                    .and_then(|m| materials.get(material_names[m]).copied())
+                            })
+                            .unwrap_or(DEFAULT_COLOR);
+
+                        let normal = match fv.normal {
+                            Some(n) => normals[n],
+                            None => {
+                                missing_normals = true;
+                                [0.0; 3]
+                            }
+                        };
+
+                        // OBJ puts v = 0 at the bottom of the image
+                        let uv = fv.uv.map_or([0.0, 0.0], |t| [uvs[t][0], 1.0 - uvs[t][1]]);
+
+                        vertices.push(Vertex {
+                            pos: positions[fv.position],
+                            color,
+                            normal,
+                            uv,
+                        });
+                        (vertices.len() - 1) as u32
+                    })
+                };
+
+                // fan triangulation: (0, k, k+1)
+                let first = resolve(corners[0]);
+                let mut prev = resolve(corners[1]);
+                for &corner in &corners[2..] {
+                    let next = resolve(corner);
+                    indices.extend_from_slice(&[first, prev, next]);
+                    prev = next;
+                }
+            }
+            "usemtl" => {
+                let name = line.trim_start()["usemtl".len()..].trim();
+                if name.is_empty() {
+                    return Err(obj_error(file, line_no, "usemtl without a name"));
+                }
+                if !materials.contains_key(name) {
+                    log::warn!("{}:{}: unknown material '{}'", file, line_no, name);
+                }
+
+                let idx = match material_names.iter().position(|&m| m == name) {
+                    Some(idx) => idx,
+                    None => {
+                        material_names.push(name);
+                        material_names.len() - 1
+                    }
+                };
+                current_material = Some(idx);
+            }
+            // grouping / smoothing / lines / points are not needed for meshes
+            "mtllib" | "o" | "g" | "s" | "l" | "p" => {}
+            other => {
+                log::debug!("{}:{}: ignoring '{}'", file, line_no, other);
+            }
+        }
+    }
+
+    if indices.is_empty() {
+        return Err(obj_error(file, source.lines().count(), "no faces found"));
+    }
+
+    let mut mesh = MeshData { vertices, indices };
+    if missing_normals {
+        mesh.compute_normals();
+    }
+
+    Ok(mesh)
+}
+
+/// Zero-based indices of one face corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+fn parse_face_vertex(
+    token: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+    file: &str,
+    line: usize,
+) -> Result<FaceVertex, ObjError> {
+    let mut parts = token.split('/');
+
+    let position = match parts.next() {
+        Some(p) if !p.is_empty() => resolve_index(p, position_count, "v", file, line)?,
+        _ => {
+            return Err(obj_error(
+                file,
+                line,
+                &format!("bad face vertex '{}'", token),
+            ));
+        }
+    };
+
+    let uv = match parts.next() {
+        Some(t) if !t.is_empty() => Some(resolve_index(t, uv_count, "vt", file, line)?),
+        _ => None,
+    };
+
+    let normal = match parts.next() {
+        Some(n) if !n.is_empty() => Some(resolve_index(n, normal_count, "vn", file, line)?),
+        _ => None,
+    };
+
+    if parts.next().is_some() {
+        return Err(obj_error(
+            file,
+            line,
+            &format!("bad face vertex '{}'", token),
+        ));
+    }
+
+    Ok(FaceVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+/// OBJ indices are 1-based; negative ones count back from the end.
+fn resolve_index(
+    token: &str,
+    count: usize,
+    kind: &str,
+    file: &str,
+    line: usize,
+) -> Result<usize, ObjError> {
+    let raw: i64 = token
+        .parse()
+        .map_err(|_| obj_error(file, line, &format!("bad {} index '{}'", kind, token)))?;
+
+    let idx = match raw {
+        0 => None,
+        r if r > 0 => Some(r as usize - 1),
+        r => count.checked_sub(r.unsigned_abs() as usize),
+    };
+
+    match idx {
+        Some(i) if i < count => Ok(i),
+        _ => Err(obj_error(
+            file,
+            line,
+            &format!("{} index {} out of range (have {})", kind, raw, count),
+        )),
+    }
+}
+
+fn parse_float(token: &str, file: &str, line: usize) -> Result<f32, ObjError> {
+    token
+        .parse()
+        .map_err(|_| obj_error(file, line, &format!("bad number '{}'", token)))
+}
+
+fn parse_floats<'a, const N: usize>(
+    tokens: impl Iterator<Item = &'a str>,
+    file: &str,
+    line: usize,
+    keyword: &str,
+) -> Result<[f32; N], ObjError> {
+    let values = tokens
+        .map(|t| parse_float(t, file, line))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    values.try_into().map_err(|v: Vec<f32>| {
+        obj_error(
+            file,
+            line,
+            &format!("{} expects {} values, got {}", keyword, N, v.len()),
+        )
+    })
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn obj_error(file: &str, line: usize, message: &str) -> ObjError {
+    ObjError {
+        file: file.to_string(),
+        line,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> MeshData {
+        parse_obj(source, "test.obj", &MaterialColors::new()).unwrap()
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let quad = parse(&format!("{SQUARE}f 1 2 3 4\n"));
+        assert_eq!(quad.indices, [0, 1, 2, 0, 2, 3]);
+
+        let pentagon = parse("v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n");
+        assert_eq!(pentagon.indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn corners_are_deduplicated_by_v_vt_vn() {
+        let mesh = parse(&format!(
+            "{SQUARE}vt 0 0\nvt 1 1\nvn 0 0 1\n\
+             f 1/1/1 2/1/1 3/1/1\n\
+             f 1/1/1 3/1/1 4/1/1\n\
+             f 1/2/1 2/1/1 4/1/1\n"
+        ));
+
+        // 1/1/1 and 3/1/1 are shared; 1/2/1 differs in its uv only
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 4, 1, 3]);
+        // v is flipped
+        assert_eq!(mesh.vertices[0].uv, [0.0, 1.0]);
+        assert_eq!(mesh.vertices[4].uv, [1.0, 0.0]);
+        assert_eq!(mesh.vertices[4].pos, mesh.vertices[0].pos);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_end() {
+        let relative = parse(&format!("{SQUARE}vn 0 0 1\nf -4//-1 -3//-1 -2//-1\n"));
+        let absolute = parse(&format!("{SQUARE}vn 0 0 1\nf 1//1 2//1 3//1\n"));
+
+        let positions = |m: &MeshData| m.vertices.iter().map(|v| v.pos).collect::<Vec<_>>();
+        assert_eq!(positions(&relative), positions(&absolute));
+        assert_eq!(relative.indices, absolute.indices);
+    }
+
+    #[test]
+    fn colors_fall_back_from_vertex_to_kd_to_default() {
+        let materials =
+            parse_mtl("newmtl red # comment\nKd 1 0 0\nnewmtl plain\n", "m.mtl").unwrap();
+        let mesh = parse_obj(
+            "v 0 0 0 0 1 0\nv 1 0 0\nv 1 1 0\n\
+             usemtl red\nf 1 2 3\n\
+             usemtl plain\nf 1 2 3\n\
+             usemtl missing\nf 1 2 3\n",
+            "test.obj",
+            &materials,
+        )
+        .unwrap();
+
+        let colors: Vec<_> = mesh.vertices.iter().map(|v| v.color).collect();
+        assert_eq!(
+            colors,
+            [
+                // vertex color wins over Kd
+                [0.0, 1.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                // newmtl without Kd
+                [0.0, 1.0, 0.0],
+                DEFAULT_COLOR,
+                DEFAULT_COLOR,
+                // unknown material
+                [0.0, 1.0, 0.0],
+                DEFAULT_COLOR,
+                DEFAULT_COLOR,
+            ]
+        );
+        // no vn anywhere: normals are generated
+        assert_eq!(mesh.vertices[1].normal, [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn errors_name_the_file_and_line() {
+        let materials = MaterialColors::new();
+        let err = |source: &str| {
+            parse_obj(source, "model.obj", &materials)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(err("v 0 0 0\nv 1 x 0\n"), "model.obj:2: bad number 'x'");
+        assert_eq!(
+            err(&format!("{SQUARE}# comment\nf 1 2 5\n")),
+            "model.obj:6: v index 5 out of range (have 4)"
+        );
+        assert_eq!(
+            err(&format!("{SQUARE}f 1 -5 2\n")),
+            "model.obj:5: v index -5 out of range (have 4)"
+        );
+        assert_eq!(
+            err(&format!("{SQUARE}f 1 2\n")),
+            "model.obj:5: face needs at least 3 vertices, got 2"
+        );
+        assert_eq!(
+            parse_mtl("Kd 1 1 1\n", "m.mtl").unwrap_err().to_string(),
+            "m.mtl:1: Kd before any newmtl"
+        );
+    }
+
+    #[test]
+    fn loads_mtllib_next_to_the_obj() {
+        // ramp.obj has a trailing comment on its mtllib line
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/models/ramp.obj");
+        let mesh = load_obj(path).unwrap();
+
+        let wood = [0.55, 0.38, 0.22];
+        let paint = [0.85, 0.2, 0.15];
+        assert_eq!(mesh.indices.len(), 8 * 3);
+        assert_eq!(mesh.vertices.len(), 18);
+        assert!(mesh.vertices[..14].iter().all(|v| v.color == wood));
+        assert!(mesh.vertices[14..].iter().all(|v| v.color == paint));
+    }
+}
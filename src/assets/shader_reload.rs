@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use thiserror::Error;
+
+/// How often `ShaderWatcher::poll` actually looks at the file system.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A GLSL compile failure. `line` is `None` for errors shaderc does not
+/// attribute to a line (e.g. a missing `main`).
+#[derive(Debug, Error)]
+#[error("{file}:{}: {message}", line.map_or("?".to_string(), |l| l.to_string()))]
+pub struct ShaderCompileError {
+    pub file: String,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+/// Polls `*.vert` / `*.frag` in a directory by modification time and
+/// recompiles the ones that changed.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    mtimes: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+    compiler: shaderc::Compiler,
+}
+
+impl ShaderWatcher {
+    /// Remembers the current mtimes, so only later edits are reported.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        let compiler = shaderc::Compiler::new().context("Failed to create shaderc compiler")?;
+
+        let mut watcher = Self {
+            dir,
+            mtimes: HashMap::new(),
+            last_poll: Instant::now(),
+            compiler,
+        };
+        watcher.mtimes = watcher.scan()?;
+
+        log::info!("Watching {} for shader changes", watcher.dir.display());
+        Ok(watcher)
+    }
+
+    /// Returns the shader sources modified (or added) since the last call.
+    /// Cheap to call every frame; the directory is only scanned every
+    /// `POLL_INTERVAL`.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let current = match self.scan() {
+            Ok(current) => current,
+            Err(e) => {
+                log::warn!("shader watcher: {:#}", e);
+                return Vec::new();
+            }
+        };
+
+        let mut changed: Vec<PathBuf> = current
+            .iter()
+            .filter(|(path, mtime)| self.mtimes.get(*path) != Some(mtime))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+
+        self.mtimes = current;
+        changed
+    }
+
+    /// Compiles one GLSL file to SPIR-V.
+    pub fn compile(&self, path: &Path) -> Result<Vec<u8>> {
+        compile_with(&self.compiler, path)
+    }
+
+    fn scan(&self) -> Result<HashMap<PathBuf, SystemTime>> {
+        let mut out = HashMap::new();
+
+        let entries = std::fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read {}", self.dir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            if shader_kind(&path).is_none() {
+                continue;
+            }
+            // editors may briefly remove the file while saving
+            if let Ok(mtime) = std::fs::metadata(&path).and_then(|m| m.modified()) {
+                out.insert(path, mtime);
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+/// One-off compile of a GLSL file (`.vert` / `.frag`) to SPIR-V, e.g. for
+/// shaders named in the config that aren't embedded at build time.
+pub fn compile_shader_file(path: &Path) -> Result<Vec<u8>> {
+    let compiler = shaderc::Compiler::new().context("Failed to create shaderc compiler")?;
+    compile_with(&compiler, path)
+}
+
+fn compile_with(compiler: &shaderc::Compiler, path: &Path) -> Result<Vec<u8>> {
+    let name = path.display().to_string();
+    let kind = shader_kind(path).with_context(|| format!("not a shader: {}", name))?;
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read shader: {}", name))?;
+
+    let artifact = compiler
+        .compile_into_spirv(&source, kind, &name, "main", None)
+        .map_err(|e| compile_error(&name, e))?;
+
+    if artifact.get_num_warnings() > 0 {
+        log::warn!("{}", artifact.get_warning_messages().trim_end());
+    }
+
+    Ok(artifact.as_binary_u8().to_vec())
+}
+
+fn shader_kind(path: &Path) -> Option<shaderc::ShaderKind> {
+    match path.extension()?.to_str()? {
+        "vert" => Some(shaderc::ShaderKind::Vertex),
+        "frag" => Some(shaderc::ShaderKind::Fragment),
+        _ => None,
+    }
+}
+
+/// shaderc reports `<file>:<line>: error: <message>`, one per line; the
+/// first one is the one worth showing.
+fn compile_error(file: &str, err: shaderc::Error) -> ShaderCompileError {
+    let text = match err {
+        shaderc::Error::CompilationError(_, text) => text,
+        other => other.to_string(),
+    };
+
+    let first = text.lines().find(|l| l.contains("error")).unwrap_or(&text);
+
+    let parsed = first
+        .strip_prefix(file)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(line, message)| Some((line.trim().parse::<u32>().ok()?, message.trim())));
+
+    match parsed {
+        Some((line, message)) => ShaderCompileError {
+            file: file.to_string(),
+            line: Some(line),
+            message: message.to_string(),
+        },
+        None => ShaderCompileError {
+            file: file.to_string(),
+            line: None,
+            message: first.trim().to_string(),
+        },
+    }
+}
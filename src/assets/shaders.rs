@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 pub fn triangle_vert_spv() -> &'static [u8] {
     include_bytes!(concat!(env!("OUT_DIR"), "/triangle.vert.spv"))
 }
@@ -5,3 +7,94 @@ pub fn triangle_vert_spv() -> &'static [u8] {
 pub fn triangle_frag_spv() -> &'static [u8] {
     include_bytes!(concat!(env!("OUT_DIR"), "/triangle.frag.spv"))
 }
+
+pub fn lit_vert_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/lit.vert.spv"))
+}
+
+pub fn lit_frag_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/lit.frag.spv"))
+}
+
+pub fn shadow_vert_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/shadow.vert.spv"))
+}
+
+pub fn fullscreen_vert_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/fullscreen.vert.spv"))
+}
+
+pub fn tonemap_frag_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/tonemap.frag.spv"))
+}
+
+pub fn fxaa_frag_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/fxaa.frag.spv"))
+}
+
+pub fn vignette_frag_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/vignette.frag.spv"))
+}
+
+pub fn debug_vert_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/debug.vert.spv"))
+}
+
+pub fn debug_frag_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/debug.frag.spv"))
+}
+
+pub fn text_vert_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/text.vert.spv"))
+}
+
+pub fn text_frag_spv() -> &'static [u8] {
+    include_bytes!(concat!(env!("OUT_DIR"), "/text.frag.spv"))
+}
+
+/// SPIR-V by shader source name (e.g. `"lit.frag"`). Starts out with the
+/// binaries `build.rs` embedded; hot-reload replaces entries at runtime.
+#[derive(Clone)]
+pub struct ShaderLibrary {
+    modules: HashMap<String, Vec<u8>>,
+}
+
+impl ShaderLibrary {
+    pub fn embedded() -> Self {
+        let modules = [
+            ("triangle.vert", triangle_vert_spv()),
+            ("triangle.frag", triangle_frag_spv()),
+            ("lit.vert", lit_vert_spv()),
+            ("lit.frag", lit_frag_spv()),
+            ("shadow.vert", shadow_vert_spv()),
+            ("fullscreen.vert", fullscreen_vert_spv()),
+            ("tonemap.frag", tonemap_frag_spv()),
+            ("fxaa.frag", fxaa_frag_spv()),
+            ("vignette.frag", vignette_frag_spv()),
+            ("debug.vert", debug_vert_spv()),
+            ("debug.frag", debug_frag_spv()),
+            ("text.vert", text_vert_spv()),
+            ("text.frag", text_frag_spv()),
+        ]
+        .into_iter()
+        .map(|(name, spv)| (name.to_string(), spv.to_vec()))
+        .collect();
+
+        Self { modules }
+    }
+
+    pub fn get(&self, name: &str) -> &[u8] {
+        self.modules
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_else(|| panic!("unknown shader: {}", name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.modules.contains_key(name)
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, spv: Vec<u8>) {
+        self.modules.insert(name.into(), spv);
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Decoded image, always tightly packed RGBA8. No GPU objects here; see
+/// `Texture::from_data` for the upload.
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl TextureData {
+    /// 1x1 texture of a single color.
+    pub fn solid(rgba: [u8; 4]) -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            rgba: rgba.to_vec(),
+        }
+    }
+
+    /// `cells` x `cells` checkerboard, `cell_px` pixels per cell.
+    pub fn checkerboard(cells: u32, cell_px: u32, a: [u8; 4], b: [u8; 4]) -> Self {
+        let size = cells * cell_px;
+        let mut rgba = Vec::with_capacity((size * size * 4) as usize);
+
+        for y in 0..size {
+            for x in 0..size {
+                let even = (x / cell_px + y / cell_px).is_multiple_of(2);
+                rgba.extend_from_slice(if even { &a } else { &b });
+            }
+        }
+
+        Self {
+            width: size,
+            height: size,
+            rgba,
+        }
+    }
+}
+
+/// Loads a PNG or TGA file (format guessed from the contents).
+pub fn load_texture(path: impl AsRef<Path>) -> Result<TextureData> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Failed to read texture: {}", path.display()))?;
+
+    parse_texture(&bytes).with_context(|| format!("Failed to decode texture: {}", path.display()))
+}
+
+/// Decodes PNG/TGA bytes, e.g. an image embedded in a glTF buffer.
+pub fn parse_texture(bytes: &[u8]) -> Result<TextureData> {
+    let img = image::load_from_memory(bytes)?.to_rgba8();
+
+    Ok(TextureData {
+        width: img.width(),
+        height: img.height(),
+        rgba: img.into_raw(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgba, RgbaImage};
+    use std::io::Cursor;
+
+    fn png(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn parses_png_into_rgba8() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([0, 0, 255, 128]));
+
+        let texture = parse_texture(&png(&image)).unwrap();
+        assert_eq!((texture.width, texture.height), (2, 1));
+        assert_eq!(texture.rgba, [255, 0, 0, 255, 0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_texture(b"not an image").is_err());
+    }
+
+    #[test]
+    fn checkerboard_alternates_cells() {
+        let (a, b) = ([1; 4], [2; 4]);
+        let texture = TextureData::checkerboard(2, 2, a, b);
+        assert_eq!((texture.width, texture.height), (4, 4));
+
+        let pixel = |x: usize, y: usize| &texture.rgba[(y * 4 + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), a);
+        assert_eq!(pixel(1, 1), a);
+        assert_eq!(pixel(2, 0), b);
+        assert_eq!(pixel(0, 3), b);
+        assert_eq!(pixel(3, 3), a);
+    }
+
+    #[test]
+    fn loads_the_floor_texture() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/textures/floor.png");
+        let texture = load_texture(path).unwrap();
+        assert_eq!((texture.width, texture.height), (64, 64));
+        assert_eq!(texture.rgba.len(), 64 * 64 * 4);
+    }
+}
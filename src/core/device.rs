@@ -1,7 +1,14 @@
 use anyhow::{Context, Result};
 use ash::{Instance, vk};
 
-use super::{queues::QueueFamilyIndices, surface::Surface};
+use crate::resources::allocator::GpuAllocator;
+
+use super::{
+    dynamic_rendering::{DynamicRendering, DynamicRenderingSupport, query_support},
+    pipeline_cache::PipelineCache,
+    queues::QueueFamilyIndices,
+    surface::Surface,
+};
 
 pub struct Device {
     pub instance: ash::Instance,
@@ -9,14 +16,39 @@ pub struct Device {
     pub device: ash::Device,
     pub queues: QueueFamilyIndices,
     pub graphics_queue: vk::Queue,
-    pub present_queue: vk::Queue,
+    pub present_queue: Option<vk::Queue>,
+    /// Queue of `queues.upload_family()`; the graphics queue when there is
+    /// no dedicated transfer family.
+    pub transfer_queue: vk::Queue,
     pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub properties: vk::PhysicalDeviceProperties,
+    /// Core features that were actually enabled on `device`.
+    pub features: vk::PhysicalDeviceFeatures,
+    /// `Some` if dynamic rendering was requested and is enabled.
+    pub dynamic_rendering: Option<DynamicRendering>,
+    /// Used for every pipeline; saved to disk when the device is dropped.
+    pub pipeline_cache: PipelineCache,
+    /// Memory of every buffer and image the renderer creates.
+    pub allocator: GpuAllocator,
     pub upload_pool: vk::CommandPool,
     pub upload_fence: vk::Fence,
 }
 
 impl Device {
-    pub fn new(instance: &Instance, surface: &Surface) -> Result<Self> {
+    /// Picks a physical device and creates the logical device.
+    ///
+    /// With `surface == None` (headless) the present queue and the swapchain
+    /// extension are skipped, so any device with a graphics queue qualifies,
+    /// including software implementations such as lavapipe.
+    ///
+    /// `dynamic_rendering` asks for dynamic rendering (1.3 core or
+    /// `VK_KHR_dynamic_rendering`); without support it falls back to render
+    /// passes with a warning.
+    pub fn new(
+        instance: &Instance,
+        surface: Option<&Surface>,
+        dynamic_rendering: bool,
+    ) -> Result<Self> {
         let physical_devices = unsafe { instance.enumerate_physical_devices() }
             .context("No physical devices found")?;
 
@@ -24,21 +56,31 @@ impl Device {
 
         for pd in physical_devices {
             if let Some(q) = Self::find_queue_families(instance, surface, pd)? {
-                if Self::supports_swapchain(instance, pd)? {
+                if surface.is_none() || Self::supports_swapchain(instance, pd)? {
                     picked = Some((pd, q));
                     break;
                 }
             }
         }
 
-        let (physical, queues) =
-            picked.context("Failed to find suitable GPU (graphics+present+swapchain)")?;
+        let (physical, queues) = match surface {
+            Some(_) => {
+                picked.context("Failed to find suitable GPU (graphics+present+swapchain)")?
+            }
+            None => picked.context("Failed to find suitable GPU (graphics)")?,
+        };
 
         let priorities = [1.0_f32];
 
         let mut unique_families = vec![queues.graphics_family];
         if !queues.same_family() {
-            unique_families.push(queues.present_family);
+            unique_families.extend(queues.present_family);
+        }
+        if let Some(transfer) = queues
+            .transfer_family
+            .filter(|fam| !unique_families.contains(fam))
+        {
+            unique_families.push(transfer);
         }
 
         let queue_infos: Vec<vk::DeviceQueueCreateInfo> = unique_families
@@ -57,7 +99,10 @@ impl Device {
                 == b"VK_KHR_portability_subset"
         });
 
-        let mut device_exts: Vec<*const i8> = vec![ash::khr::swapchain::NAME.as_ptr()];
+        let mut device_exts: Vec<*const i8> = Vec::new();
+        if surface.is_some() {
+            device_exts.push(ash::khr::swapchain::NAME.as_ptr());
+        }
 
         // Keep CString alive until create_device()
         let portability_subset_name = std::ffi::CString::new("VK_KHR_portability_subset")?;
@@ -69,17 +114,60 @@ impl Device {
             }
         }
 
-        let create_info = vk::DeviceCreateInfo::default()
+        let dynamic_support = if dynamic_rendering {
+            let support = query_support(instance, physical);
+            if support.is_none() {
+                log::warn!("Dynamic rendering not supported, using render passes");
+            }
+            support
+        } else {
+            None
+        };
+        if dynamic_support == Some(DynamicRenderingSupport::Extension) {
+            device_exts.push(ash::khr::dynamic_rendering::NAME.as_ptr());
+        }
+
+        // ---- optional core features ----
+        let supported = unsafe { instance.get_physical_device_features(physical) };
+        let features = vk::PhysicalDeviceFeatures::default()
+            .sampler_anisotropy(supported.sampler_anisotropy == vk::TRUE);
+
+        // same struct for the 1.3 feature and the KHR extension
+        let mut dynamic_features =
+            vk::PhysicalDeviceDynamicRenderingFeatures::default().dynamic_rendering(true);
+
+        let mut create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_infos)
-            .enabled_extension_names(&device_exts);
+            .enabled_extension_names(&device_exts)
+            .enabled_features(&features);
+        if dynamic_support.is_some() {
+            create_info = create_info.push_next(&mut dynamic_features);
+        }
 
         let device = unsafe { instance.create_device(physical, &create_info, None) }
             .context("Failed to create logical device")?;
 
+        let dynamic_rendering = dynamic_support.map(|support| {
+            log::info!("Using dynamic rendering ({:?})", support);
+            match support {
+                DynamicRenderingSupport::Core => DynamicRendering::Core,
+                DynamicRenderingSupport::Extension => DynamicRendering::Khr(
+                    ash::khr::dynamic_rendering::Device::new(instance, &device),
+                ),
+            }
+        });
+
         let graphics_queue = unsafe { device.get_device_queue(queues.graphics_family, 0) };
-        let present_queue = unsafe { device.get_device_queue(queues.present_family, 0) };
+        let present_queue = queues
+            .present_family
+            .map(|fam| unsafe { device.get_device_queue(fam, 0) });
+        let transfer_queue = unsafe { device.get_device_queue(queues.upload_family(), 0) };
+        if let Some(fam) = queues.transfer_family {
+            log::info!("Using transfer queue family {}", fam);
+        }
 
         let memory_properties = unsafe { instance.get_physical_device_memory_properties(physical) };
+        let properties = unsafe { instance.get_physical_device_properties(physical) };
 
         let upload_pool_info = vk::CommandPoolCreateInfo::default()
             .queue_family_index(queues.graphics_family)
@@ -90,6 +178,9 @@ impl Device {
         let fence_info = vk::FenceCreateInfo::default();
         let upload_fence = unsafe { device.create_fence(&fence_info, None)? };
 
+        let pipeline_cache = PipelineCache::new(&device, &properties)?;
+        let allocator = GpuAllocator::new(&device, memory_properties, &properties);
+
         Ok(Self {
             instance: instance.clone(), // ✅ FIXED
             physical,
@@ -97,7 +188,13 @@ impl Device {
             queues,
             graphics_queue,
             present_queue,
+            transfer_queue,
             memory_properties,
+            properties,
+            features,
+            dynamic_rendering,
+            pipeline_cache,
+            allocator,
             upload_pool,
             upload_fence,
         })
@@ -117,6 +214,53 @@ impl Device {
         )
     }
 
+    /// Depth format that can also be sampled with linear filtering (for
+    /// shadow maps). D16 is guaranteed to qualify.
+    pub fn pick_shadow_depth_format(&self) -> Result<vk::Format> {
+        self.find_supported_format(
+            &[vk::Format::D32_SFLOAT, vk::Format::D16_UNORM],
+            vk::ImageTiling::OPTIMAL,
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )
+    }
+
+    /// Highest sample count `<= requested` usable for both color and depth
+    /// framebuffer attachments. Always at least `TYPE_1`.
+    pub fn clamp_sample_count(&self, requested: u32) -> vk::SampleCountFlags {
+        let limits = &self.properties.limits;
+        let supported =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+        [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|&count| count.as_raw() <= requested && supported.contains(count))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+    }
+
+    /// True if `format` can be the source and destination of a linear
+    /// `cmd_blit_image` (needed for mipmap generation).
+    pub fn supports_linear_blit(&self, format: vk::Format) -> bool {
+        let props = unsafe {
+            self.instance
+                .get_physical_device_format_properties(self.physical, format)
+        };
+
+        props.optimal_tiling_features.contains(
+            vk::FormatFeatureFlags::BLIT_SRC
+                | vk::FormatFeatureFlags::BLIT_DST
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )
+    }
+
     // ---------- private helpers ----------
 
     fn find_supported_format(
@@ -154,13 +298,14 @@ impl Device {
 
     fn find_queue_families(
         instance: &Instance,
-        surface: &Surface,
+        surface: Option<&Surface>,
         pd: vk::PhysicalDevice,
     ) -> Result<Option<QueueFamilyIndices>> {
         let props = unsafe { instance.get_physical_device_queue_family_properties(pd) };
 
         let mut graphics = None;
         let mut present = None;
+        let mut transfer: Option<(u32, vk::QueueFlags)> = None;
 
         for (i, p) in props.iter().enumerate() {
             let idx = i as u32;
@@ -169,6 +314,22 @@ impl Device {
                 graphics = Some(idx);
             }
 
+            // transfer without graphics, preferring families without compute
+            let flags = p.queue_flags;
+            if flags.contains(vk::QueueFlags::TRANSFER)
+                && !flags.contains(vk::QueueFlags::GRAPHICS)
+                && transfer.is_none_or(|(_, best)| {
+                    best.contains(vk::QueueFlags::COMPUTE)
+                        && !flags.contains(vk::QueueFlags::COMPUTE)
+                })
+            {
+                transfer = Some((idx, flags));
+            }
+
+            let Some(surface) = surface else {
+                continue;
+            };
+
             let present_support = unsafe {
                 surface
                     .loader
@@ -179,10 +340,18 @@ impl Device {
             }
         }
 
-        match (graphics, present) {
-            (Some(g), Some(p)) => Ok(Some(QueueFamilyIndices {
+        let transfer_family = transfer.map(|(idx, _)| idx);
+
+        match (graphics, present, surface) {
+            (Some(g), Some(p), Some(_)) => Ok(Some(QueueFamilyIndices {
+                graphics_family: g,
+                present_family: Some(p),
+                transfer_family,
+            })),
+            (Some(g), _, None) => Ok(Some(QueueFamilyIndices {
                 graphics_family: g,
-                present_family: p,
+                present_family: None,
+                transfer_family,
             })),
             _ => Ok(None),
         }
@@ -192,6 +361,9 @@ impl Device {
 impl Drop for Device {
     fn drop(&mut self) {
         unsafe {
+            self.pipeline_cache.destroy(&self.device);
+            self.allocator.log_stats();
+            self.allocator.destroy();
             self.device.destroy_fence(self.upload_fence, None);
             self.device.destroy_command_pool(self.upload_pool, None);
             self.device.destroy_device(None);
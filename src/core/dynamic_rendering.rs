@@ -0,0 +1,73 @@
+use ash::{Instance, vk};
+
+/// Where `vkCmdBeginRendering` comes from: Vulkan 1.3 core, or the
+/// `VK_KHR_dynamic_rendering` extension on 1.2 devices.
+#[derive(Clone)]
+pub enum DynamicRendering {
+    Core,
+    Khr(ash::khr::dynamic_rendering::Device),
+}
+
+impl DynamicRendering {
+    pub unsafe fn cmd_begin_rendering(
+        &self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        info: &vk::RenderingInfo,
+    ) {
+        unsafe {
+            match self {
+                DynamicRendering::Core => device.cmd_begin_rendering(cmd, info),
+                DynamicRendering::Khr(loader) => loader.cmd_begin_rendering(cmd, info),
+            }
+        }
+    }
+
+    pub unsafe fn cmd_end_rendering(&self, device: &ash::Device, cmd: vk::CommandBuffer) {
+        unsafe {
+            match self {
+                DynamicRendering::Core => device.cmd_end_rendering(cmd),
+                DynamicRendering::Khr(loader) => loader.cmd_end_rendering(cmd),
+            }
+        }
+    }
+}
+
+/// How a physical device can do dynamic rendering, before the logical
+/// device exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DynamicRenderingSupport {
+    Core,
+    /// Needs `VK_KHR_dynamic_rendering` enabled.
+    Extension,
+}
+
+pub fn query_support(
+    instance: &Instance,
+    physical: vk::PhysicalDevice,
+) -> Option<DynamicRenderingSupport> {
+    let properties = unsafe { instance.get_physical_device_properties(physical) };
+
+    let support = if properties.api_version >= vk::API_VERSION_1_3 {
+        DynamicRenderingSupport::Core
+    } else {
+        // the extension's dependencies are core in 1.2
+        if properties.api_version < vk::API_VERSION_1_2 {
+            return None;
+        }
+        let exts = unsafe { instance.enumerate_device_extension_properties(physical) }.ok()?;
+        let has_ext = exts.iter().any(|e| unsafe {
+            std::ffi::CStr::from_ptr(e.extension_name.as_ptr()) == ash::khr::dynamic_rendering::NAME
+        });
+        if !has_ext {
+            return None;
+        }
+        DynamicRenderingSupport::Extension
+    };
+
+    let mut dynamic = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+    let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut dynamic);
+    unsafe { instance.get_physical_device_features2(physical, &mut features) };
+
+    (dynamic.dynamic_rendering == vk::TRUE).then_some(support)
+}
@@ -8,16 +8,28 @@ pub struct VkInstance {
 }
 
 impl VkInstance {
-    pub fn new(entry: &Entry, display_handle: raw_window_handle::RawDisplayHandle) -> Result<Self> {
+    /// `display_handle == None` creates a headless instance without any
+    /// surface extensions.
+    pub fn new(
+        entry: &Entry,
+        display_handle: Option<raw_window_handle::RawDisplayHandle>,
+    ) -> Result<Self> {
         let app_name = CString::new("vulkan-rust-playground")?;
         let engine_name = CString::new("no-engine")?;
 
         let app_info = vk::ApplicationInfo::default()
             .application_name(&app_name)
             .engine_name(&engine_name)
-            .api_version(vk::make_api_version(0, 1, 2, 0));
+            // 1.3 where the device has it (dynamic rendering); the effective
+            // version is still capped by each device
+            .api_version(vk::make_api_version(0, 1, 3, 0));
 
-        let mut extensions = ash_window::enumerate_required_extensions(display_handle)?.to_vec();
+        let mut extensions = match display_handle {
+            Some(display_handle) => {
+                ash_window::enumerate_required_extensions(display_handle)?.to_vec()
+            }
+            None => Vec::new(),
+        };
 
         // 1. ADD THIS: Mandatory extension for macOS/MoltenVK
         // These are the standard names for these extensions in ash
@@ -32,7 +44,18 @@ impl VkInstance {
         let mut enabled_layers = Vec::new();
         #[cfg(debug_assertions)]
         {
-            enabled_layers.push(CString::new("VK_LAYER_KHRONOS_validation")?);
+            // CI boxes (lavapipe) often ship without the SDK layers.
+            let validation = CString::new("VK_LAYER_KHRONOS_validation")?;
+            let available = unsafe { entry.enumerate_instance_layer_properties()? };
+            let has_validation = available.iter().any(|l| unsafe {
+                std::ffi::CStr::from_ptr(l.layer_name.as_ptr()) == validation.as_c_str()
+            });
+
+            if has_validation {
+                enabled_layers.push(validation);
+            } else {
+                log::warn!("VK_LAYER_KHRONOS_validation not available, running without it");
+            }
         }
         let layer_ptrs: Vec<*const i8> = enabled_layers
             .iter()
@@ -1,7 +1,9 @@
 pub mod debug;
 pub mod device;
+pub mod dynamic_rendering;
 pub mod entry;
 pub mod instance;
+pub mod pipeline_cache;
 pub mod queues;
 pub mod surface;
 pub mod swapchain;
@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+use ash::vk;
+use std::{fs, path::PathBuf};
+
+/// `VkPipelineCacheHeaderVersionOne`: headerSize, headerVersion, vendorID,
+/// deviceID, pipelineCacheUUID.
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// Pipeline cache shared by every pipeline the renderer creates, persisted
+/// between runs in the per-user cache directory.
+pub struct PipelineCache {
+    pub cache: vk::PipelineCache,
+    /// `None` if no cache directory could be determined; the cache then
+    /// only lives for this run.
+    path: Option<PathBuf>,
+}
+
+impl PipelineCache {
+    /// Creates the cache, seeded from disk when the stored blob was written
+    /// by this driver and device. Stale or unreadable blobs are discarded.
+    pub fn new(device: &ash::Device, properties: &vk::PhysicalDeviceProperties) -> Result<Self> {
+        let path = cache_file();
+
+        let data = path
+            .as_ref()
+            .and_then(|p| fs::read(p).ok())
+            .filter(|blob| {
+                let valid = header_matches(blob, properties);
+                if !valid {
+                    log::info!("Discarding pipeline cache from another driver or device");
+                }
+                valid
+            })
+            .unwrap_or_default();
+
+        let info = vk::PipelineCacheCreateInfo::default().initial_data(&data);
+        let cache = match unsafe { device.create_pipeline_cache(&info, None) } {
+            Ok(cache) => cache,
+            // some drivers reject blobs they wrote themselves; start empty
+            Err(e) if !data.is_empty() => {
+                log::warn!("Pipeline cache rejected ({}), starting empty", e);
+                let empty = vk::PipelineCacheCreateInfo::default();
+                unsafe { device.create_pipeline_cache(&empty, None)? }
+            }
+            Err(e) => return Err(e).context("Failed to create pipeline cache"),
+        };
+
+        if !data.is_empty() {
+            log::info!("Loaded pipeline cache ({} bytes)", data.len());
+        }
+
+        Ok(Self { cache, path })
+    }
+
+    /// Writes the cache contents to disk.
+    pub fn save(&self, device: &ash::Device) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let data = unsafe { device.get_pipeline_cache_data(self.cache)? };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+
+        // write then rename, so a crash never leaves a truncated blob behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, &data).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+
+        log::info!("Saved pipeline cache ({} bytes)", data.len());
+        Ok(())
+    }
+
+    /// Saves (logging failures) and destroys the cache.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        if let Err(e) = self.save(device) {
+            log::warn!("Pipeline cache not saved: {:#}", e);
+        }
+        unsafe {
+            device.destroy_pipeline_cache(self.cache, None);
+        }
+        self.cache = vk::PipelineCache::null();
+    }
+}
+
+/// True if `blob` starts with a version one header for this exact device
+/// and driver (vendor, device and cache UUID).
+pub fn header_matches(blob: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+    if blob.len() < HEADER_SIZE {
+        return false;
+    }
+
+    let word = |i: usize| u32::from_ne_bytes(blob[i * 4..i * 4 + 4].try_into().unwrap());
+    let header_size = word(0);
+    let version = word(1);
+
+    header_size as usize >= HEADER_SIZE
+        && version == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        && word(2) == properties.vendor_id
+        && word(3) == properties.device_id
+        && blob[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+}
+
+/// `<user cache dir>/<crate name>/pipeline_cache.bin`.
+fn cache_file() -> Option<PathBuf> {
+    let env_dir = |name: &str| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+
+    let base = if cfg!(windows) {
+        env_dir("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library/Caches"))
+    } else {
+        env_dir("XDG_CACHE_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".cache")))
+    };
+
+    base.map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("pipeline_cache.bin"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x2484,
+            pipeline_cache_uuid: [7; vk::UUID_SIZE],
+            ..Default::default()
+        }
+    }
+
+    fn header(vendor: u32, device: u32, uuid: [u8; vk::UUID_SIZE]) -> Vec<u8> {
+        let mut blob = Vec::new();
+        blob.extend_from_slice(&(HEADER_SIZE as u32).to_ne_bytes());
+        blob.extend_from_slice(&1u32.to_ne_bytes());
+        blob.extend_from_slice(&vendor.to_ne_bytes());
+        blob.extend_from_slice(&device.to_ne_bytes());
+        blob.extend_from_slice(&uuid);
+        blob.extend_from_slice(&[0xab; 64]); // driver payload
+        blob
+    }
+
+    #[test]
+    fn header_from_the_same_device_matches() {
+        assert!(header_matches(
+            &header(0x10de, 0x2484, [7; 16]),
+            &properties()
+        ));
+    }
+
+    #[test]
+    fn stale_headers_are_rejected() {
+        let props = properties();
+        assert!(!header_matches(&header(0x1002, 0x2484, [7; 16]), &props));
+        assert!(!header_matches(&header(0x10de, 0x1111, [7; 16]), &props));
+        assert!(!header_matches(&header(0x10de, 0x2484, [8; 16]), &props));
+        assert!(!header_matches(
+            &header(0x10de, 0x2484, [7; 16])[..20],
+            &props
+        ));
+        assert!(!header_matches(&[], &props));
+
+        let mut bad_version = header(0x10de, 0x2484, [7; 16]);
+        bad_version[4] = 2;
+        assert!(!header_matches(&bad_version, &props));
+    }
+}
@@ -1,11 +1,21 @@
 #[derive(Clone, Copy, Debug)]
 pub struct QueueFamilyIndices {
     pub graphics_family: u32,
-    pub present_family: u32,
+    /// `None` when running headless (no surface to present to).
+    pub present_family: Option<u32>,
+    /// Transfer-only family for uploads, if the device has one.
+    pub transfer_family: Option<u32>,
 }
 
 impl QueueFamilyIndices {
     pub fn same_family(&self) -> bool {
-        self.graphics_family == self.present_family
+        self.present_family
+            .is_none_or(|present| present == self.graphics_family)
+    }
+
+    /// Family uploads are submitted to: the dedicated transfer family, or
+    /// graphics.
+    pub fn upload_family(&self) -> u32 {
+        self.transfer_family.unwrap_or(self.graphics_family)
     }
 }
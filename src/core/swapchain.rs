@@ -10,6 +10,8 @@ pub struct Swapchain {
     pub extent: vk::Extent2D,
     pub images: Vec<vk::Image>,
     pub image_views: Vec<vk::ImageView>,
+    pub image_usage: vk::ImageUsageFlags,
+    pub present_mode: vk::PresentModeKHR,
 }
 
 impl Swapchain {
@@ -68,12 +70,26 @@ impl Swapchain {
             image_count = image_count.min(caps.max_image_count);
         }
 
+        // TRANSFER_SRC lets the renderer read frames back (capture_frame)
+        let mut image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        if caps
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+
         let (sharing_mode, queue_family_indices) = if dev.queues.same_family() {
             (vk::SharingMode::EXCLUSIVE, vec![])
         } else {
             (
                 vk::SharingMode::CONCURRENT,
-                vec![dev.queues.graphics_family, dev.queues.present_family],
+                vec![
+                    dev.queues.graphics_family,
+                    dev.queues
+                        .present_family
+                        .context("swapchain requires a present queue family")?,
+                ],
             )
         };
 
@@ -84,7 +100,7 @@ impl Swapchain {
             .image_color_space(surface_format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(image_usage)
             .image_sharing_mode(sharing_mode)
             .queue_family_indices(&queue_family_indices)
             .pre_transform(caps.current_transform)
@@ -121,6 +137,8 @@ impl Swapchain {
             extent,
             images,
             image_views,
+            image_usage,
+            present_mode,
         })
     }
 
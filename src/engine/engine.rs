@@ -1,29 +1,65 @@
+use crate::assets::shader_reload::ShaderWatcher;
 use crate::engine::game_loop::GameLoop;
+use crate::engine::stats::FrameStats;
 use crate::engine::time::Time;
 use crate::gfx::context::VkContext;
 use crate::gfx::swapchain::SwapchainManager;
 use crate::input::input_state::InputState;
 use crate::platform::window_glfw::GlfwWindow;
+use crate::renderer::capture::CapturedFrame;
+use crate::renderer::debug_draw::DebugDraw;
 use crate::renderer::error::RenderError;
+use crate::renderer::graph::PassInputs;
+use crate::renderer::render_types::{FrameItems, RenderItem};
 use crate::renderer::renderer::Renderer;
+use crate::renderer::text::TextDraw;
+use crate::scene::material_store::MaterialStore;
 use crate::utils::config::Config;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use ash::vk;
+use glam::{Vec2, Vec3};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 
+/// Time step of headless runs, so their frames (and golden images) don't
+/// depend on how fast the machine renders.
+const HEADLESS_DT: f32 = 1.0 / 60.0;
+
+/// Glyph scale of the stats overlay.
+const STATS_SCALE: f32 = 2.0;
+
 pub struct Engine {
-    pub window: GlfwWindow,
+    /// `None` when running headless.
+    pub window: Option<GlfwWindow>,
     pub config: Config,
 
     pub context: VkContext,
-    pub swapchain: SwapchainManager,
+    /// `None` when running headless; the renderer owns its target instead.
+    pub swapchain: Option<SwapchainManager>,
     pub renderer: Renderer,
+    /// Outlives renderer rebuilds; its set layout is baked into the pipelines.
+    pub materials: MaterialStore,
+    /// Only with `renderer.shader_hot_reload`.
+    pub shader_watcher: Option<ShaderWatcher>,
+    /// Debug lines for the next frame, see `DebugDraw`.
+    pub debug: DebugDraw,
+    /// Screen text for the next frame, see `TextDraw`.
+    pub text: TextDraw,
+    /// Fed by `run`; shown with `debug.stats`.
+    pub stats: FrameStats,
 
     pub input: InputState,
     pub time: Time,
+    /// Step of the current frame (`HEADLESS_DT` when headless); timed debug
+    /// lines count down by it.
+    pub dt: f32,
 }
 
 impl Engine {
     pub fn new(cfg: Config) -> Result<Self> {
+        if cfg.renderer.headless {
+            return Self::new_headless(cfg);
+        }
+
         let window = GlfwWindow::new(cfg.window.width, cfg.window.height, &cfg.window.title)?;
 
         let display_handle = window
@@ -38,51 +74,140 @@ impl Engine {
             .map_err(|e| anyhow::anyhow!("window_handle error: {:?}", e))?
             .as_raw();
 
-        let context = VkContext::new(display_handle, window_handle)?;
+        let context = VkContext::new(
+            display_handle,
+            window_handle,
+            cfg.renderer.dynamic_rendering,
+        )?;
         let (fb_w, fb_h) = window.framebuffer_size();
         let swapchain = SwapchainManager::new(&context, &cfg, fb_w, fb_h)?;
+        let materials = MaterialStore::new(&context.device)?;
         let renderer = Renderer::new(
             &context.device,
             &swapchain.swapchain,
-            cfg.renderer.frames_in_flight,
+            materials.layout(),
+            &cfg,
         )?;
 
+        let shader_watcher = create_shader_watcher(&cfg);
+        let debug = DebugDraw::new(cfg.debug.draw);
+        let text = TextDraw::new(&context.device)?;
+
+        Ok(Self {
+            window: Some(window),
+            config: cfg,
+            context,
+            swapchain: Some(swapchain),
+            renderer,
+            materials,
+            shader_watcher,
+            debug,
+            text,
+            stats: FrameStats::new(),
+            input: InputState::default(),
+            time: Time::new(),
+            dt: 0.0,
+        })
+    }
+
+    /// No window, surface or swapchain: renders `window.width x window.height`
+    /// frames into an offscreen image (works on lavapipe / CI).
+    fn new_headless(cfg: Config) -> Result<Self> {
+        let context = VkContext::new_headless(cfg.renderer.dynamic_rendering)?;
+
+        let extent = vk::Extent2D {
+            width: cfg.window.width,
+            height: cfg.window.height,
+        };
+        let materials = MaterialStore::new(&context.device)?;
+        let renderer = Renderer::new_offscreen(&context.device, extent, materials.layout(), &cfg)?;
+
+        log::info!(
+            "Headless mode: {}x{}, {} frames",
+            extent.width,
+            extent.height,
+            cfg.renderer.headless_frames
+        );
+
+        let shader_watcher = create_shader_watcher(&cfg);
+        let debug = DebugDraw::new(cfg.debug.draw);
+        let text = TextDraw::new(&context.device)?;
+
         Ok(Self {
-            window,
+            window: None,
             config: cfg,
             context,
-            swapchain,
+            swapchain: None,
             renderer,
+            materials,
+            shader_watcher,
+            debug,
+            text,
+            stats: FrameStats::new(),
             input: InputState::default(),
             time: Time::new(),
+            dt: 0.0,
         })
     }
 
+    pub fn is_headless(&self) -> bool {
+        self.context.is_headless()
+    }
+
+    /// Extent of whatever the renderer draws into (swapchain or offscreen).
+    pub fn extent(&self) -> vk::Extent2D {
+        match &self.swapchain {
+            Some(swapchain) => swapchain.extent(),
+            None => self.renderer.extent,
+        }
+    }
+
+    /// Always `false` when headless.
+    pub fn key_down(&self, key: glfw::Key) -> bool {
+        self.window.as_ref().is_some_and(|w| w.key_down(key))
+    }
+
     pub fn run<G: GameLoop>(&mut self, game: &mut G) -> Result<()> {
-        while !self.window.should_close() {
-            self.window.poll_events();
+        if self.is_headless() {
+            return self.run_headless(game, self.config.renderer.headless_frames);
+        }
+
+        loop {
+            let window = self
+                .window
+                .as_mut()
+                .context("windowed run without a window")?;
+            if window.should_close() {
+                break;
+            }
+            window.poll_events();
 
             let dt = self.time.tick();
+            self.dt = dt;
+            self.stats.record(dt);
 
-            self.input.update(&self.window, &self.config.controls);
+            self.input.update(window, &self.config.controls);
 
             let input = self.input; // COPY
             game.update(self, &input, dt)?;
 
-            if self.window.is_minimized() {
+            let window = self
+                .window
+                .as_mut()
+                .context("windowed run without a window")?;
+            if window.is_minimized() {
                 std::thread::sleep(std::time::Duration::from_millis(16));
                 continue;
             }
 
             // swapchain resize handling stays in engine
-            if self.window.take_resized() {
-                let (w, h) = self.window.framebuffer_size();
-                if self.swapchain.recreate(&self.context, w, h)? {
-                    self.renderer
-                        .rebuild_for_swapchain(&self.context, &self.swapchain.swapchain)?;
-                }
+            if window.take_resized() {
+                let (w, h) = window.framebuffer_size();
+                self.recreate_swapchain(w, h)?;
             }
 
+            self.reload_changed_shaders()?;
+
             // game drives what to render
             match game.render(self) {
                 Ok(()) => {}
@@ -96,30 +221,161 @@ impl Engine {
         Ok(())
     }
 
+    /// Runs exactly `frames` update/render iterations with no input,
+    /// stepping `HEADLESS_DT` each instead of the wall clock.
+    pub fn run_headless<G: GameLoop>(&mut self, game: &mut G, frames: u32) -> Result<()> {
+        for _ in 0..frames {
+            self.dt = HEADLESS_DT;
+            self.stats.record(HEADLESS_DT);
+
+            let input = InputState::default();
+            game.update(self, &input, HEADLESS_DT)?;
+            game.render(self)?;
+        }
+
+        unsafe {
+            self.context.device.device.device_wait_idle()?;
+        }
+        Ok(())
+    }
+
     pub fn draw_frame(
         &mut self,
         globals: crate::renderer::render_types::FrameGlobals,
-        items: &[crate::renderer::render_types::RenderItem],
+        items: &[RenderItem],
+        shadow_casters: &[RenderItem],
     ) -> Result<()> {
-        match self.renderer.draw_frame(
-            &self.context.device,
-            &self.context.surface,
-            &self.swapchain.swapchain,
-            globals,
+        let items = FrameItems {
             items,
-        ) {
+            shadow_casters,
+        };
+        if self.config.debug.stats {
+            self.draw_stats();
+        }
+
+        let dev = &self.context.device;
+        let mut inputs = PassInputs::new();
+        if let Some(lines) = self.debug.prepare(dev, &self.renderer)? {
+            inputs.insert(lines);
+        }
+        if let Some(text) = self.text.prepare(dev, &self.renderer)? {
+            inputs.insert(text);
+        }
+
+        let result = match self.swapchain.as_ref() {
+            Some(swapchain) => self.renderer.draw_frame(
+                dev,
+                &swapchain.swapchain,
+                &self.materials,
+                globals,
+                items,
+                inputs,
+            ),
+            None => {
+                self.renderer
+                    .draw_frame_offscreen(dev, &self.materials, globals, items, inputs)
+            }
+        };
+        self.debug.end_frame(self.dt);
+        self.text.end_frame();
+
+        match result {
             Ok(()) => Ok(()),
             Err(RenderError::SwapchainOutOfDate) => {
-                let (w, h) = self.window.framebuffer_size();
-                if self.swapchain.recreate(&self.context, w, h)? {
-                    self.renderer
-                        .rebuild_for_swapchain(&self.context, &self.swapchain.swapchain)?;
+                if let Some(window) = &self.window {
+                    let (w, h) = window.framebuffer_size();
+                    self.recreate_swapchain(w, h)?;
                 }
                 Ok(())
             }
             Err(e) => Err(e.into()),
         }
     }
+
+    /// Resizes the renderer's frame ring (see `Renderer::set_frames_in_flight`)
+    /// and keeps `config.renderer.frames_in_flight` in sync.
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: usize) -> Result<()> {
+        self.renderer
+            .set_frames_in_flight(&self.context.device, frames_in_flight)?;
+        self.config.renderer.frames_in_flight = self.renderer.frames_in_flight;
+        Ok(())
+    }
+
+    /// Frame rate, frame time, last frame's draw count, frames in flight,
+    /// extent and present mode, right-aligned in the top-right corner.
+    fn draw_stats(&mut self) {
+        let (extent, present_mode) = match &self.swapchain {
+            Some(s) => (
+                s.swapchain.extent,
+                format!("{:?}", s.swapchain.present_mode),
+            ),
+            None => (self.renderer.extent, "offscreen".to_string()),
+        };
+        let stats = &self.stats;
+        let text = format!(
+            "{:.0} fps  {:.2} ms (max {:.2})\n{} draws  {} frames in flight\n{}x{} {}",
+            stats.fps,
+            stats.frame_ms,
+            stats.max_frame_ms,
+            self.renderer.draw_count,
+            self.renderer.frames_in_flight,
+            extent.width,
+            extent.height,
+            present_mode
+        );
+        let size = self.text.measure(&text, STATS_SCALE);
+        let pos = Vec2::new((extent.width as f32 - size.x - 8.0).max(8.0), 8.0);
+        self.text
+            .text(pos, &text, STATS_SCALE, Vec3::new(1.0, 1.0, 0.6));
+    }
+
+    /// Reads back the last rendered frame (see `Renderer::capture_frame`).
+    pub fn capture_frame(&self) -> Result<CapturedFrame> {
+        self.renderer.capture_frame(
+            &self.context.device,
+            self.swapchain.as_ref().map(|s| &s.swapchain),
+        )
+    }
+
+    /// Recompiles shaders the watcher saw change and hands them to the
+    /// renderer. Compile errors are logged and the old shaders stay active.
+    fn reload_changed_shaders(&mut self) -> Result<()> {
+        let Some(watcher) = self.shader_watcher.as_mut() else {
+            return Ok(());
+        };
+
+        let mut updates = Vec::new();
+        for path in watcher.poll() {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+
+            match watcher.compile(&path) {
+                Ok(spv) => {
+                    log::info!("Recompiled {}", path.display());
+                    updates.push((name.to_string(), spv));
+                }
+                Err(e) => log::error!("Shader compile failed: {:#}", e),
+            }
+        }
+
+        if updates.is_empty() {
+            return Ok(());
+        }
+        self.renderer.reload_shaders(&self.context.device, updates)
+    }
+
+    fn recreate_swapchain(&mut self, width: u32, height: u32) -> Result<()> {
+        let Some(swapchain) = self.swapchain.as_mut() else {
+            return Ok(());
+        };
+
+        if swapchain.recreate(&self.context, width, height)? {
+            self.renderer
+                .rebuild_for_swapchain(&self.context, &swapchain.swapchain)?;
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Engine {
@@ -128,6 +384,25 @@ impl Drop for Engine {
             self.context.device.device.device_wait_idle().ok();
         }
         self.renderer.destroy(&self.context.device.device);
-        self.swapchain.destroy(&mut self.context.device.device);
+        self.debug.destroy(&self.context.device.device);
+        self.text.destroy(&self.context.device.device);
+        self.materials.destroy(&self.context.device.device);
+        if let Some(swapchain) = self.swapchain.as_mut() {
+            swapchain.destroy(&self.context.device.device);
+        }
+    }
+}
+
+fn create_shader_watcher(cfg: &Config) -> Option<ShaderWatcher> {
+    if !cfg.renderer.shader_hot_reload {
+        return None;
+    }
+
+    match ShaderWatcher::new("shaders") {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            log::warn!("Shader hot-reload disabled: {:#}", e);
+            None
+        }
     }
 }
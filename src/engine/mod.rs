@@ -3,6 +3,7 @@ pub mod camera_rig;
 pub mod camera_system;
 
 pub mod time;
+pub mod stats;
 pub mod engine;
 pub mod game_loop;
 
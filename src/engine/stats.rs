@@ -0,0 +1,64 @@
+/// Seconds of frames averaged into each reading.
+const WINDOW: f32 = 0.5;
+
+/// Frame rate and frame time for the stats overlay. Readings are averaged
+/// over `WINDOW` seconds so they stay legible; all zero until the first
+/// window has passed.
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    pub fps: f32,
+    /// Mean frame time, milliseconds.
+    pub frame_ms: f32,
+    /// Slowest frame of the window, milliseconds.
+    pub max_frame_ms: f32,
+    frames: u32,
+    elapsed: f32,
+    slowest: f32,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `dt` is the frame time returned by `Time::tick`.
+    pub fn record(&mut self, dt: f32) {
+        self.frames += 1;
+        self.elapsed += dt;
+        self.slowest = self.slowest.max(dt);
+
+        if self.elapsed >= WINDOW {
+            self.fps = self.frames as f32 / self.elapsed;
+            self.frame_ms = self.elapsed * 1000.0 / self.frames as f32;
+            self.max_frame_ms = self.slowest * 1000.0;
+
+            self.frames = 0;
+            self.elapsed = 0.0;
+            self.slowest = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_over_the_window() {
+        let mut stats = FrameStats::new();
+        for _ in 0..10 {
+            stats.record(0.01);
+        }
+        // 0.1 s so far
+        assert_eq!(stats.fps, 0.0);
+
+        for _ in 0..39 {
+            stats.record(0.01);
+        }
+        stats.record(0.02);
+
+        assert!((stats.fps - 50.0 / 0.51).abs() < 1e-3);
+        assert!((stats.frame_ms - 10.2).abs() < 1e-3);
+        assert!((stats.max_frame_ms - 20.0).abs() < 1e-3);
+    }
+}
@@ -131,4 +131,9 @@ impl CharacterMotor {
             charge: 0.0,
         }
     }
+
+    /// Zero while grounded; the jump velocity while airborne.
+    pub fn velocity(&self) -> Vec3 {
+        self.vel
+    }
 }
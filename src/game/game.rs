@@ -1,7 +1,12 @@
 use anyhow::Result;
+use glam::Vec3;
 use glfw::Key;
+use std::path::Path;
 
+use crate::assets::gltf_import::load_gltf;
 use crate::assets::mesh;
+use crate::assets::obj::load_obj;
+use crate::assets::texture::TextureData;
 use crate::engine::camera::Camera;
 use crate::engine::camera_rig::{CameraRig, CameraTargetMode};
 use crate::engine::camera_system::CameraSystem;
@@ -9,26 +14,41 @@ use crate::engine::engine::Engine;
 use crate::engine::game_loop::GameLoop;
 
 use crate::game::character_controller::CharacterControllerSystem;
-use crate::renderer::render_types::FrameGlobals;
+use crate::renderer::material::{BlendMode, CullMode, Material};
+use crate::renderer::render_types::{DirectionalLight, FrameGlobals};
+use crate::renderer::shadow::fit_light_view_proj;
+use crate::renderer::texture::Texture;
+use crate::resources::sampler::SamplerDesc;
 use crate::scene::{
+    culling::{Aabb, CullStats, Frustum},
+    material_store::TextureId,
     mesh_store::MeshStore,
     scene::{Object, Scene},
     transform::Transform,
 };
+use crate::utils::config::ModelConfig;
 
 pub struct Game {
     pub scene: Scene,
     pub meshes: MeshStore,
     pub rig: CameraRig,
     pub motor: crate::game::character_controller::CharacterMotor,
+    /// Culling result of the last rendered frame.
+    pub cull_stats: CullStats,
+    /// Roots of `GameConfig::models`; the character can carry them.
+    pub props: Vec<usize>,
 }
 
+/// How far from the character's origin a prop's origin may be for `E` to
+/// pick it up.
+const PICKUP_RANGE: f32 = 2.0;
+
 impl Game {
     pub fn new(engine: &mut Engine) -> Result<Self> {
         let mut meshes = MeshStore::new();
 
         let floor_cpu = mesh::plane(engine.config.game.arena_size);
-        let floor_id = meshes.upload(&engine.renderer, &engine.context.device, &floor_cpu)?;
+        let floor_id = meshes.upload(&mut engine.renderer, &engine.context.device, &floor_cpu)?;
 
         let cube_cpu = mesh::cube();
         let cube_gpu = engine
@@ -36,6 +56,26 @@ impl Game {
             .upload_mesh(&engine.context.device, &cube_cpu)?;
         let cube_id = meshes.add(cube_gpu);
 
+        let floor_texture = floor_texture(engine)?;
+        let floor_mat = engine.materials.add(
+            &engine.context.device,
+            Material {
+                base_color_texture: Some(floor_texture),
+                ..Material::default()
+            },
+        )?;
+
+        // see-through cube next to the character, exercises the blended pass
+        let glass_mat = engine.materials.add(
+            &engine.context.device,
+            Material {
+                base_color: glam::vec4(0.6, 0.8, 1.0, 0.35),
+                blend: BlendMode::AlphaBlend,
+                cull: CullMode::Back,
+                ..Material::default()
+            },
+        )?;
+
         let camera = Camera {
             yaw: -90.0,
             pitch: 0.0,
@@ -46,24 +86,26 @@ impl Game {
             far: engine.config.camera.far,
         };
 
-        let floor = Object {
-            mesh: floor_id,
-            transform: Transform::identity(),
-        };
-
         let mut cube_tf = Transform::identity();
         cube_tf.position = glam::vec3(0.0, -0.5, 0.0);
 
-        let cube = Object {
-            mesh: cube_id,
-            transform: cube_tf,
-        };
+        let mut glass_tf = Transform::identity();
+        glass_tf.position = glam::vec3(2.0, -0.5, 0.0);
 
-        let scene = Scene {
-            camera,
-            objects: vec![floor, cube],
-            character: 1,
-        };
+        let mut scene = Scene::new(camera);
+        scene.add(Object::new(Some(floor_id), Transform::identity()).with_material(floor_mat));
+        scene.character = scene.add(Object::new(Some(cube_id), cube_tf));
+        // translucent marker floating over the character, moves with it
+        let mut marker_tf = Transform::identity();
+        marker_tf.position = glam::vec3(0.0, -0.9, 0.0);
+        marker_tf.scale = Vec3::splat(0.25);
+        scene.add_child(
+            scene.character,
+            Object::new(Some(cube_id), marker_tf).with_opacity(0.5),
+        );
+        scene.add(Object::new(Some(cube_id), glass_tf).with_material(glass_mat));
+        let props = spawn_models(engine, &mut scene, &mut meshes);
+        scene.update_transforms();
 
         let rig = CameraRig {
             yaw: -90.0,
@@ -79,10 +121,62 @@ impl Game {
             meshes,
             rig,
             motor,
+            cull_stats: CullStats::default(),
+            props,
         })
     }
 }
 
+impl Game {
+    /// Attaches the nearest prop within `PICKUP_RANGE` to the character,
+    /// keeping its world placement, and returns it. Does nothing while
+    /// carrying one.
+    fn pick_up(&mut self) -> Result<Option<usize>> {
+        let character = self.scene.character;
+        let carrying = self
+            .props
+            .iter()
+            .any(|&p| self.scene.object(p).parent() == Some(character));
+        if carrying {
+            return Ok(None);
+        }
+
+        let origin = world_position(self.scene.object(character));
+        let nearest = self
+            .props
+            .iter()
+            .map(|&p| (p, world_position(self.scene.object(p)).distance(origin)))
+            .filter(|&(_, distance)| distance <= PICKUP_RANGE)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((prop, _)) = nearest {
+            self.scene.set_parent(prop, Some(character), true)?;
+        }
+        Ok(nearest.map(|(prop, _)| prop))
+    }
+
+    /// Detaches every carried prop, leaving it where it is.
+    fn put_down(&mut self) -> Result<()> {
+        let carried: Vec<usize> = self
+            .scene
+            .object(self.scene.character)
+            .children()
+            .iter()
+            .copied()
+            .filter(|c| self.props.contains(c))
+            .collect();
+
+        for prop in carried {
+            self.scene.detach(prop)?;
+        }
+        Ok(())
+    }
+}
+
+fn world_position(object: &Object) -> Vec3 {
+    object.world_matrix().w_axis.truncate()
+}
+
 impl GameLoop for Game {
     fn update(
         &mut self,
@@ -90,6 +184,21 @@ impl GameLoop for Game {
         input: &crate::input::input_state::InputState,
         dt: f32,
     ) -> Result<()> {
+        // E picks up the nearest prop (flashing a marker around it), Q puts
+        // it down where it is
+        if engine.key_down(Key::E)
+            && let Some(prop) = self.pick_up()?
+        {
+            let center = world_position(self.scene.object(prop));
+            engine
+                .debug
+                .sphere(center, 0.5, Vec3::new(0.3, 0.6, 1.0))
+                .duration(0.5);
+        }
+        if engine.key_down(Key::Q) {
+            self.put_down()?;
+        }
+
         // character move
         CharacterControllerSystem::update(
             &mut self.scene,
@@ -102,43 +211,118 @@ impl GameLoop for Game {
         // camera rig controls (HJKL still)
         let speed_deg = engine.config.camera.orbit_speed_deg;
 
-        if engine.window.key_down(Key::H) {
+        if engine.key_down(Key::H) {
             self.rig.yaw -= speed_deg * dt;
         }
-        if engine.window.key_down(Key::L) {
+        if engine.key_down(Key::L) {
             self.rig.yaw += speed_deg * dt;
         }
-        if engine.window.key_down(Key::J) {
+        if engine.key_down(Key::J) {
             self.rig.pitch += speed_deg * dt;
         }
-        if engine.window.key_down(Key::K) {
+        if engine.key_down(Key::K) {
             self.rig.pitch -= speed_deg * dt;
         }
 
         // toggle follow/origin quickly (optional)
-        if engine.window.key_down(Key::O) {
+        if engine.key_down(Key::O) {
             self.rig.mode = CameraTargetMode::Origin;
         }
-        if engine.window.key_down(Key::P) {
+        if engine.key_down(Key::P) {
             self.rig.mode = CameraTargetMode::FollowCharacter;
         }
 
+        // 1-3: frames in flight, up to renderer.max_frames_in_flight
+        for (key, frames) in [(Key::Num1, 1), (Key::Num2, 2), (Key::Num3, 3)] {
+            if engine.key_down(key)
+                && frames != engine.renderer.frames_in_flight
+                && frames <= engine.renderer.max_frames_in_flight
+            {
+                engine.set_frames_in_flight(frames)?;
+            }
+        }
+
         // apply rig to camera
-        let character_pos = self.scene.objects[self.scene.character].transform.position;
+        let character_pos = self.scene.object(self.scene.character).transform.position;
 
         CameraSystem::update(&mut self.scene.camera, &mut self.rig, character_pos);
+
+        // gizmos: velocity (scaled to a quarter second of travel), the
+        // floor's extent, one unit tall (world up is -Y), a unit grid just
+        // above the floor, the pickup range and the character's axes
+        let half = engine.config.game.arena_size * 0.5;
+        let arena = Aabb {
+            min: Vec3::new(-half, -1.0, -half),
+            max: Vec3::new(half, 0.0, half),
+        };
+        engine.debug.aabb(&arena, Vec3::new(0.2, 0.8, 0.2));
+        engine
+            .debug
+            .line(
+                character_pos,
+                character_pos + self.motor.velocity() * 0.25,
+                Vec3::new(1.0, 0.8, 0.1),
+            )
+            .on_top();
+        engine.debug.grid(
+            Vec3::new(0.0, -0.01, 0.0),
+            half,
+            1.0,
+            Vec3::new(0.15, 0.15, 0.15),
+        );
+        engine
+            .debug
+            .sphere(character_pos, PICKUP_RANGE, Vec3::new(0.3, 0.6, 1.0));
+        engine
+            .debug
+            .axes(self.scene.object(self.scene.character).world_matrix(), 1.0);
+
+        self.scene.update_transforms();
         Ok(())
     }
 
     fn render(&mut self, engine: &mut Engine) -> Result<()> {
-        let extent = engine.swapchain.extent();
+        let extent = engine.extent();
         let aspect = extent.width as f32 / extent.height as f32;
 
         let view_proj = self.scene.camera.view_proj(aspect);
-        let globals = FrameGlobals { view_proj };
+        let lighting = &engine.config.lighting;
+        let light_dir = glam::Vec3::from(lighting.direction);
+        let shadows = &engine.config.shadows;
+        let light_view_proj = fit_light_view_proj(
+            light_dir,
+            &self.scene.camera,
+            aspect,
+            shadows.distance,
+            shadows.resolution,
+        );
+        let globals = FrameGlobals {
+            view_proj,
+            light_view_proj,
+            camera_pos: self.scene.camera.pos,
+            light: DirectionalLight {
+                direction: light_dir,
+                color: glam::Vec3::from(lighting.color) * lighting.intensity,
+                ambient: glam::Vec3::from(lighting.ambient),
+                specular: lighting.specular,
+                shininess: lighting.shininess,
+                shadow_bias: shadows.bias,
+            },
+        };
+
+        self.meshes
+            .collect_garbage(&engine.renderer, &engine.context.device.device);
+
+        let frustum = Frustum::from_view_proj(view_proj);
+        let (items, stats) = self.scene.render_items(&self.meshes, &frustum);
+        self.cull_stats = stats;
+        log::trace!("drawn {} / culled {}", stats.drawn, stats.culled);
 
-        let items = self.scene.render_items(&self.meshes);
-        engine.draw_frame(globals, &items)?;
+        // culled against the light, so casters out of view still shadow it
+        let light_frustum = Frustum::from_view_proj(light_view_proj);
+        let (casters, _) = self.scene.render_items(&self.meshes, &light_frustum);
+
+        engine.draw_frame(globals, &items, &casters)?;
 
         Ok(())
     }
@@ -151,3 +335,74 @@ impl Drop for Game {
         // For now, do it explicitly in main before engine drops, or store mesh destroy in engine.
     }
 }
+
+/// `GameConfig::floor_texture`, or a 2x2 checker per world unit (plane UVs
+/// repeat once per unit) if it is unset or fails to load.
+fn floor_texture(engine: &mut Engine) -> Result<TextureId> {
+    if let Some(path) = &engine.config.game.floor_texture {
+        match engine.materials.load_texture(&engine.context.device, path) {
+            Ok(id) => return Ok(id),
+            Err(e) => log::error!("Using the checkerboard floor: {:#}", e),
+        }
+    }
+
+    let checker = TextureData::checkerboard(2, 32, [200, 200, 200, 255], [90, 90, 100, 255]);
+    let checker = Texture::from_data(&engine.context.device, &checker, &SamplerDesc::default())?;
+    Ok(engine.materials.add_texture(checker))
+}
+
+/// Places `GameConfig::models` in the scene and returns their root
+/// objects. Models that fail to load are logged and skipped.
+fn spawn_models(engine: &mut Engine, scene: &mut Scene, meshes: &mut MeshStore) -> Vec<usize> {
+    let mut roots = Vec::new();
+    for model in engine.config.game.models.clone() {
+        match spawn_model(engine, scene, meshes, &model) {
+            Ok(root) => {
+                log::info!("Loaded model {}", model.path);
+                roots.push(root);
+            }
+            Err(e) => log::error!("Skipping model {}: {:#}", model.path, e),
+        }
+    }
+    roots
+}
+
+fn spawn_model(
+    engine: &mut Engine,
+    scene: &mut Scene,
+    meshes: &mut MeshStore,
+    model: &ModelConfig,
+) -> Result<usize> {
+    let mut root_tf = Transform::identity();
+    root_tf.position = Vec3::from(model.position);
+    root_tf.scale = Vec3::splat(model.scale);
+
+    let path = Path::new(&model.path);
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gltf" | "glb") => {
+            let imported = load_gltf(path)?;
+            let spawned = scene.instantiate(
+                &imported,
+                None,
+                meshes,
+                &mut engine.renderer,
+                &engine.context.device,
+            )?;
+            // the root only exists once the import succeeded, so a failed
+            // upload leaves nothing behind
+            let root = scene.add(Object::new(None, root_tf));
+            for idx in spawned {
+                if scene.object(idx).parent().is_none() {
+                    scene.set_parent(idx, Some(root), false)?;
+                }
+            }
+            Ok(root)
+        }
+        Some("obj") => {
+            let data = load_obj(path)?;
+            let mesh = meshes.upload(&mut engine.renderer, &engine.context.device, &data)?;
+            Ok(scene.add(Object::new(Some(mesh), root_tf)))
+        }
+        _ => anyhow::bail!("unsupported model format"),
+    }
+}
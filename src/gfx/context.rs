@@ -11,17 +11,23 @@ pub struct VkContext {
     pub entry: ash::Entry,
     pub instance: ash::Instance,
     pub debug: Option<DebugMessenger>,
-    pub surface: Surface,
+    /// `None` in headless mode.
+    pub surface: Option<Surface>,
     pub device: Device,
 }
 
 impl VkContext {
-    pub fn new(display: RawDisplayHandle, window: RawWindowHandle) -> Result<Self> {
+    /// `dynamic_rendering`: see `Device::new`.
+    pub fn new(
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+        dynamic_rendering: bool,
+    ) -> Result<Self> {
         // Entry
         let entry = create_entry()?;
 
         // Instance
-        let instance_wrapper = VkInstance::new(&entry, display)?;
+        let instance_wrapper = VkInstance::new(&entry, Some(display))?;
         let instance = instance_wrapper.instance.clone();
 
         // Debug
@@ -31,14 +37,39 @@ impl VkContext {
         let surface = Surface::new(&entry, &instance, display, window)?;
 
         // Device
-        let device = Device::new(&instance, &surface)?;
+        let device = Device::new(&instance, Some(&surface), dynamic_rendering)?;
 
         Ok(Self {
             entry,
             instance,
             debug,
-            surface,
+            surface: Some(surface),
             device,
         })
     }
+
+    /// Context without a window: no surface, no present queue, no swapchain
+    /// extension. Used for offscreen rendering on CI / software Vulkan.
+    pub fn new_headless(dynamic_rendering: bool) -> Result<Self> {
+        let entry = create_entry()?;
+
+        let instance_wrapper = VkInstance::new(&entry, None)?;
+        let instance = instance_wrapper.instance.clone();
+
+        let debug = DebugMessenger::new(&entry, &instance)?;
+
+        let device = Device::new(&instance, None, dynamic_rendering)?;
+
+        Ok(Self {
+            entry,
+            instance,
+            debug,
+            surface: None,
+            device,
+        })
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
+    }
 }
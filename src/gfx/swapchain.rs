@@ -1,7 +1,7 @@
 use crate::core::swapchain::Swapchain;
 use crate::gfx::context::VkContext;
 use crate::utils::config::Config;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use ash::vk;
 pub struct SwapchainManager {
     pub swapchain: Swapchain,
@@ -9,13 +9,11 @@ pub struct SwapchainManager {
 
 impl SwapchainManager {
     pub fn new(context: &VkContext, cfg: &Config, width: u32, height: u32) -> Result<Self> {
-        let swapchain = Swapchain::new(
-            &context.instance,
-            &context.device,
-            &context.surface,
-            width,
-            height,
-        )?;
+        let surface = context
+            .surface
+            .as_ref()
+            .context("swapchain requires a surface (context is headless)")?;
+        let swapchain = Swapchain::new(&context.instance, &context.device, surface, width, height)?;
 
         Ok(Self { swapchain })
     }
@@ -31,15 +29,15 @@ impl SwapchainManager {
             context.device.device.device_wait_idle()?;
         }
 
+        let surface = context
+            .surface
+            .as_ref()
+            .context("swapchain requires a surface (context is headless)")?;
+
         // ✅ FIX HERE
         self.swapchain.destroy(&context.device.device);
-        self.swapchain = Swapchain::new(
-            &context.instance,
-            &context.device,
-            &context.surface,
-            width,
-            height,
-        )?;
+        self.swapchain =
+            Swapchain::new(&context.instance, &context.device, surface, width, height)?;
 
         Ok(true)
     }
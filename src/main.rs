@@ -16,15 +16,68 @@ use utils::config::load_config;
 fn main() -> anyhow::Result<()> {
     utils::logger::init();
 
-    let cfg = load_config("assets/config.toml")?;
+    let mut cfg = load_config("assets/config.toml")?;
+
+    // `--headless [--frames N]` overrides the config (handy for CI)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if has_flag(&args, "--headless") {
+        cfg.renderer.headless = true;
+    }
+    if let Some(n) = flag_value(&args, "--frames")? {
+        cfg.renderer.headless_frames = n.parse()?;
+    }
+
+    // `--capture out.png|out.ppm` saves the last frame,
+    // `--golden ref.png [--tolerance N] [--update-golden]` compares against it
+    let capture = flag_value(&args, "--capture")?;
+    let golden = flag_value(&args, "--golden")?;
+    let tolerance: u8 = match flag_value(&args, "--tolerance")? {
+        Some(t) => t.parse()?,
+        None => 2,
+    };
+    let update_golden = has_flag(&args, "--update-golden");
+    if golden.is_some() {
+        // frame timings differ from run to run
+        cfg.debug.stats = false;
+    }
 
     let mut engine = engine::engine::Engine::new(cfg)?;
     let mut game = game::game::Game::new(&mut engine)?;
 
-    engine.run(&mut game)?;
+    let result = engine.run(&mut game).and_then(|()| {
+        if capture.is_none() && golden.is_none() {
+            return Ok(());
+        }
+
+        let frame = engine.capture_frame()?;
+        if let Some(path) = capture {
+            frame.save(path)?;
+        }
+        if let Some(path) = golden {
+            utils::golden::check_golden(&frame, path.as_ref(), tolerance, update_golden)?;
+        }
+        Ok(())
+    });
 
     // cleanup meshes before engine drops (since it owns VkDevice)
+    unsafe {
+        engine.context.device.device.device_wait_idle().ok();
+    }
     game.meshes.destroy_all(&engine.context.device.device);
 
-    Ok(())
+    result
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|a| a == flag)
+}
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> anyhow::Result<Option<&'a str>> {
+    match args.iter().position(|a| a == flag) {
+        Some(pos) => args
+            .get(pos + 1)
+            .map(|v| Some(v.as_str()))
+            .ok_or_else(|| anyhow::anyhow!("{} needs a value", flag)),
+        None => Ok(None),
+    }
 }
@@ -0,0 +1,181 @@
+use crate::core::device::Device;
+use crate::resources::buffer::create_buffer;
+use anyhow::{Context, Result};
+use ash::vk;
+use std::io::Write;
+use std::path::Path;
+
+/// A rendered frame read back to the CPU, always tightly packed RGBA8.
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Copies `image` (a color attachment in `layout`) into a host-visible buffer
+/// and converts it to RGBA8. The image is put back into `layout` afterwards.
+///
+/// Blocks until the copy is done; the caller must make sure rendering into
+/// the image has finished (e.g. `device_wait_idle`).
+pub fn capture_image(
+    dev: &Device,
+    image: vk::Image,
+    format: vk::Format,
+    extent: vk::Extent2D,
+    layout: vk::ImageLayout,
+) -> Result<CapturedFrame> {
+    let size = extent.width as u64 * extent.height as u64 * 4;
+
+    let readback = create_buffer(
+        dev,
+        size,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+
+    let range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .level_count(1)
+        .layer_count(1);
+
+    let cmd = dev.begin_one_time_commands()?;
+    unsafe {
+        let to_transfer = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .old_layout(layout)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(range);
+
+        dev.device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_transfer],
+        );
+
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .layer_count(1),
+            )
+            .image_extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            });
+
+        dev.device.cmd_copy_image_to_buffer(
+            cmd,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            readback.buffer,
+            &[region],
+        );
+
+        let back = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .dst_access_mask(vk::AccessFlags::empty())
+            .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(range);
+
+        let to_host = vk::BufferMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(readback.buffer)
+            .size(vk::WHOLE_SIZE);
+
+        dev.device.cmd_pipeline_barrier(
+            cmd,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[to_host],
+            &[back],
+        );
+    }
+    dev.end_one_time_commands(cmd)?;
+
+    let mut raw = vec![0u8; size as usize];
+    unsafe {
+        std::ptr::copy_nonoverlapping(readback.mapped_ptr(), raw.as_mut_ptr(), raw.len());
+    }
+    readback.destroy(&dev.device);
+
+    let rgba = to_rgba8(format, raw)?;
+
+    Ok(CapturedFrame {
+        width: extent.width,
+        height: extent.height,
+        rgba,
+    })
+}
+
+/// Reorders 4-byte pixels into RGBA. sRGB formats are left encoded, which is
+/// what image files expect anyway.
+pub fn to_rgba8(format: vk::Format, mut bytes: Vec<u8>) -> Result<Vec<u8>> {
+    match format {
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => Ok(bytes),
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => {
+            for px in bytes.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+            Ok(bytes)
+        }
+        other => anyhow::bail!("capture: unsupported color format {:?}", other),
+    }
+}
+
+impl CapturedFrame {
+    /// Writes `.png` or `.ppm` depending on the extension.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match ext.as_deref() {
+            Some("png") => self.write_png(path),
+            Some("ppm") => self.write_ppm(path),
+            _ => anyhow::bail!("capture: unknown image extension: {}", path.display()),
+        }
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<()> {
+        image::save_buffer(
+            path,
+            &self.rgba,
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgba8,
+        )
+        .with_context(|| format!("Failed to write PNG: {}", path.display()))
+    }
+
+    /// Binary P6, alpha dropped.
+    pub fn write_ppm(&self, path: &Path) -> Result<()> {
+        let mut out = Vec::with_capacity(self.rgba.len() / 4 * 3 + 32);
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for px in self.rgba.chunks_exact(4) {
+            out.extend_from_slice(&px[..3]);
+        }
+
+        std::fs::write(path, out)
+            .with_context(|| format!("Failed to write PPM: {}", path.display()))
+    }
+}
@@ -1,10 +1,7 @@
-use crate::renderer::render_types::RenderItem;
+use crate::renderer::graph::{FrameContext, RenderGraph};
+use crate::resources::upload::UploadWait;
 use anyhow::Result;
 use ash::vk;
-pub struct Commands {
-    pub pool: vk::CommandPool,
-    pub buffers: Vec<vk::CommandBuffer>,
-}
 
 pub fn create_command_pool(device: &ash::Device, graphics_family: u32) -> Result<vk::CommandPool> {
     let info = vk::CommandPoolCreateInfo::default()
@@ -27,93 +24,25 @@ pub fn allocate_command_buffers(
     Ok(unsafe { device.allocate_command_buffers(&info)? })
 }
 
-pub fn record_scene_cmd(
+/// Records one frame: every live pass of `graph`, in order. `variant` is
+/// the swapchain image index (0 offscreen). Ownership of buffers in
+/// `uploads` is acquired first.
+pub fn record_frame(
     device: &ash::Device,
     cmd: vk::CommandBuffer,
-    render_pass: vk::RenderPass,
-    framebuffer: vk::Framebuffer,
-    extent: vk::Extent2D,
-    pipeline: vk::Pipeline,
-    pipeline_layout: vk::PipelineLayout,
-    descriptor_set: vk::DescriptorSet,
-    items: &[RenderItem],
+    graph: &RenderGraph,
+    variant: usize,
+    frame: &FrameContext,
+    uploads: &UploadWait,
 ) -> Result<()> {
     let begin = vk::CommandBufferBeginInfo::default();
     unsafe { device.begin_command_buffer(cmd, &begin)? };
 
-    let clear_values = [
-        vk::ClearValue {
-            color: vk::ClearColorValue {
-                float32: [0.05, 0.05, 0.08, 1.0],
-            },
-        },
-        vk::ClearValue {
-            depth_stencil: vk::ClearDepthStencilValue {
-                depth: 1.0,
-                stencil: 0,
-            },
-        },
-    ];
-
-    let rp_begin = vk::RenderPassBeginInfo::default()
-        .render_pass(render_pass)
-        .framebuffer(framebuffer)
-        .render_area(vk::Rect2D::default().extent(extent))
-        .clear_values(&clear_values);
-
-    unsafe {
-        device.cmd_begin_render_pass(cmd, &rp_begin, vk::SubpassContents::INLINE);
-
-        device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
-
-        // Global descriptor set (view_proj UBO) once per cmd buffer
-        device.cmd_bind_descriptor_sets(
-            cmd,
-            vk::PipelineBindPoint::GRAPHICS,
-            pipeline_layout,
-            0,
-            &[descriptor_set],
-            &[],
-        );
-
-        // Dynamic viewport/scissor once
-        let viewport = vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: extent.width as f32,
-            height: extent.height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        };
-        let scissor = vk::Rect2D::default().extent(extent);
-        device.cmd_set_viewport(cmd, 0, &[viewport]);
-        device.cmd_set_scissor(cmd, 0, &[scissor]);
-
-        // Draw all items
-        for item in items {
-            let model_bytes = item.model.to_cols_array_2d();
-
-            device.cmd_push_constants(
-                cmd,
-                pipeline_layout,
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                bytemuck::bytes_of(&model_bytes),
-            );
+    uploads.record_acquires(device, cmd);
 
-            device.cmd_bind_vertex_buffers(cmd, 0, &[item.mesh.vertex_buffer.buffer], &[0]);
-            device.cmd_bind_index_buffer(
-                cmd,
-                item.mesh.index_buffer.buffer,
-                0,
-                vk::IndexType::UINT32,
-            );
-            device.cmd_draw_indexed(cmd, item.mesh.index_count, 1, 0, 0, 0);
-        }
+    graph.execute(device, cmd, variant, frame);
 
-        device.cmd_end_render_pass(cmd);
-        device.end_command_buffer(cmd)?;
-    }
+    unsafe { device.end_command_buffer(cmd)? };
 
     Ok(())
 }
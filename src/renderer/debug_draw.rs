@@ -0,0 +1,408 @@
+use crate::core::device::Device;
+use crate::renderer::dynamic_mesh::DynamicMesh;
+use crate::renderer::graph::{FrameContext, GraphPass, PassContext, PipelineContext};
+use crate::renderer::mesh::MeshBuffers;
+use crate::renderer::pipeline::{DepthTest, PipelineDesc, create_pipeline, create_pipeline_layout};
+use crate::renderer::renderer::Renderer;
+use crate::resources::buffer::Vertex;
+use crate::scene::culling::Aabb;
+use anyhow::Result;
+use ash::vk;
+use glam::{Mat4, Vec3};
+
+/// Shader sources the debug line pipelines are built from.
+pub const DEBUG_VERT: &str = "debug.vert";
+pub const DEBUG_FRAG: &str = "debug.frag";
+
+/// Segments per circle of `DebugDraw::sphere`.
+const CIRCLE_SEGMENTS: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+struct DebugLine {
+    a: Vec3,
+    b: Vec3,
+    color: Vec3,
+    depth_test: bool,
+    /// Seconds left; `None`: drawn in the next frame only.
+    remaining: Option<f32>,
+}
+
+/// Immediate-mode debug lines. Shapes can be added at any point before the
+/// frame is drawn (e.g. from `GameLoop::update`) and are shown in the next
+/// frame, or for as long as their `duration`. Colors are linear HDR, like
+/// the scene they are drawn into.
+pub struct DebugDraw {
+    /// When `false`, shapes are dropped as they are added.
+    pub enabled: bool,
+    lines: Vec<DebugLine>,
+    mesh: DynamicMesh,
+}
+
+/// The lines added by one `DebugDraw` call, to adjust how they are drawn.
+pub struct DebugShape<'a> {
+    lines: &'a mut [DebugLine],
+}
+
+impl DebugShape<'_> {
+    /// Keeps the shape for `seconds` instead of a single frame.
+    pub fn duration(self, seconds: f32) -> Self {
+        for line in self.lines.iter_mut() {
+            line.remaining = Some(seconds.max(0.0));
+        }
+        self
+    }
+
+    /// Draws the shape over the scene instead of depth testing it.
+    pub fn on_top(self) -> Self {
+        for line in self.lines.iter_mut() {
+            line.depth_test = false;
+        }
+        self
+    }
+}
+
+/// Debug lines of one frame, the debug pass's input: indices
+/// `0..depth_tested` are depth tested, the rest are drawn on top.
+#[derive(Clone, Copy)]
+pub struct DebugLines {
+    pub mesh: MeshBuffers,
+    pub depth_tested: u32,
+}
+
+impl DebugDraw {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            lines: Vec::new(),
+            mesh: DynamicMesh::new(),
+        }
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Vec3) -> DebugShape<'_> {
+        self.push([(a, b)], color)
+    }
+
+    /// The 12 edges of `aabb`.
+    pub fn aabb(&mut self, aabb: &Aabb, color: Vec3) -> DebugShape<'_> {
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            )
+        };
+        // corners differing in exactly one axis bit
+        let edges = (0..8).flat_map(|i| {
+            [1, 2, 4]
+                .into_iter()
+                .filter(move |bit| i & bit == 0)
+                .map(move |bit| (corner(i), corner(i | bit)))
+        });
+        self.push(edges, color)
+    }
+
+    /// Three great circles, one per axis plane.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec3) -> DebugShape<'_> {
+        let point = |axis: usize, k: usize| {
+            let angle = k as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            let (s, c) = angle.sin_cos();
+            let offset = match axis {
+                0 => Vec3::new(c, s, 0.0),
+                1 => Vec3::new(c, 0.0, s),
+                _ => Vec3::new(0.0, c, s),
+            };
+            center + offset * radius
+        };
+        let segments = (0..3).flat_map(|axis| {
+            (0..CIRCLE_SEGMENTS).map(move |k| (point(axis, k), point(axis, k + 1)))
+        });
+        self.push(segments, color)
+    }
+
+    /// X, Y and Z axes of `transform`, `size` long, in red, green and blue.
+    pub fn axes(&mut self, transform: Mat4, size: f32) -> DebugShape<'_> {
+        let origin = transform.transform_point3(Vec3::ZERO);
+        let start = self.lines.len();
+        for (axis, color) in [(Vec3::X, Vec3::X), (Vec3::Y, Vec3::Y), (Vec3::Z, Vec3::Z)] {
+            let tip = transform.transform_point3(axis * size);
+            self.push([(origin, tip)], color);
+        }
+        self.shape_from(start)
+    }
+
+    /// Square grid on the XZ plane through `center`, `half_extent` to each
+    /// side with lines every `spacing` units.
+    pub fn grid(
+        &mut self,
+        center: Vec3,
+        half_extent: f32,
+        spacing: f32,
+        color: Vec3,
+    ) -> DebugShape<'_> {
+        let steps = if spacing > 0.0 {
+            (half_extent / spacing).floor() as i32
+        } else {
+            0
+        };
+        let lines = (-steps..=steps).flat_map(|i| {
+            let t = i as f32 * spacing;
+            [
+                (
+                    center + Vec3::new(t, 0.0, -half_extent),
+                    center + Vec3::new(t, 0.0, half_extent),
+                ),
+                (
+                    center + Vec3::new(-half_extent, 0.0, t),
+                    center + Vec3::new(half_extent, 0.0, t),
+                ),
+            ]
+        });
+        self.push(lines, color)
+    }
+
+    /// Writes the current lines into the buffers of `renderer`'s next frame.
+    /// `None` if there is nothing to draw.
+    pub fn prepare(&mut self, dev: &Device, renderer: &Renderer) -> Result<Option<DebugLines>> {
+        // depth tested first; the pass switches pipelines once
+        self.lines.sort_by_key(|line| !line.depth_test);
+        let depth_tested = self.lines.iter().filter(|l| l.depth_test).count() as u32 * 2;
+
+        let vertex = |pos: Vec3, color: Vec3| Vertex {
+            pos: pos.to_array(),
+            color: color.to_array(),
+            normal: [0.0; 3],
+            uv: [0.0; 2],
+        };
+        let vertices: Vec<Vertex> = self
+            .lines
+            .iter()
+            .flat_map(|l| [vertex(l.a, l.color), vertex(l.b, l.color)])
+            .collect();
+        let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+
+        self.mesh.update_vertices(&vertices);
+        self.mesh.update_indices(&indices);
+
+        let Some(mesh) = self.mesh.prepare(dev, renderer)? else {
+            return Ok(None);
+        };
+        Ok(Some(DebugLines {
+            mesh: mesh.buffers(),
+            depth_tested,
+        }))
+    }
+
+    /// Counts timed lines down by the frame step `dt` and drops the ones
+    /// whose time is up. Call once per frame, after drawing.
+    pub fn end_frame(&mut self, dt: f32) {
+        self.lines.retain_mut(|line| match &mut line.remaining {
+            Some(remaining) => {
+                *remaining -= dt;
+                *remaining > 0.0
+            }
+            None => false,
+        });
+    }
+
+    /// The GPU must be done with every frame that drew debug lines.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        self.mesh.destroy(device);
+        self.lines.clear();
+    }
+
+    fn push(
+        &mut self,
+        segments: impl IntoIterator<Item = (Vec3, Vec3)>,
+        color: Vec3,
+    ) -> DebugShape<'_> {
+        let start = self.lines.len();
+        if self.enabled {
+            self.lines
+                .extend(segments.into_iter().map(|(a, b)| DebugLine {
+                    a,
+                    b,
+                    color,
+                    depth_test: true,
+                    remaining: None,
+                }));
+        }
+        self.shape_from(start)
+    }
+
+    fn shape_from(&mut self, start: usize) -> DebugShape<'_> {
+        DebugShape {
+            lines: &mut self.lines[start..],
+        }
+    }
+}
+
+/// Draws this frame's `DebugLines` after the scene: depth tested lines
+/// against the scene depth, then the ones marked `on_top`.
+#[derive(Default)]
+pub struct DebugPass {
+    layout: vk::PipelineLayout,
+    depth_tested: vk::Pipeline,
+    on_top: vk::Pipeline,
+}
+
+impl GraphPass for DebugPass {
+    fn shaders(&self) -> Vec<&str> {
+        vec![DEBUG_VERT, DEBUG_FRAG]
+    }
+
+    fn build_pipelines(&mut self, ctx: &PipelineContext) -> Result<()> {
+        // set 0 only, for `view_proj`
+        if self.layout == vk::PipelineLayout::null() {
+            self.layout = create_pipeline_layout(ctx.device, &ctx.set_layouts[..1])?;
+        }
+
+        // position and color of `Vertex`, no culling or blending; the depth
+        // attachment is read-only
+        let bindings = [Vertex::binding_description()];
+        let attributes = &Vertex::attribute_descriptions()[..2];
+        let base = PipelineDesc {
+            bindings: &bindings,
+            attributes,
+            topology: vk::PrimitiveTopology::LINE_LIST,
+            samples: ctx.samples,
+            ..PipelineDesc::new(
+                ctx.target,
+                self.layout,
+                ctx.shaders.get(DEBUG_VERT),
+                ctx.shaders.get(DEBUG_FRAG),
+            )
+        };
+        let create = |depth_test: bool| {
+            let desc = PipelineDesc {
+                // LESS_OR_EQUAL so lines lying on a surface still show
+                depth: depth_test.then_some(DepthTest {
+                    write: false,
+                    compare: vk::CompareOp::LESS_OR_EQUAL,
+                }),
+                ..base
+            };
+            create_pipeline(ctx.device, ctx.cache, &desc)
+        };
+        let depth_tested = create(true)?;
+        let on_top = match create(false) {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                unsafe { ctx.device.destroy_pipeline(depth_tested, None) };
+                return Err(e);
+            }
+        };
+
+        unsafe {
+            ctx.device.destroy_pipeline(self.depth_tested, None);
+            ctx.device.destroy_pipeline(self.on_top, None);
+        }
+        self.depth_tested = depth_tested;
+        self.on_top = on_top;
+        Ok(())
+    }
+
+    fn record(&self, ctx: &PassContext, frame: &FrameContext) {
+        let Some(lines) = frame.input::<DebugLines>() else {
+            return;
+        };
+        let (device, cmd) = (ctx.device, ctx.cmd);
+        let count = lines.mesh.index_count;
+
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+            device.cmd_bind_vertex_buffers(cmd, 0, &[lines.mesh.vertex_buffer], &[0]);
+            device.cmd_bind_index_buffer(cmd, lines.mesh.index_buffer, 0, vk::IndexType::UINT32);
+
+            for (pipeline, first, count) in [
+                (self.depth_tested, 0, lines.depth_tested),
+                (self.on_top, lines.depth_tested, count - lines.depth_tested),
+            ] {
+                if count == 0 {
+                    continue;
+                }
+                device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                device.cmd_draw_indexed(cmd, count, 1, first, 0, 0);
+            }
+        }
+    }
+
+    fn destroy(&mut self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.depth_tested, None);
+            device.destroy_pipeline(self.on_top, None);
+            device.destroy_pipeline_layout(self.layout, None);
+        }
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_emit_expected_line_counts() {
+        let mut debug = DebugDraw::new(true);
+        let unit = Aabb {
+            min: Vec3::ZERO,
+            max: Vec3::ONE,
+        };
+
+        assert_eq!(debug.aabb(&unit, Vec3::ONE).lines.len(), 12);
+        assert_eq!(
+            debug.sphere(Vec3::ZERO, 1.0, Vec3::ONE).lines.len(),
+            3 * CIRCLE_SEGMENTS
+        );
+        assert_eq!(debug.axes(Mat4::IDENTITY, 1.0).lines.len(), 3);
+        // -2..=2 in both directions
+        assert_eq!(debug.grid(Vec3::ZERO, 2.0, 1.0, Vec3::ONE).lines.len(), 10);
+        assert_eq!(debug.lines.len(), 12 + 3 * CIRCLE_SEGMENTS + 3 + 10);
+    }
+
+    #[test]
+    fn aabb_edges_are_axis_aligned() {
+        let mut debug = DebugDraw::new(true);
+        let aabb = Aabb {
+            min: Vec3::new(-1.0, -2.0, -3.0),
+            max: Vec3::new(1.0, 2.0, 3.0),
+        };
+        debug.aabb(&aabb, Vec3::ONE);
+
+        for line in &debug.lines {
+            let d = (line.b - line.a).abs();
+            let axes = [d.x, d.y, d.z].iter().filter(|&&v| v > 0.0).count();
+            assert_eq!(axes, 1);
+        }
+    }
+
+    #[test]
+    fn lines_expire_after_their_duration() {
+        let mut debug = DebugDraw::new(true);
+        debug.line(Vec3::ZERO, Vec3::X, Vec3::ONE);
+        debug.line(Vec3::ZERO, Vec3::Y, Vec3::ONE).duration(0.25);
+        debug.line(Vec3::ZERO, Vec3::Z, Vec3::ONE).on_top();
+
+        debug.end_frame(0.1);
+        assert_eq!(debug.lines.len(), 1);
+        assert_eq!(debug.lines[0].b, Vec3::Y);
+
+        debug.end_frame(0.1);
+        assert_eq!(debug.lines.len(), 1);
+        debug.end_frame(0.1);
+        assert!(debug.lines.is_empty());
+    }
+
+    #[test]
+    fn disabled_debug_draw_keeps_nothing() {
+        let mut debug = DebugDraw::new(false);
+        debug.sphere(Vec3::ZERO, 1.0, Vec3::ONE).duration(10.0);
+        assert!(debug.lines.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::core::device::Device;
+use crate::renderer::mesh::Mesh;
+use crate::renderer::renderer::Renderer;
+use crate::resources::buffer::{GpuBuffer, Vertex, create_buffer};
+use crate::scene::culling::{Aabb, BoundingSphere};
+use anyhow::Result;
+use ash::vk;
+use glam::Vec3;
+
+/// Smallest buffers we allocate, in vertices or indices.
+const MIN_CAPACITY: usize = 64;
+
+/// Mesh whose vertices and indices are rewritten from the CPU (debug
+/// geometry, particle trails, edited terrain).
+///
+/// `update_vertices` and `update_indices` only keep a copy of the data.
+/// `prepare` writes it into host-visible buffers owned by the frame slot
+/// the next `draw_frame` records into, after waiting for that slot's fence,
+/// so frames still in flight keep reading their own copy. The index count
+/// may change with every update.
+pub struct DynamicMesh {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    aabb: Aabb,
+    sphere: BoundingSphere,
+    /// Bumped by every update. Slots start at 0, so a fresh slot is always
+    /// written.
+    vertex_version: u64,
+    index_version: u64,
+    /// One per frame in flight, created on first use.
+    slots: Vec<Option<Slot>>,
+}
+
+struct Slot {
+    mesh: Mesh,
+    vertex_capacity: usize,
+    index_capacity: usize,
+    vertex_version: u64,
+    index_version: u64,
+}
+
+impl DynamicMesh {
+    /// No GPU memory is allocated until the first `prepare`.
+    pub fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            aabb: Aabb::from_points([]),
+            sphere: BoundingSphere::from_points(&[]),
+            vertex_version: 1,
+            index_version: 1,
+            slots: Vec::new(),
+        }
+    }
+
+    pub fn update_vertices(&mut self, vertices: &[Vertex]) {
+        self.vertices.clear();
+        self.vertices.extend_from_slice(vertices);
+        self.vertex_version += 1;
+
+        let points: Vec<Vec3> = vertices.iter().map(|v| Vec3::from(v.pos)).collect();
+        self.aabb = Aabb::from_points(points.iter().copied());
+        self.sphere = BoundingSphere::from_points(&points);
+    }
+
+    pub fn update_indices(&mut self, indices: &[u32]) {
+        self.indices.clear();
+        self.indices.extend_from_slice(indices);
+        self.index_version += 1;
+    }
+
+    /// Copies the latest data into the buffers of `renderer`'s next frame
+    /// and returns the mesh to draw in it, `None` while there are no
+    /// vertices or indices. Only data changed since the slot was last
+    /// written is copied.
+    pub fn prepare(&mut self, dev: &Device, renderer: &Renderer) -> Result<Option<&Mesh>> {
+        let slot_index = renderer.wait_frame_slot(dev)?;
+
+        // the frame ring was resized (after a device wait idle), so slots
+        // past its end are no longer read
+        let frame_count = renderer.frames.len();
+        if self.slots.len() > frame_count {
+            for slot in self.slots.drain(frame_count..).flatten() {
+                slot.mesh.destroy(&dev.device);
+            }
+        }
+        self.slots.resize_with(frame_count, || None);
+
+        if self.vertices.is_empty() || self.indices.is_empty() {
+            return Ok(None);
+        }
+
+        let slot = match &mut self.slots[slot_index] {
+            Some(slot) => slot,
+            empty => empty.insert(Slot::new(
+                dev,
+                capacity_for(self.vertices.len()),
+                capacity_for(self.indices.len()),
+            )?),
+        };
+
+        if self.vertices.len() > slot.vertex_capacity {
+            let capacity = capacity_for(self.vertices.len());
+            let buffer =
+                create_stream_buffer::<Vertex>(dev, capacity, vk::BufferUsageFlags::VERTEX_BUFFER)?;
+            std::mem::replace(&mut slot.mesh.vertex_buffer, buffer).destroy(&dev.device);
+            slot.vertex_capacity = capacity;
+            slot.vertex_version = 0;
+        }
+        if self.indices.len() > slot.index_capacity {
+            let capacity = capacity_for(self.indices.len());
+            let buffer =
+                create_stream_buffer::<u32>(dev, capacity, vk::BufferUsageFlags::INDEX_BUFFER)?;
+            std::mem::replace(&mut slot.mesh.index_buffer, buffer).destroy(&dev.device);
+            slot.index_capacity = capacity;
+            slot.index_version = 0;
+        }
+
+        if slot.vertex_version != self.vertex_version {
+            slot.mesh
+                .vertex_buffer
+                .write_bytes(bytemuck::cast_slice(&self.vertices));
+            slot.vertex_version = self.vertex_version;
+        }
+        if slot.index_version != self.index_version {
+            slot.mesh
+                .index_buffer
+                .write_bytes(bytemuck::cast_slice(&self.indices));
+            slot.index_version = self.index_version;
+        }
+
+        slot.mesh.index_count = self.indices.len() as u32;
+        slot.mesh.aabb = self.aabb;
+        slot.mesh.sphere = self.sphere;
+
+        Ok(Some(&slot.mesh))
+    }
+
+    /// The GPU must be done with every frame that drew this mesh.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        for slot in self.slots.drain(..).flatten() {
+            slot.mesh.destroy(device);
+        }
+    }
+}
+
+impl Slot {
+    fn new(dev: &Device, vertex_capacity: usize, index_capacity: usize) -> Result<Self> {
+        let vertex_buffer = create_stream_buffer::<Vertex>(
+            dev,
+            vertex_capacity,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+        let index_buffer = match create_stream_buffer::<u32>(
+            dev,
+            index_capacity,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+        ) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                vertex_buffer.destroy(&dev.device);
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            mesh: Mesh {
+                vertex_buffer,
+                index_buffer,
+                index_count: 0,
+                aabb: Aabb::from_points([]),
+                sphere: BoundingSphere::from_points(&[]),
+            },
+            vertex_capacity,
+            index_capacity,
+            vertex_version: 0,
+            index_version: 0,
+        })
+    }
+}
+
+fn capacity_for(len: usize) -> usize {
+    len.next_power_of_two().max(MIN_CAPACITY)
+}
+
+/// Host-visible buffer of `capacity` elements of `T`.
+fn create_stream_buffer<T>(
+    dev: &Device,
+    capacity: usize,
+    usage: vk::BufferUsageFlags,
+) -> Result<GpuBuffer> {
+    create_buffer(
+        dev,
+        (capacity * std::mem::size_of::<T>()) as vk::DeviceSize,
+        usage,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )
+}
@@ -0,0 +1,155 @@
+use super::command_buffers::{allocate_command_buffers, create_command_pool};
+use super::instancing::InstanceBuffer;
+use crate::core::device::Device;
+use crate::renderer::render_types::FrameGlobals;
+use crate::resources::buffer::{
+    GpuBuffer, LightingUbo, UniformBufferObject, create_uniform_buffer,
+};
+use crate::resources::descriptor::{
+    allocate_descriptor_sets, create_descriptor_pool, update_descriptor_sets,
+};
+use anyhow::Result;
+use ash::vk;
+
+/// Everything the CPU writes while recording one frame. The renderer keeps
+/// a ring of `frames_in_flight` of these, independent of the swapchain
+/// image count; a slot is reused once its `fence` has signaled.
+pub struct FrameData {
+    pub pool: vk::CommandPool,
+    pub cmd: vk::CommandBuffer,
+    /// Signaled when the GPU has finished the frame last submitted from
+    /// this slot.
+    pub fence: vk::Fence,
+    /// Signaled by swapchain acquire, waited on by the submit.
+    pub image_available: vk::Semaphore,
+
+    /// Host visible, written by `write_uniforms`.
+    pub uniform_buffer: GpuBuffer,
+    pub light_buffer: GpuBuffer,
+    pub instance_buffer: InstanceBuffer,
+    /// Set 0; see `create_descriptor_set_layout`.
+    pub descriptor_set: vk::DescriptorSet,
+}
+
+impl FrameData {
+    pub fn new(dev: &Device, descriptor_set: vk::DescriptorSet) -> Result<Self> {
+        let device = &dev.device;
+
+        let pool = create_command_pool(device, dev.queues.graphics_family)?;
+        let cmd = allocate_command_buffers(device, pool, 1)?[0];
+
+        let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+        let fence = unsafe { device.create_fence(&fence_info, None)? };
+        let image_available =
+            unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)? };
+
+        let ubo_size = std::mem::size_of::<UniformBufferObject>() as u64;
+        let uniform_buffer = create_uniform_buffer(dev, ubo_size)?;
+
+        let light_size = std::mem::size_of::<LightingUbo>() as u64;
+        let light_buffer = create_uniform_buffer(dev, light_size)?;
+
+        Ok(Self {
+            pool,
+            cmd,
+            fence,
+            image_available,
+            uniform_buffer,
+            light_buffer,
+            instance_buffer: InstanceBuffer::new(),
+            descriptor_set,
+        })
+    }
+
+    /// Copies the camera and lighting UBOs. The GPU must be done with this
+    /// slot (its fence waited on).
+    pub fn write_uniforms(&self, globals: &FrameGlobals) {
+        let ubo = UniformBufferObject {
+            view_proj: globals.view_proj.to_cols_array_2d(),
+            light_view_proj: globals.light_view_proj.to_cols_array_2d(),
+        };
+
+        let light = &globals.light;
+        let lighting = LightingUbo {
+            direction: light
+                .direction
+                .normalize_or_zero()
+                .extend(light.shadow_bias)
+                .to_array(),
+            color: light.color.extend(light.specular).to_array(),
+            ambient: light.ambient.extend(0.0).to_array(),
+            camera_pos: globals.camera_pos.extend(light.shininess).to_array(),
+        };
+
+        self.uniform_buffer.write_bytes(bytemuck::bytes_of(&ubo));
+        self.light_buffer.write_bytes(bytemuck::bytes_of(&lighting));
+    }
+
+    /// The descriptor set is freed with its pool, not here.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        self.uniform_buffer.destroy(device);
+        self.light_buffer.destroy(device);
+        self.instance_buffer.destroy(device);
+
+        unsafe {
+            device.destroy_semaphore(self.image_available, None);
+            device.destroy_fence(self.fence, None);
+            device.destroy_command_pool(self.pool, None);
+        }
+    }
+}
+
+/// `count` frames and the descriptor pool their sets come from. Set 0 is
+/// left for `write_frame_descriptors`, since the shadow map view changes
+/// with the render graph.
+pub fn create_frames(
+    dev: &Device,
+    count: usize,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+) -> Result<(vk::DescriptorPool, Vec<FrameData>)> {
+    let pool = create_descriptor_pool(&dev.device, count as u32)?;
+    let sets = allocate_descriptor_sets(&dev.device, pool, descriptor_set_layout, count)?;
+
+    let frames = sets
+        .into_iter()
+        .map(|set| FrameData::new(dev, set))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((pool, frames))
+}
+
+/// Points every frame's set 0 at its own UBOs and at `shadow_map`.
+pub fn write_frame_descriptors(
+    device: &ash::Device,
+    frames: &[FrameData],
+    shadow_map: vk::DescriptorImageInfo,
+) {
+    let sets: Vec<vk::DescriptorSet> = frames.iter().map(|f| f.descriptor_set).collect();
+    let uniforms: Vec<vk::Buffer> = frames.iter().map(|f| f.uniform_buffer.buffer).collect();
+    let lights: Vec<vk::Buffer> = frames.iter().map(|f| f.light_buffer.buffer).collect();
+
+    update_descriptor_sets(
+        device,
+        &sets,
+        &uniforms,
+        std::mem::size_of::<UniformBufferObject>() as u64,
+        &lights,
+        std::mem::size_of::<LightingUbo>() as u64,
+        shadow_map,
+    );
+}
+
+/// Validates `renderer.frames_in_flight` against `max_frames_in_flight`.
+pub fn check_frames_in_flight(frames_in_flight: usize, max: usize) -> Result<usize> {
+    if max == 0 {
+        anyhow::bail!("renderer.max_frames_in_flight must be at least 1");
+    }
+    if frames_in_flight == 0 || frames_in_flight > max {
+        anyhow::bail!(
+            "renderer.frames_in_flight = {} is out of range (1..={})",
+            frames_in_flight,
+            max
+        );
+    }
+    Ok(frames_in_flight)
+}
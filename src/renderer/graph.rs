@@ -0,0 +1,1637 @@
+use crate::assets::shaders::ShaderLibrary;
+use crate::core::device::Device;
+use crate::core::dynamic_rendering::DynamicRendering;
+use crate::renderer::framebuffers::create_framebuffer;
+use crate::renderer::pipeline::PipelineTarget;
+use crate::renderer::render_pass::{RenderPassDesc, create_render_pass};
+use crate::resources::image::{GpuImage, create_image};
+use crate::resources::image_view::create_image_view;
+use crate::scene::material_store::MaterialStore;
+use anyhow::{Context, Result};
+use ash::vk;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BufferHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSize {
+    /// Follows the extent passed to `RenderGraph::compile`.
+    Swapchain,
+    Fixed(vk::Extent2D),
+}
+
+/// A graph-owned image, created at compile time.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDesc {
+    pub format: vk::Format,
+    pub size: ImageSize,
+    pub samples: vk::SampleCountFlags,
+}
+
+/// What every pass may need while recording one frame. Data for a single
+/// pass goes through `inputs`.
+pub struct FrameContext<'a> {
+    /// Set 0 (camera, lighting, shadow map) for this frame.
+    pub descriptor_set: vk::DescriptorSet,
+    pub materials: &'a MaterialStore,
+    /// Seconds since the renderer was created.
+    pub time: f32,
+    pub inputs: &'a PassInputs,
+}
+
+impl FrameContext<'_> {
+    /// This frame's `T`, if it was prepared (see `PassInputs`).
+    pub fn input<T: 'static>(&self) -> Option<&T> {
+        self.inputs.get()
+    }
+}
+
+/// Per-frame data of individual passes, one value per type. Whoever
+/// prepares it (e.g. `DebugDraw::prepare`) inserts it and the pass reads
+/// it with `FrameContext::input`, so a new pass adds its own type instead
+/// of a field on `FrameContext`.
+#[derive(Default)]
+pub struct PassInputs {
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl PassInputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces an earlier value of the same type.
+    pub fn insert<T: 'static>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+}
+
+/// Handed to `GraphPass::record`; the render pass is already begun and the
+/// viewport / scissor cover `extent`.
+pub struct PassContext<'a> {
+    pub device: &'a ash::Device,
+    pub cmd: vk::CommandBuffer,
+    pub extent: vk::Extent2D,
+}
+
+/// Handed to `GraphPass::build_pipelines`.
+pub struct PipelineContext<'a> {
+    pub device: &'a ash::Device,
+    /// `Device::pipeline_cache`, for every pipeline the pass creates.
+    pub cache: vk::PipelineCache,
+    pub target: PipelineTarget<'a>,
+    pub samples: vk::SampleCountFlags,
+    pub shaders: &'a ShaderLibrary,
+    /// Set 0 (frame globals), set 1 (material).
+    pub set_layouts: &'a [vk::DescriptorSetLayout],
+    /// View and layout of each image the pass samples, in `sample` order
+    /// (variant 0 for imported images).
+    pub sampled: &'a [(vk::ImageView, vk::ImageLayout)],
+}
+
+/// The recording side of a pass. Attachments and reads are declared on
+/// the `PassBuilder` returned by `RenderGraph::add_pass`.
+pub trait GraphPass {
+    /// `ShaderLibrary` names the pipelines are built from, so hot reload
+    /// knows which passes to rebuild.
+    fn shaders(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// (Re)creates pipelines against `ctx.target`. On error the old
+    /// pipelines must stay usable.
+    fn build_pipelines(&mut self, ctx: &PipelineContext) -> Result<()>;
+
+    fn record(&self, ctx: &PassContext, frame: &FrameContext);
+
+    fn destroy(&mut self, device: &ash::Device);
+}
+
+enum ImageSource {
+    Transient(ImageDesc),
+    /// Owned outside the graph (swapchain / offscreen target). `execute`
+    /// picks one of `images` with its `variant` argument.
+    Imported {
+        format: vk::Format,
+        extent: vk::Extent2D,
+        images: Vec<vk::Image>,
+        views: Vec<vk::ImageView>,
+        final_layout: vk::ImageLayout,
+    },
+}
+
+struct ImageNode {
+    name: String,
+    source: ImageSource,
+}
+
+struct BufferNode {
+    name: String,
+    /// Write made before the frame, outside the graph.
+    external_write: Option<(vk::PipelineStageFlags, vk::AccessFlags)>,
+}
+
+struct BufferAccess {
+    buffer: BufferHandle,
+    stage: vk::PipelineStageFlags,
+    access: vk::AccessFlags,
+    write: bool,
+}
+
+struct PassNode {
+    name: String,
+    colors: Vec<(ImageHandle, Option<vk::ClearValue>)>,
+    /// (image, clear, writes)
+    depth: Option<(ImageHandle, Option<vk::ClearValue>, bool)>,
+    resolves: Vec<ImageHandle>,
+    sampled: Vec<ImageHandle>,
+    buffers: Vec<BufferAccess>,
+    pass: Box<dyn GraphPass>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageUse {
+    Color,
+    Resolve,
+    DepthWrite,
+    DepthRead,
+    Sampled,
+}
+
+impl ImageUse {
+    fn layout(self, depth_format: bool) -> vk::ImageLayout {
+        match self {
+            ImageUse::Color | ImageUse::Resolve => vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageUse::DepthWrite => vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ImageUse::DepthRead => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ImageUse::Sampled if depth_format => vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ImageUse::Sampled => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }
+    }
+
+    fn stage(self) -> vk::PipelineStageFlags {
+        match self {
+            ImageUse::Color | ImageUse::Resolve => vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ImageUse::DepthWrite | ImageUse::DepthRead => {
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+            }
+            ImageUse::Sampled => vk::PipelineStageFlags::FRAGMENT_SHADER,
+        }
+    }
+
+    fn access(self) -> vk::AccessFlags {
+        match self {
+            ImageUse::Color | ImageUse::Resolve => {
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            }
+            ImageUse::DepthWrite => {
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            }
+            ImageUse::DepthRead => vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            ImageUse::Sampled => vk::AccessFlags::SHADER_READ,
+        }
+    }
+
+    fn writes(self) -> bool {
+        matches!(
+            self,
+            ImageUse::Color | ImageUse::Resolve | ImageUse::DepthWrite
+        )
+    }
+}
+
+fn is_depth_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM
+            | vk::Format::X8_D24_UNORM_PACK32
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+fn is_write_access(access: vk::AccessFlags) -> bool {
+    access.intersects(
+        vk::AccessFlags::SHADER_WRITE
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            | vk::AccessFlags::TRANSFER_WRITE
+            | vk::AccessFlags::HOST_WRITE
+            | vk::AccessFlags::MEMORY_WRITE,
+    )
+}
+
+fn has_stencil(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+/// Declares attachments and reads for one pass. Attachments without a
+/// clear value keep what earlier passes wrote (or start undefined).
+pub struct PassBuilder<'g> {
+    graph: &'g mut RenderGraph,
+    pass: usize,
+}
+
+impl PassBuilder<'_> {
+    fn node(&mut self) -> &mut PassNode {
+        &mut self.graph.passes[self.pass]
+    }
+
+    pub fn color(mut self, image: ImageHandle, clear: Option<[f32; 4]>) -> Self {
+        let clear = clear.map(|float32| vk::ClearValue {
+            color: vk::ClearColorValue { float32 },
+        });
+        self.node().colors.push((image, clear));
+        self
+    }
+
+    pub fn depth(mut self, image: ImageHandle, clear: Option<f32>) -> Self {
+        let clear = clear.map(|depth| vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue { depth, stencil: 0 },
+        });
+        self.node().depth = Some((image, clear, true));
+        self
+    }
+
+    /// Depth test against `image` without writing it.
+    pub fn depth_read(mut self, image: ImageHandle) -> Self {
+        self.node().depth = Some((image, None, false));
+        self
+    }
+
+    /// Resolve target for the color attachment with the same index.
+    pub fn resolve(mut self, image: ImageHandle) -> Self {
+        self.node().resolves.push(image);
+        self
+    }
+
+    /// Read in the fragment shader through a sampler.
+    pub fn sample(mut self, image: ImageHandle) -> Self {
+        self.node().sampled.push(image);
+        self
+    }
+
+    /// Reads or writes `buffer` at `stage`; any `*_WRITE` flag in `access`
+    /// makes it a write.
+    pub fn buffer(
+        mut self,
+        buffer: BufferHandle,
+        stage: vk::PipelineStageFlags,
+        access: vk::AccessFlags,
+    ) -> Self {
+        self.node().buffers.push(BufferAccess {
+            buffer,
+            stage,
+            access,
+            write: is_write_access(access),
+        });
+        self
+    }
+}
+
+/// How one attachment is loaded, stored and laid out by its pass.
+#[derive(Clone, Copy)]
+struct AttachmentPlan {
+    image: ImageHandle,
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+    load_op: vk::AttachmentLoadOp,
+    store_op: vk::AttachmentStoreOp,
+    /// Render pass path: layouts on entry / exit.
+    initial_layout: vk::ImageLayout,
+    final_layout: vk::ImageLayout,
+    /// Layout during the pass.
+    layout: vk::ImageLayout,
+    clear: vk::ClearValue,
+}
+
+/// Layout transition and hazard between two uses of a graph image, recorded
+/// explicitly on the dynamic rendering path.
+#[derive(Clone, Copy)]
+struct ImageBarrier {
+    image: ImageHandle,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    src_stage: vk::PipelineStageFlags,
+    src_access: vk::AccessFlags,
+    dst_stage: vk::PipelineStageFlags,
+    dst_access: vk::AccessFlags,
+}
+
+impl ImageBarrier {
+    /// From `prev` to `usage`. `discard` drops the contents (transition from
+    /// `UNDEFINED`). `None` for a read after a read in the same layout.
+    fn between(
+        image: ImageHandle,
+        prev: ImageUse,
+        usage: ImageUse,
+        depth: bool,
+        discard: bool,
+    ) -> Option<Self> {
+        let old_layout = if discard {
+            vk::ImageLayout::UNDEFINED
+        } else {
+            prev.layout(depth)
+        };
+        let new_layout = usage.layout(depth);
+        if !discard && !prev.writes() && !usage.writes() && old_layout == new_layout {
+            return None;
+        }
+
+        Some(Self {
+            image,
+            old_layout,
+            new_layout,
+            src_stage: prev.stage(),
+            src_access: if prev.writes() {
+                prev.access()
+            } else {
+                vk::AccessFlags::empty()
+            },
+            dst_stage: usage.stage(),
+            dst_access: usage.access(),
+        })
+    }
+
+    /// After the last use of an imported image: into its `final_layout`.
+    fn to_final(
+        image: ImageHandle,
+        usage: ImageUse,
+        depth: bool,
+        final_layout: vk::ImageLayout,
+    ) -> Self {
+        let (dst_stage, dst_access) = if final_layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL {
+            (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+            )
+        } else {
+            (
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::AccessFlags::empty(),
+            )
+        };
+
+        Self {
+            image,
+            old_layout: usage.layout(depth),
+            new_layout: final_layout,
+            src_stage: usage.stage(),
+            src_access: if usage.writes() {
+                usage.access()
+            } else {
+                vk::AccessFlags::empty()
+            },
+            dst_stage,
+            dst_access,
+        }
+    }
+}
+
+/// Where a buffer stands while `compile` walks the passes in order.
+#[derive(Clone, Copy, Default)]
+struct BufferState {
+    /// Last write, if any.
+    write: Option<(vk::PipelineStageFlags, vk::AccessFlags)>,
+    /// Stages and accesses a barrier already made that write visible to.
+    visible: (vk::PipelineStageFlags, vk::AccessFlags),
+    /// Stages that read the buffer since; the next write waits for them.
+    readers: vk::PipelineStageFlags,
+}
+
+struct BufferBarrier {
+    src_stage: vk::PipelineStageFlags,
+    dst_stage: vk::PipelineStageFlags,
+    barrier: vk::MemoryBarrier<'static>,
+}
+
+/// A pass compiled for dynamic rendering: no render pass or framebuffers,
+/// transitions are recorded around it.
+struct DynamicPass {
+    colors: Vec<AttachmentPlan>,
+    depth: Option<AttachmentPlan>,
+    /// One per color attachment, or empty.
+    resolves: Vec<AttachmentPlan>,
+    color_formats: Vec<vk::Format>,
+    /// `UNDEFINED` without depth.
+    depth_format: vk::Format,
+    before: Vec<ImageBarrier>,
+    after: Vec<ImageBarrier>,
+}
+
+struct CompiledPass {
+    /// Index into `RenderGraph::passes`.
+    node: usize,
+    /// Null on the dynamic rendering path.
+    render_pass: vk::RenderPass,
+    /// One per variant of the imported images it touches (or just one).
+    /// Empty on the dynamic rendering path.
+    framebuffers: Vec<vk::Framebuffer>,
+    extent: vk::Extent2D,
+    clear_values: Vec<vk::ClearValue>,
+    /// Memory barrier for buffers written before the pass.
+    buffer_barrier: Option<BufferBarrier>,
+    dynamic: Option<DynamicPass>,
+}
+
+impl CompiledPass {
+    fn target(&self) -> PipelineTarget<'_> {
+        match &self.dynamic {
+            Some(dynamic) => PipelineTarget::Dynamic {
+                color_formats: &dynamic.color_formats,
+                depth_format: dynamic.depth_format,
+            },
+            None => PipelineTarget::RenderPass(self.render_pass),
+        }
+    }
+}
+
+/// A frame as a list of passes over declared images and buffers. `compile`
+/// culls passes that don't contribute to an output, allocates transient
+/// images, and builds one render pass per pass whose attachment layouts and
+/// subpass dependencies come from how the previous and next passes use the
+/// same images. No explicit image barriers are needed between passes. On
+/// devices with dynamic rendering enabled (`Device::dynamic_rendering`) it
+/// skips render passes and framebuffers and records those transitions as
+/// image barriers instead.
+///
+/// Buffers written on the GPU, by a pass or before the frame (e.g. the
+/// upload context's copies, see `buffer`), get a memory barrier before the
+/// passes that read them. Host-written buffers (instances, UBOs, dynamic
+/// meshes) are made visible by the queue submit and need not be declared.
+#[derive(Default)]
+pub struct RenderGraph {
+    images: Vec<ImageNode>,
+    buffers: Vec<BufferNode>,
+    passes: Vec<PassNode>,
+    outputs: Vec<ImageHandle>,
+
+    // filled by `compile`
+    transients: Vec<Option<(GpuImage, vk::ImageView)>>,
+    compiled: Vec<CompiledPass>,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    /// Set by `compile` when the device uses dynamic rendering.
+    dynamic_rendering: Option<DynamicRendering>,
+    /// Set by `compile`, reused by `rebuild_pipelines`.
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create_image(&mut self, name: &str, desc: ImageDesc) -> ImageHandle {
+        self.images.push(ImageNode {
+            name: name.to_string(),
+            source: ImageSource::Transient(desc),
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+
+    /// `images` / `views` are parallel, one per variant (e.g. swapchain
+    /// image). The image is left in `final_layout` at the end of the frame.
+    pub fn import_image(
+        &mut self,
+        name: &str,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        images: &[vk::Image],
+        views: &[vk::ImageView],
+        final_layout: vk::ImageLayout,
+    ) -> ImageHandle {
+        self.images.push(ImageNode {
+            name: name.to_string(),
+            source: ImageSource::Imported {
+                format,
+                extent,
+                images: images.to_vec(),
+                views: views.to_vec(),
+                final_layout,
+            },
+        });
+        ImageHandle(self.images.len() - 1)
+    }
+
+    /// A buffer passes read or write (`PassBuilder::buffer`). Only used for
+    /// ordering, culling and barriers; passes get the actual `vk::Buffer`
+    /// from elsewhere. `written_before` is the last write before the frame,
+    /// outside the graph (e.g. transfer copies), which its first reader
+    /// waits for; `None` if only passes write it.
+    pub fn buffer(
+        &mut self,
+        name: &str,
+        written_before: Option<(vk::PipelineStageFlags, vk::AccessFlags)>,
+    ) -> BufferHandle {
+        self.buffers.push(BufferNode {
+            name: name.to_string(),
+            external_write: written_before,
+        });
+        BufferHandle(self.buffers.len() - 1)
+    }
+
+    /// Passes run in the order they are added.
+    pub fn add_pass(&mut self, name: &str, pass: impl GraphPass + 'static) -> PassBuilder<'_> {
+        self.passes.push(PassNode {
+            name: name.to_string(),
+            colors: Vec::new(),
+            depth: None,
+            resolves: Vec::new(),
+            sampled: Vec::new(),
+            buffers: Vec::new(),
+            pass: Box::new(pass),
+        });
+        PassBuilder {
+            pass: self.passes.len() - 1,
+            graph: self,
+        }
+    }
+
+    /// Marks `image` as a result of the frame; passes that don't lead to an
+    /// output are culled.
+    pub fn set_output(&mut self, image: ImageHandle) {
+        self.outputs.push(image);
+    }
+
+    fn format(&self, image: ImageHandle) -> vk::Format {
+        match &self.images[image.0].source {
+            ImageSource::Transient(desc) => desc.format,
+            ImageSource::Imported { format, .. } => *format,
+        }
+    }
+
+    fn samples(&self, image: ImageHandle) -> vk::SampleCountFlags {
+        match &self.images[image.0].source {
+            ImageSource::Transient(desc) => desc.samples,
+            ImageSource::Imported { .. } => vk::SampleCountFlags::TYPE_1,
+        }
+    }
+
+    fn extent(&self, image: ImageHandle, swapchain_extent: vk::Extent2D) -> vk::Extent2D {
+        match &self.images[image.0].source {
+            ImageSource::Transient(desc) => match desc.size {
+                ImageSize::Swapchain => swapchain_extent,
+                ImageSize::Fixed(extent) => extent,
+            },
+            ImageSource::Imported { extent, .. } => *extent,
+        }
+    }
+
+    fn variant_count(&self, image: ImageHandle) -> usize {
+        match &self.images[image.0].source {
+            ImageSource::Transient(_) => 1,
+            ImageSource::Imported { views, .. } => views.len(),
+        }
+    }
+
+    /// View of `image` for `variant` (ignored for graph-owned images).
+    /// Only valid after `compile`.
+    pub fn image_view(&self, image: ImageHandle, variant: usize) -> vk::ImageView {
+        match &self.images[image.0].source {
+            ImageSource::Transient(_) => {
+                self.transients[image.0]
+                    .as_ref()
+                    .expect("image is not used by any live pass")
+                    .1
+            }
+            ImageSource::Imported { views, .. } => views[variant % views.len()],
+        }
+    }
+
+    /// Layout a sampled `image` is in while passes read it.
+    pub fn sampled_layout(&self, image: ImageHandle) -> vk::ImageLayout {
+        ImageUse::Sampled.layout(is_depth_format(self.format(image)))
+    }
+
+    fn pass_uses(pass: &PassNode) -> Vec<(ImageHandle, ImageUse)> {
+        let mut uses: Vec<(ImageHandle, ImageUse)> = pass
+            .colors
+            .iter()
+            .map(|&(image, _)| (image, ImageUse::Color))
+            .collect();
+        if let Some((image, _, write)) = pass.depth {
+            let usage = if write {
+                ImageUse::DepthWrite
+            } else {
+                ImageUse::DepthRead
+            };
+            uses.push((image, usage));
+        }
+        uses.extend(
+            pass.resolves
+                .iter()
+                .map(|&image| (image, ImageUse::Resolve)),
+        );
+        uses.extend(pass.sampled.iter().map(|&image| (image, ImageUse::Sampled)));
+        uses
+    }
+
+    /// Which passes contribute to an output, walking backwards from them.
+    fn live_passes(&self) -> Vec<bool> {
+        let mut needed_images: Vec<bool> = vec![false; self.images.len()];
+        let mut needed_buffers: Vec<bool> = vec![false; self.buffers.len()];
+        for output in &self.outputs {
+            needed_images[output.0] = true;
+        }
+
+        let mut live = vec![false; self.passes.len()];
+        for (i, pass) in self.passes.iter().enumerate().rev() {
+            let uses = Self::pass_uses(pass);
+
+            let writes_needed = uses
+                .iter()
+                .any(|(image, usage)| usage.writes() && needed_images[image.0])
+                || pass
+                    .buffers
+                    .iter()
+                    .any(|b| b.write && needed_buffers[b.buffer.0]);
+            if !writes_needed {
+                log::debug!("render graph: culled pass '{}'", pass.name);
+                continue;
+            }
+            live[i] = true;
+
+            // attachments without a clear may load what earlier passes wrote
+            for &(image, clear) in &pass.colors {
+                if clear.is_none() {
+                    needed_images[image.0] = true;
+                }
+            }
+            if let Some((image, None, _)) = pass.depth {
+                needed_images[image.0] = true;
+            }
+            for image in &pass.sampled {
+                needed_images[image.0] = true;
+            }
+            for b in pass.buffers.iter().filter(|b| !b.write) {
+                needed_buffers[b.buffer.0] = true;
+            }
+        }
+
+        live
+    }
+
+    /// Allocates transient images, creates render passes and framebuffers
+    /// and builds every pass's pipelines. `extent` sizes `ImageSize::Swapchain`
+    /// images. Call `destroy` before compiling again.
+    pub fn compile(
+        &mut self,
+        dev: &Device,
+        extent: vk::Extent2D,
+        shaders: &ShaderLibrary,
+        set_layouts: &[vk::DescriptorSetLayout],
+    ) -> Result<()> {
+        self.dynamic_rendering = dev.dynamic_rendering.clone();
+        self.pipeline_cache = dev.pipeline_cache.cache;
+
+        let live = self.live_passes();
+        let order: Vec<usize> = (0..self.passes.len()).filter(|&i| live[i]).collect();
+
+        // uses of each image by live passes, in execution order
+        let mut image_uses: Vec<Vec<(usize, ImageUse)>> = vec![Vec::new(); self.images.len()];
+        for &p in &order {
+            for (image, usage) in Self::pass_uses(&self.passes[p]) {
+                if image_uses[image.0].iter().any(|&(q, _)| q == p) {
+                    anyhow::bail!(
+                        "pass '{}' uses image '{}' twice",
+                        self.passes[p].name,
+                        self.images[image.0].name
+                    );
+                }
+                image_uses[image.0].push((p, usage));
+            }
+        }
+
+        self.allocate_transients(dev, extent, &image_uses)?;
+
+        let mut buffer_states = self.initial_buffer_states();
+        for &p in &order {
+            let compiled = self
+                .compile_pass(dev, p, extent, &image_uses, &mut buffer_states)
+                .with_context(|| format!("render graph pass '{}'", self.passes[p].name))?;
+            self.compiled.push(compiled);
+        }
+
+        self.set_layouts = set_layouts.to_vec();
+
+        for i in 0..self.compiled.len() {
+            let node = self.compiled[i].node;
+            let samples = self.pass_samples(node);
+            let sampled = self.sampled_views(node);
+            let ctx = PipelineContext {
+                device: &dev.device,
+                cache: self.pipeline_cache,
+                target: self.compiled[i].target(),
+                samples,
+                shaders,
+                set_layouts: &self.set_layouts,
+                sampled: &sampled,
+            };
+            let pass = &mut self.passes[node];
+            pass.pass
+                .build_pipelines(&ctx)
+                .with_context(|| format!("pipelines for pass '{}'", pass.name))?;
+        }
+
+        Ok(())
+    }
+
+    fn allocate_transients(
+        &mut self,
+        dev: &Device,
+        extent: vk::Extent2D,
+        image_uses: &[Vec<(usize, ImageUse)>],
+    ) -> Result<()> {
+        self.transients = (0..self.images.len()).map(|_| None).collect();
+
+        for (i, uses) in image_uses.iter().enumerate() {
+            let ImageSource::Transient(desc) = self.images[i].source else {
+                continue;
+            };
+            if uses.is_empty() {
+                continue;
+            }
+
+            let depth = is_depth_format(desc.format);
+            let mut usage = vk::ImageUsageFlags::empty();
+            for &(_, u) in uses {
+                usage |= match u {
+                    ImageUse::Color | ImageUse::Resolve => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                    ImageUse::DepthWrite | ImageUse::DepthRead => {
+                        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                    }
+                    ImageUse::Sampled => vk::ImageUsageFlags::SAMPLED,
+                };
+            }
+            // never leaves the pass that uses it: may live in tile memory
+            if uses.len() == 1 && !usage.contains(vk::ImageUsageFlags::SAMPLED) {
+                usage |= vk::ImageUsageFlags::TRANSIENT_ATTACHMENT;
+            }
+
+            let size = self.extent(ImageHandle(i), extent);
+            let image = create_image(dev, size, 1, desc.samples, desc.format, usage)?;
+            // sampled views may only have one aspect
+            let aspect = if !depth {
+                vk::ImageAspectFlags::COLOR
+            } else if has_stencil(desc.format) && !usage.contains(vk::ImageUsageFlags::SAMPLED) {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            } else {
+                vk::ImageAspectFlags::DEPTH
+            };
+            let view = create_image_view(&dev.device, image.image, desc.format, aspect, 1)?;
+
+            log::debug!(
+                "render graph: '{}' {}x{} {:?} x{}",
+                self.images[i].name,
+                size.width,
+                size.height,
+                desc.format,
+                desc.samples.as_raw()
+            );
+            self.transients[i] = Some((image, view));
+        }
+
+        Ok(())
+    }
+
+    fn pass_samples(&self, p: usize) -> vk::SampleCountFlags {
+        let pass = &self.passes[p];
+        pass.colors
+            .first()
+            .map(|&(image, _)| image)
+            .or(pass.depth.map(|(image, _, _)| image))
+            .map_or(vk::SampleCountFlags::TYPE_1, |image| self.samples(image))
+    }
+
+    fn sampled_views(&self, p: usize) -> Vec<(vk::ImageView, vk::ImageLayout)> {
+        self.passes[p]
+            .sampled
+            .iter()
+            .map(|&image| (self.image_view(image, 0), self.sampled_layout(image)))
+            .collect()
+    }
+
+    fn compile_pass(
+        &self,
+        dev: &Device,
+        p: usize,
+        swapchain_extent: vk::Extent2D,
+        image_uses: &[Vec<(usize, ImageUse)>],
+        buffer_states: &mut [BufferState],
+    ) -> Result<CompiledPass> {
+        let pass = &self.passes[p];
+
+        // (image, use, clear) in attachment order: colors, depth, resolves
+        let mut attachments: Vec<(ImageHandle, ImageUse, Option<vk::ClearValue>)> = pass
+            .colors
+            .iter()
+            .map(|&(image, clear)| (image, ImageUse::Color, clear))
+            .collect();
+        if let Some((image, clear, write)) = pass.depth {
+            let usage = if write {
+                ImageUse::DepthWrite
+            } else {
+                ImageUse::DepthRead
+            };
+            attachments.push((image, usage, clear));
+        }
+        attachments.extend(
+            pass.resolves
+                .iter()
+                .map(|&image| (image, ImageUse::Resolve, None)),
+        );
+
+        let mut src_stage = vk::PipelineStageFlags::empty();
+        let mut src_access = vk::AccessFlags::empty();
+        let mut dst_stage = vk::PipelineStageFlags::empty();
+        let mut dst_access = vk::AccessFlags::empty();
+        let mut out_src_stage = vk::PipelineStageFlags::empty();
+        let mut out_src_access = vk::AccessFlags::empty();
+        let mut out_dst_stage = vk::PipelineStageFlags::empty();
+        let mut out_dst_access = vk::AccessFlags::empty();
+
+        let mut plans: Vec<AttachmentPlan> = Vec::new();
+        // explicit barriers, only used without render passes
+        let mut before: Vec<ImageBarrier> = Vec::new();
+        let mut after: Vec<ImageBarrier> = Vec::new();
+        let mut extent = None;
+
+        let all_uses = attachments
+            .iter()
+            .map(|&(image, usage, clear)| (image, usage, clear))
+            .chain(
+                pass.sampled
+                    .iter()
+                    .map(|&image| (image, ImageUse::Sampled, None)),
+            );
+
+        for (image, usage, clear) in all_uses {
+            let uses = &image_uses[image.0];
+            let k = uses
+                .iter()
+                .position(|&(q, _)| q == p)
+                .expect("pass missing from its image's use list");
+
+            // first use of the frame waits on the last one of the previous
+            // frame (same image, shared between frames in flight)
+            let prev = if k > 0 {
+                uses[k - 1]
+            } else {
+                *uses.last().unwrap()
+            };
+            src_stage |= prev.1.stage();
+            if prev.1.writes() {
+                src_access |= prev.1.access();
+            }
+            dst_stage |= usage.stage();
+            dst_access |= usage.access();
+
+            if usage.writes() {
+                out_src_stage |= usage.stage();
+                out_src_access |= usage.access();
+            }
+
+            let imported_final = match &self.images[image.0].source {
+                ImageSource::Imported { final_layout, .. } => Some(*final_layout),
+                ImageSource::Transient(_) => None,
+            };
+            let next = uses.get(k + 1).copied();
+            match (next, imported_final) {
+                (Some((_, next_use)), _) => {
+                    out_dst_stage |= next_use.stage();
+                    out_dst_access |= next_use.access();
+                }
+                (None, Some(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)) => {
+                    out_dst_stage |= vk::PipelineStageFlags::TRANSFER;
+                    out_dst_access |= vk::AccessFlags::TRANSFER_READ;
+                }
+                _ => {}
+            }
+
+            let format = self.format(image);
+            let depth = is_depth_format(format);
+
+            if usage == ImageUse::Sampled {
+                if k == 0 {
+                    anyhow::bail!(
+                        "'{}' is sampled before any pass writes it",
+                        self.images[image.0].name
+                    );
+                }
+                before.extend(ImageBarrier::between(image, prev.1, usage, depth, false));
+                continue;
+            }
+
+            let size = self.extent(image, swapchain_extent);
+            if *extent.get_or_insert(size) != size {
+                anyhow::bail!(
+                    "attachment '{}' is {}x{}, other attachments differ",
+                    self.images[image.0].name,
+                    size.width,
+                    size.height
+                );
+            }
+
+            let written_before = uses[..k].iter().any(|&(_, u)| u.writes());
+
+            // a loaded image is already in this use's layout: the previous
+            // pass's render pass moved it there (`final_layout` below)
+            let (load_op, initial_layout) = if clear.is_some() {
+                (vk::AttachmentLoadOp::CLEAR, vk::ImageLayout::UNDEFINED)
+            } else if written_before || usage == ImageUse::DepthRead {
+                (vk::AttachmentLoadOp::LOAD, usage.layout(depth))
+            } else {
+                (vk::AttachmentLoadOp::DONT_CARE, vk::ImageLayout::UNDEFINED)
+            };
+            if load_op == vk::AttachmentLoadOp::LOAD && k == 0 {
+                anyhow::bail!(
+                    "'{}' is read before any pass writes it",
+                    self.images[image.0].name
+                );
+            }
+
+            let keep = next.is_some() || imported_final.is_some();
+            let store_op = if keep {
+                vk::AttachmentStoreOp::STORE
+            } else {
+                vk::AttachmentStoreOp::DONT_CARE
+            };
+            let final_layout = match (next, imported_final) {
+                (Some((_, next_use)), _) => next_use.layout(depth),
+                (None, Some(layout)) => layout,
+                (None, None) => usage.layout(depth),
+            };
+
+            let discard = load_op != vk::AttachmentLoadOp::LOAD;
+            before.extend(ImageBarrier::between(image, prev.1, usage, depth, discard));
+            if let (None, Some(layout)) = (next, imported_final) {
+                after.push(ImageBarrier::to_final(image, usage, depth, layout));
+            }
+
+            plans.push(AttachmentPlan {
+                image,
+                format,
+                samples: self.samples(image),
+                load_op,
+                store_op,
+                initial_layout,
+                final_layout,
+                layout: usage.layout(depth),
+                clear: clear.unwrap_or_default(),
+            });
+        }
+
+        let extent = extent.context("pass has no attachments")?;
+        let colors = pass.colors.len();
+        let has_depth = pass.depth.is_some() as usize;
+
+        let clear_values = plans.iter().map(|a| a.clear).collect();
+        if let Some(b) = pass
+            .buffers
+            .iter()
+            .find(|b| !b.write && buffer_states[b.buffer.0].write.is_none())
+        {
+            anyhow::bail!(
+                "buffer '{}' is read before any pass writes it",
+                self.buffers[b.buffer.0].name
+            );
+        }
+        let buffer_barrier = Self::buffer_barrier(pass, buffer_states);
+
+        if self.dynamic_rendering.is_some() {
+            let dynamic = DynamicPass {
+                color_formats: plans[..colors].iter().map(|a| a.format).collect(),
+                depth_format: pass
+                    .depth
+                    .map_or(vk::Format::UNDEFINED, |_| plans[colors].format),
+                colors: plans[..colors].to_vec(),
+                depth: pass.depth.map(|_| plans[colors]),
+                resolves: plans[colors + has_depth..].to_vec(),
+                before,
+                after,
+            };
+
+            return Ok(CompiledPass {
+                node: p,
+                render_pass: vk::RenderPass::null(),
+                framebuffers: Vec::new(),
+                extent,
+                clear_values,
+                buffer_barrier,
+                dynamic: Some(dynamic),
+            });
+        }
+
+        let descs: Vec<vk::AttachmentDescription> = plans
+            .iter()
+            .map(|a| {
+                vk::AttachmentDescription::default()
+                    .format(a.format)
+                    .samples(a.samples)
+                    .load_op(a.load_op)
+                    .store_op(a.store_op)
+                    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(a.initial_layout)
+                    .final_layout(a.final_layout)
+            })
+            .collect();
+
+        let dependencies = [
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(or_top(src_stage))
+                .dst_stage_mask(dst_stage)
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(or_top(out_src_stage))
+                .dst_stage_mask(if out_dst_stage.is_empty() {
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE
+                } else {
+                    out_dst_stage
+                })
+                .src_access_mask(out_src_access)
+                .dst_access_mask(out_dst_access),
+        ];
+
+        let render_pass = create_render_pass(
+            &dev.device,
+            &RenderPassDesc {
+                colors: &descs[..colors],
+                depth: pass.depth.map(|_| descs[colors]),
+                depth_read_only: matches!(pass.depth, Some((_, _, false))),
+                resolves: &descs[colors + has_depth..],
+                dependencies: &dependencies,
+            },
+        )?;
+
+        let variants = attachments
+            .iter()
+            .map(|&(image, _, _)| self.variant_count(image))
+            .max()
+            .unwrap_or(1);
+
+        let mut framebuffers = Vec::with_capacity(variants);
+        for v in 0..variants {
+            let views: Vec<vk::ImageView> = attachments
+                .iter()
+                .map(|&(image, _, _)| self.image_view(image, v))
+                .collect();
+            framebuffers.push(create_framebuffer(
+                &dev.device,
+                render_pass,
+                extent,
+                &views,
+            )?);
+        }
+
+        Ok(CompiledPass {
+            node: p,
+            render_pass,
+            framebuffers,
+            extent,
+            clear_values,
+            buffer_barrier,
+            dynamic: None,
+        })
+    }
+
+    /// Buffer states at the start of the frame: written outside the graph
+    /// (`written_before`) or not at all yet.
+    fn initial_buffer_states(&self) -> Vec<BufferState> {
+        self.buffers
+            .iter()
+            .map(|b| BufferState {
+                write: b.external_write,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// One global barrier covering the hazards of `pass` on the buffers it
+    /// declares: reads after a write not yet visible to them, and writes
+    /// after earlier writes or reads. Advances `states` past the pass.
+    fn buffer_barrier(pass: &PassNode, states: &mut [BufferState]) -> Option<BufferBarrier> {
+        let mut src = (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty());
+        let mut dst = (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty());
+
+        for b in pass.buffers.iter().filter(|b| !b.write) {
+            let state = &mut states[b.buffer.0];
+            if let Some((stage, access)) = state.write
+                && !(state.visible.0.contains(b.stage) && state.visible.1.contains(b.access))
+            {
+                src.0 |= stage;
+                src.1 |= access;
+                dst.0 |= b.stage;
+                dst.1 |= b.access;
+                state.visible.0 |= b.stage;
+                state.visible.1 |= b.access;
+            }
+            state.readers |= b.stage;
+        }
+        for b in pass.buffers.iter().filter(|b| b.write) {
+            let state = &mut states[b.buffer.0];
+            if let Some((stage, access)) = state.write {
+                src.0 |= stage;
+                src.1 |= access;
+                dst.0 |= b.stage;
+                dst.1 |= b.access;
+            }
+            // write after read: execution dependency only
+            if !state.readers.is_empty() {
+                src.0 |= state.readers;
+                dst.0 |= b.stage;
+            }
+            *state = BufferState {
+                write: Some((b.stage, b.access)),
+                ..Default::default()
+            };
+        }
+
+        (!src.0.is_empty()).then(|| BufferBarrier {
+            src_stage: src.0,
+            dst_stage: dst.0,
+            barrier: vk::MemoryBarrier::default()
+                .src_access_mask(src.1)
+                .dst_access_mask(dst.1),
+        })
+    }
+
+    fn vk_image(&self, image: ImageHandle, variant: usize) -> vk::Image {
+        match &self.images[image.0].source {
+            ImageSource::Transient(_) => {
+                self.transients[image.0]
+                    .as_ref()
+                    .expect("image is not used by any live pass")
+                    .0
+                    .image
+            }
+            ImageSource::Imported { images, .. } => images[variant % images.len()],
+        }
+    }
+
+    fn record_image_barriers(
+        &self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        variant: usize,
+        barriers: &[ImageBarrier],
+    ) {
+        if barriers.is_empty() {
+            return;
+        }
+
+        let mut src_stage = vk::PipelineStageFlags::empty();
+        let mut dst_stage = vk::PipelineStageFlags::empty();
+        let vk_barriers: Vec<vk::ImageMemoryBarrier> = barriers
+            .iter()
+            .map(|b| {
+                src_stage |= b.src_stage;
+                dst_stage |= b.dst_stage;
+
+                let format = self.format(b.image);
+                let aspect = if !is_depth_format(format) {
+                    vk::ImageAspectFlags::COLOR
+                } else if has_stencil(format) {
+                    vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+                } else {
+                    vk::ImageAspectFlags::DEPTH
+                };
+
+                vk::ImageMemoryBarrier::default()
+                    .old_layout(b.old_layout)
+                    .new_layout(b.new_layout)
+                    .src_access_mask(b.src_access)
+                    .dst_access_mask(b.dst_access)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(self.vk_image(b.image, variant))
+                    .subresource_range(
+                        vk::ImageSubresourceRange::default()
+                            .aspect_mask(aspect)
+                            .level_count(1)
+                            .layer_count(1),
+                    )
+            })
+            .collect();
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cmd,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &vk_barriers,
+            );
+        }
+    }
+
+    fn begin_rendering(
+        &self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        variant: usize,
+        extent: vk::Extent2D,
+        dynamic: &DynamicPass,
+    ) {
+        let attachment_info = |a: &AttachmentPlan| {
+            vk::RenderingAttachmentInfo::default()
+                .image_view(self.image_view(a.image, variant))
+                .image_layout(a.layout)
+                .load_op(a.load_op)
+                .store_op(a.store_op)
+                .clear_value(a.clear)
+        };
+
+        let colors: Vec<vk::RenderingAttachmentInfo> = dynamic
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                let info = attachment_info(a);
+                match dynamic.resolves.get(i) {
+                    Some(resolve) => info
+                        .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                        .resolve_image_view(self.image_view(resolve.image, variant))
+                        .resolve_image_layout(resolve.layout),
+                    None => info,
+                }
+            })
+            .collect();
+        let depth = dynamic.depth.as_ref().map(attachment_info);
+
+        let mut info = vk::RenderingInfo::default()
+            .render_area(vk::Rect2D::default().extent(extent))
+            .layer_count(1)
+            .color_attachments(&colors);
+        if let Some(depth) = &depth {
+            info = info.depth_attachment(depth);
+        }
+
+        let loader = self
+            .dynamic_rendering
+            .as_ref()
+            .expect("dynamic pass without dynamic rendering");
+        unsafe {
+            loader.cmd_begin_rendering(device, cmd, &info);
+        }
+    }
+
+    /// Records every live pass into `cmd` (which must be recording).
+    /// `variant` selects the imported image, e.g. the swapchain index.
+    pub fn execute(
+        &self,
+        device: &ash::Device,
+        cmd: vk::CommandBuffer,
+        variant: usize,
+        frame: &FrameContext,
+    ) {
+        for compiled in &self.compiled {
+            let node = &self.passes[compiled.node];
+
+            if let Some(b) = &compiled.buffer_barrier {
+                unsafe {
+                    device.cmd_pipeline_barrier(
+                        cmd,
+                        b.src_stage,
+                        b.dst_stage,
+                        vk::DependencyFlags::empty(),
+                        &[b.barrier],
+                        &[],
+                        &[],
+                    );
+                }
+            }
+
+            match &compiled.dynamic {
+                Some(dynamic) => {
+                    self.record_image_barriers(device, cmd, variant, &dynamic.before);
+                    self.begin_rendering(device, cmd, variant, compiled.extent, dynamic);
+                }
+                None => {
+                    let framebuffer = compiled.framebuffers[variant % compiled.framebuffers.len()];
+                    let begin = vk::RenderPassBeginInfo::default()
+                        .render_pass(compiled.render_pass)
+                        .framebuffer(framebuffer)
+                        .render_area(vk::Rect2D::default().extent(compiled.extent))
+                        .clear_values(&compiled.clear_values);
+                    unsafe {
+                        device.cmd_begin_render_pass(cmd, &begin, vk::SubpassContents::INLINE);
+                    }
+                }
+            }
+
+            unsafe {
+                let viewport = vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: compiled.extent.width as f32,
+                    height: compiled.extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                };
+                device.cmd_set_viewport(cmd, 0, &[viewport]);
+                device.cmd_set_scissor(cmd, 0, &[vk::Rect2D::default().extent(compiled.extent)]);
+            }
+
+            let ctx = PassContext {
+                device,
+                cmd,
+                extent: compiled.extent,
+            };
+            node.pass.record(&ctx, frame);
+
+            match &compiled.dynamic {
+                Some(dynamic) => {
+                    if let Some(loader) = &self.dynamic_rendering {
+                        unsafe { loader.cmd_end_rendering(device, cmd) };
+                    }
+                    self.record_image_barriers(device, cmd, variant, &dynamic.after);
+                }
+                None => unsafe { device.cmd_end_render_pass(cmd) },
+            }
+        }
+    }
+
+    /// True if a live pass builds pipelines from any of `names`.
+    pub fn uses_any_shader(&self, names: &[String]) -> bool {
+        self.compiled.iter().any(|c| {
+            self.passes[c.node]
+                .pass
+                .shaders()
+                .iter()
+                .any(|s| names.iter().any(|n| n == s))
+        })
+    }
+
+    /// Rebuilds the pipelines of passes that use any of `changed`. The GPU
+    /// must be idle. Passes that fail keep their old pipelines; their
+    /// shader names are returned so the caller can roll the SPIR-V back.
+    pub fn rebuild_pipelines(
+        &mut self,
+        device: &ash::Device,
+        shaders: &ShaderLibrary,
+        changed: &[String],
+    ) -> Vec<String> {
+        let mut failed = Vec::new();
+
+        for i in 0..self.compiled.len() {
+            let node = self.compiled[i].node;
+            let affected = self.passes[node]
+                .pass
+                .shaders()
+                .iter()
+                .any(|s| changed.iter().any(|n| n == s));
+            if !affected {
+                continue;
+            }
+
+            let samples = self.pass_samples(node);
+            let sampled = self.sampled_views(node);
+            let ctx = PipelineContext {
+                device,
+                cache: self.pipeline_cache,
+                target: self.compiled[i].target(),
+                samples,
+                shaders,
+                set_layouts: &self.set_layouts,
+                sampled: &sampled,
+            };
+            let pass = &mut self.passes[node];
+            match pass.pass.build_pipelines(&ctx) {
+                Ok(()) => log::info!("Rebuilt pipelines for pass '{}'", pass.name),
+                Err(e) => {
+                    log::error!("Keeping old pipelines for pass '{}': {:#}", pass.name, e);
+                    failed.extend(pass.pass.shaders().into_iter().map(String::from));
+                }
+            }
+        }
+
+        failed
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device) {
+        for pass in &mut self.passes {
+            pass.pass.destroy(device);
+        }
+        unsafe {
+            for compiled in self.compiled.drain(..) {
+                for fb in compiled.framebuffers {
+                    device.destroy_framebuffer(fb, None);
+                }
+                if compiled.render_pass != vk::RenderPass::null() {
+                    device.destroy_render_pass(compiled.render_pass, None);
+                }
+            }
+            for (image, view) in self.transients.drain(..).flatten() {
+                device.destroy_image_view(view, None);
+                image.destroy(device);
+            }
+        }
+    }
+}
+
+fn or_top(stage: vk::PipelineStageFlags) -> vk::PipelineStageFlags {
+    if stage.is_empty() {
+        vk::PipelineStageFlags::TOP_OF_PIPE
+    } else {
+        stage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopPass;
+
+    impl GraphPass for NoopPass {
+        fn build_pipelines(&mut self, _: &PipelineContext) -> Result<()> {
+            Ok(())
+        }
+        fn record(&self, _: &PassContext, _: &FrameContext) {}
+        fn destroy(&mut self, _: &ash::Device) {}
+    }
+
+    fn image(graph: &mut RenderGraph, name: &str) -> ImageHandle {
+        graph.create_image(
+            name,
+            ImageDesc {
+                format: vk::Format::R8G8B8A8_UNORM,
+                size: ImageSize::Swapchain,
+                samples: vk::SampleCountFlags::TYPE_1,
+            },
+        )
+    }
+
+    #[test]
+    fn passes_not_leading_to_an_output_are_culled() {
+        let mut graph = RenderGraph::new();
+        let (shadow, unused, out) = (
+            image(&mut graph, "shadow"),
+            image(&mut graph, "unused"),
+            image(&mut graph, "out"),
+        );
+        graph.add_pass("shadow", NoopPass).depth(shadow, Some(1.0));
+        graph.add_pass("unused", NoopPass).color(unused, None);
+        graph
+            .add_pass("scene", NoopPass)
+            .color(out, Some([0.0; 4]))
+            .sample(shadow);
+        graph.set_output(out);
+
+        assert_eq!(graph.live_passes(), [true, false, true]);
+    }
+
+    #[test]
+    fn loaded_attachments_keep_earlier_writers() {
+        let mut graph = RenderGraph::new();
+        let (hdr, out) = (image(&mut graph, "hdr"), image(&mut graph, "out"));
+        graph.add_pass("scene", NoopPass).color(hdr, Some([0.0; 4]));
+        // loads what "scene" wrote
+        graph.add_pass("debug", NoopPass).color(hdr, None);
+        graph
+            .add_pass("post", NoopPass)
+            .color(out, None)
+            .sample(hdr);
+        graph.set_output(out);
+
+        assert_eq!(graph.live_passes(), [true, true, true]);
+
+        // nothing reads `hdr` any more
+        graph.passes[2].sampled.clear();
+        assert_eq!(graph.live_passes(), [false, false, true]);
+    }
+
+    #[test]
+    fn pass_inputs_are_looked_up_by_type() {
+        struct Lines(u32);
+        struct Quads(u32);
+
+        let mut inputs = PassInputs::new();
+        inputs.insert(Lines(1));
+        inputs.insert(Lines(2));
+        assert_eq!(inputs.get::<Lines>().map(|l| l.0), Some(2));
+        assert!(inputs.get::<Quads>().is_none());
+
+        inputs.insert(Quads(3));
+        assert_eq!(inputs.get::<Quads>().map(|q| q.0), Some(3));
+    }
+
+    /// (src, dst) stages of each pass's buffer barrier, in pass order.
+    fn buffer_barriers(
+        graph: &RenderGraph,
+    ) -> Vec<Option<(vk::PipelineStageFlags, vk::PipelineStageFlags)>> {
+        let mut states = graph.initial_buffer_states();
+        graph
+            .passes
+            .iter()
+            .map(|pass| {
+                RenderGraph::buffer_barrier(pass, &mut states).map(|b| (b.src_stage, b.dst_stage))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn passes_writing_read_buffers_stay_live() {
+        use vk::{AccessFlags as A, PipelineStageFlags as S};
+
+        let mut graph = RenderGraph::new();
+        let (mask, out) = (image(&mut graph, "mask"), image(&mut graph, "out"));
+        let visible = graph.buffer("visible", None);
+        graph.add_pass("cull", NoopPass).color(mask, None).buffer(
+            visible,
+            S::VERTEX_SHADER,
+            A::SHADER_WRITE,
+        );
+        graph
+            .add_pass("scene", NoopPass)
+            .color(out, Some([0.0; 4]))
+            .buffer(visible, S::DRAW_INDIRECT, A::INDIRECT_COMMAND_READ);
+        graph.set_output(out);
+
+        assert_eq!(graph.live_passes(), [true, true]);
+
+        graph.passes[1].buffers.clear();
+        assert_eq!(graph.live_passes(), [false, true]);
+    }
+
+    #[test]
+    fn imported_buffers_sync_their_first_reader_only() {
+        use vk::{AccessFlags as A, PipelineStageFlags as S};
+
+        let mut graph = RenderGraph::new();
+        let out = image(&mut graph, "out");
+        let meshes = graph.buffer("meshes", Some((S::TRANSFER, A::TRANSFER_WRITE)));
+        for name in ["shadow", "scene"] {
+            graph.add_pass(name, NoopPass).color(out, None).buffer(
+                meshes,
+                S::VERTEX_INPUT,
+                A::VERTEX_ATTRIBUTE_READ,
+            );
+        }
+
+        assert_eq!(
+            buffer_barriers(&graph),
+            [Some((S::TRANSFER, S::VERTEX_INPUT)), None]
+        );
+    }
+
+    #[test]
+    fn buffer_writes_wait_for_earlier_writes_and_reads() {
+        use vk::{AccessFlags as A, PipelineStageFlags as S};
+
+        let mut graph = RenderGraph::new();
+        let out = image(&mut graph, "out");
+        let particles = graph.buffer("particles", None);
+        graph
+            .add_pass("simulate", NoopPass)
+            .color(out, None)
+            .buffer(particles, S::VERTEX_SHADER, A::SHADER_WRITE);
+        graph.add_pass("draw", NoopPass).color(out, None).buffer(
+            particles,
+            S::VERTEX_INPUT,
+            A::VERTEX_ATTRIBUTE_READ,
+        );
+        graph.add_pass("respawn", NoopPass).color(out, None).buffer(
+            particles,
+            S::FRAGMENT_SHADER,
+            A::SHADER_WRITE,
+        );
+
+        assert_eq!(
+            buffer_barriers(&graph),
+            [
+                None,
+                Some((S::VERTEX_SHADER, S::VERTEX_INPUT)),
+                Some((S::VERTEX_SHADER | S::VERTEX_INPUT, S::FRAGMENT_SHADER)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::core::device::Device;
+use crate::renderer::material::{BlendMode, PipelineKey};
+use crate::renderer::mesh::MeshBuffers;
+use crate::renderer::render_types::RenderItem;
+use crate::resources::buffer::{GpuBuffer, InstanceData, create_buffer};
+use crate::scene::material_store::{MaterialId, MaterialStore};
+use anyhow::Result;
+use ash::vk::{self, Handle};
+use glam::Mat4;
+
+/// Smallest instance buffer we allocate, in instances.
+const MIN_INSTANCE_CAPACITY: usize = 256;
+
+/// One instanced draw: `instance_count` copies of `mesh`, reading model
+/// matrices `first_instance..` from the frame's instance buffer.
+pub struct DrawBatch {
+    pub mesh: MeshBuffers,
+    pub material: MaterialId,
+    /// Usually the material's key; forced to `AlphaBlend` for items with
+    /// `opacity < 1`.
+    pub pipeline: PipelineKey,
+    pub first_instance: u32,
+    pub instance_count: u32,
+}
+
+/// Splits `items` into an opaque and a transparent pass. Opaque items are
+/// sorted by pipeline, material and mesh; transparent ones back to front
+/// by view depth (clip-space `w` under `view_proj`). Consecutive items
+/// with the same mesh, material and pipeline are merged into one batch.
+/// Model matrices are appended to `instances` in batch order.
+pub fn build_batches<'a>(
+    items: &[RenderItem<'a>],
+    materials: &MaterialStore,
+    view_proj: Mat4,
+    instances: &mut Vec<InstanceData>,
+) -> Vec<DrawBatch> {
+    let mut opaque: Vec<(PipelineKey, &RenderItem<'a>)> = Vec::new();
+    let mut transparent: Vec<(PipelineKey, f32, &RenderItem<'a>)> = Vec::new();
+
+    for item in items {
+        let material = materials.get(item.material);
+        if material.blend == BlendMode::AlphaBlend || item.opacity < 1.0 {
+            let key = PipelineKey::new(BlendMode::AlphaBlend, material.cull);
+            let depth = (view_proj * item.model.w_axis).w;
+            transparent.push((key, depth, item));
+        } else {
+            opaque.push((material.pipeline_key(), item));
+        }
+    }
+
+    opaque
+        .sort_by_key(|(key, item)| (*key, item.material, item.mesh.vertex_buffer.buffer.as_raw()));
+    // farthest first; blending is order dependent
+    transparent.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut batches: Vec<DrawBatch> = Vec::new();
+
+    let ordered = opaque
+        .into_iter()
+        .chain(transparent.into_iter().map(|(key, _, item)| (key, item)));
+
+    for (pipeline, item) in ordered {
+        let first_instance = instances.len() as u32;
+        instances.push(InstanceData {
+            model: item.model.to_cols_array_2d(),
+            opacity: item.opacity,
+        });
+
+        match batches.last_mut() {
+            Some(batch)
+                if batch.pipeline == pipeline
+                    && batch.material == item.material
+                    && batch.mesh == item.mesh.buffers() =>
+            {
+                batch.instance_count += 1;
+            }
+            _ => batches.push(DrawBatch {
+                mesh: item.mesh.buffers(),
+                material: item.material,
+                pipeline,
+                first_instance,
+                instance_count: 1,
+            }),
+        }
+    }
+
+    batches
+}
+
+/// Batches of one frame and the instance buffer they read from.
+pub struct DrawList {
+    pub instance_buffer: vk::Buffer,
+    pub batches: Vec<DrawBatch>,
+}
+
+/// Host-visible vertex buffer of `InstanceData`, persistently mapped and
+/// grown on demand. One per frame slot, so it is only rewritten once the
+/// frame that last read it has finished.
+pub struct InstanceBuffer {
+    buffer: Option<GpuBuffer>,
+    capacity: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new() -> Self {
+        Self {
+            buffer: None,
+            capacity: 0,
+        }
+    }
+
+    /// `vk::Buffer::null()` until the first `write`.
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+            .as_ref()
+            .map_or(vk::Buffer::null(), |b| b.buffer)
+    }
+
+    /// Copies `instances` to the start of the buffer, reallocating if it
+    /// is too small. The GPU must be done with this buffer.
+    pub fn write(&mut self, dev: &Device, instances: &[InstanceData]) -> Result<()> {
+        if instances.is_empty() {
+            return Ok(());
+        }
+
+        if instances.len() > self.capacity {
+            self.destroy(&dev.device);
+
+            let capacity = instances
+                .len()
+                .next_power_of_two()
+                .max(MIN_INSTANCE_CAPACITY);
+            let size = (capacity * std::mem::size_of::<InstanceData>()) as u64;
+            let buffer = create_buffer(
+                dev,
+                size,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )?;
+
+            self.buffer = Some(buffer);
+            self.capacity = capacity;
+        }
+
+        if let Some(buffer) = &self.buffer {
+            buffer.write_bytes(bytemuck::cast_slice(instances));
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device) {
+        if let Some(buffer) = self.buffer.take() {
+            buffer.destroy(device);
+        }
+        self.capacity = 0;
+    }
+}
@@ -0,0 +1,77 @@
+use ash::vk;
+use glam::Vec4;
+
+use crate::scene::material_store::TextureId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BlendMode {
+    Opaque,
+    /// Straight alpha (`src * a + dst * (1 - a)`), no depth writes.
+    AlphaBlend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+impl CullMode {
+    pub fn to_vk(self) -> vk::CullModeFlags {
+        match self {
+            CullMode::None => vk::CullModeFlags::NONE,
+            CullMode::Back => vk::CullModeFlags::BACK,
+            CullMode::Front => vk::CullModeFlags::FRONT,
+        }
+    }
+}
+
+/// The fixed-function state a material needs; one pipeline per key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PipelineKey {
+    pub blend: BlendMode,
+    pub cull: CullMode,
+}
+
+impl PipelineKey {
+    pub const ALL: [PipelineKey; 6] = [
+        PipelineKey::new(BlendMode::Opaque, CullMode::None),
+        PipelineKey::new(BlendMode::Opaque, CullMode::Back),
+        PipelineKey::new(BlendMode::Opaque, CullMode::Front),
+        PipelineKey::new(BlendMode::AlphaBlend, CullMode::None),
+        PipelineKey::new(BlendMode::AlphaBlend, CullMode::Back),
+        PipelineKey::new(BlendMode::AlphaBlend, CullMode::Front),
+    ];
+
+    pub const fn new(blend: BlendMode, cull: CullMode) -> Self {
+        Self { blend, cull }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    /// Linear RGBA, multiplied with the vertex color and the texture.
+    pub base_color: Vec4,
+    /// `None` samples a 1x1 white texture.
+    pub base_color_texture: Option<TextureId>,
+    pub blend: BlendMode,
+    pub cull: CullMode,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::ONE,
+            base_color_texture: None,
+            blend: BlendMode::Opaque,
+            cull: CullMode::None,
+        }
+    }
+}
+
+impl Material {
+    pub fn pipeline_key(&self) -> PipelineKey {
+        PipelineKey::new(self.blend, self.cull)
+    }
+}
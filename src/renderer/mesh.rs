@@ -1,12 +1,33 @@
 use crate::resources::buffer::GpuBuffer;
+use crate::scene::culling::{Aabb, BoundingSphere};
+use ash::vk;
 
 pub struct Mesh {
     pub vertex_buffer: GpuBuffer,
     pub index_buffer: GpuBuffer,
     pub index_count: u32,
+    /// Object-space bounds, used for culling.
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+/// What a draw needs from a `Mesh`, handed to passes without borrowing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshBuffers {
+    pub vertex_buffer: vk::Buffer,
+    pub index_buffer: vk::Buffer,
+    pub index_count: u32,
 }
 
 impl Mesh {
+    pub fn buffers(&self) -> MeshBuffers {
+        MeshBuffers {
+            vertex_buffer: self.vertex_buffer.buffer,
+            index_buffer: self.index_buffer.buffer,
+            index_count: self.index_count,
+        }
+    }
+
     pub fn destroy(&self, device: &ash::Device) {
         self.vertex_buffer.destroy(device);
         self.index_buffer.destroy(device);
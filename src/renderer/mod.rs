@@ -1,8 +1,21 @@
+pub mod capture;
 pub mod command_buffers;
 pub mod framebuffers;
 pub mod pipeline;
 pub mod render_pass;
 pub mod renderer;
+pub mod frame_data;
 pub mod error;
 pub mod mesh;
+pub mod dynamic_mesh;
+pub mod offscreen;
 pub mod render_types;
+pub mod texture;
+pub mod material;
+pub mod instancing;
+pub mod shadow;
+pub mod graph;
+pub mod scene_pass;
+pub mod debug_draw;
+pub mod text;
+pub mod post;
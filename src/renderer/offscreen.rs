@@ -0,0 +1,88 @@
+use crate::core::device::Device;
+use crate::renderer::renderer::find_memory_type_fallback;
+use crate::resources::allocator::{Allocation, ResourceKind};
+use anyhow::Result;
+use ash::vk;
+
+/// Color format of the headless target. Matches the swapchain format we
+/// prefer so offscreen output looks the same as the windowed one.
+pub const OFFSCREEN_COLOR_FORMAT: vk::Format = vk::Format::B8G8R8A8_SRGB;
+
+/// Color image the renderer draws into when there is no swapchain.
+pub struct OffscreenTarget {
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub view: vk::ImageView,
+}
+
+impl OffscreenTarget {
+    pub fn new(dev: &Device, extent: vk::Extent2D, format: vk::Format) -> Result<Self> {
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            // TRANSFER_SRC so the frame can be read back
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        let image = unsafe { dev.device.create_image(&image_info, None)? };
+        let reqs = unsafe { dev.device.get_image_memory_requirements(image) };
+
+        let mem_index = find_memory_type_fallback(
+            &dev.memory_properties,
+            reqs.memory_type_bits,
+            &[
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                vk::MemoryPropertyFlags::empty(),
+            ],
+        )?;
+
+        let allocation = dev
+            .allocator
+            .allocate(reqs, mem_index, ResourceKind::Optimal)?;
+        unsafe {
+            dev.device
+                .bind_image_memory(image, allocation.memory, allocation.offset)?
+        };
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(
+                vk::ImageSubresourceRange::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .level_count(1)
+                    .layer_count(1),
+            );
+
+        let view = unsafe { dev.device.create_image_view(&view_info, None)? };
+
+        Ok(Self {
+            format,
+            extent,
+            image,
+            allocation,
+            view,
+        })
+    }
+
+    pub fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+        }
+        self.allocation.free();
+    }
+}
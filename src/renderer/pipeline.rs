@@ -1,10 +1,61 @@
-use crate::resources::buffer::Vertex;
+use crate::renderer::material::{BlendMode, PipelineKey};
+use crate::resources::buffer::{InstanceData, Vertex};
 use anyhow::{Context, Result};
 use ash::vk;
+use std::collections::HashMap;
 
-pub struct Pipeline {
+/// What a pipeline draws into: subpass 0 of a render pass, or the
+/// attachment formats of a dynamic rendering pass.
+#[derive(Debug, Clone, Copy)]
+pub enum PipelineTarget<'a> {
+    RenderPass(vk::RenderPass),
+    Dynamic {
+        color_formats: &'a [vk::Format],
+        /// `UNDEFINED` without a depth attachment.
+        depth_format: vk::Format,
+    },
+}
+
+impl<'a> PipelineTarget<'a> {
+    fn apply(
+        self,
+        info: vk::GraphicsPipelineCreateInfo<'a>,
+        rendering: &'a mut vk::PipelineRenderingCreateInfo<'a>,
+    ) -> vk::GraphicsPipelineCreateInfo<'a> {
+        match self {
+            PipelineTarget::RenderPass(render_pass) => info.render_pass(render_pass).subpass(0),
+            PipelineTarget::Dynamic {
+                color_formats,
+                depth_format,
+            } => {
+                *rendering = vk::PipelineRenderingCreateInfo::default()
+                    .color_attachment_formats(color_formats)
+                    .depth_attachment_format(depth_format);
+                info.push_next(rendering)
+            }
+        }
+    }
+}
+
+/// Every `PipelineKey` variant of one shader pair, sharing one layout.
+pub struct PipelineSet {
     pub layout: vk::PipelineLayout,
-    pub pipeline: vk::Pipeline,
+    pub pipelines: HashMap<PipelineKey, vk::Pipeline>,
+}
+
+impl PipelineSet {
+    pub fn get(&self, key: PipelineKey) -> vk::Pipeline {
+        self.pipelines[&key]
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device) {
+        unsafe {
+            for (_, pipeline) in self.pipelines.drain() {
+                device.destroy_pipeline(pipeline, None);
+            }
+            device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
 }
 
 fn create_shader_module(device: &ash::Device, bytes: &[u8]) -> Result<vk::ShaderModule> {
@@ -15,39 +66,178 @@ fn create_shader_module(device: &ash::Device, bytes: &[u8]) -> Result<vk::Shader
     Ok(unsafe { device.create_shader_module(&info, None)? })
 }
 
-pub fn create_pipeline(
+/// Builds all `PipelineKey::ALL` variants. `set_layouts` are bound in order
+/// (set 0 = frame globals, set 1 = material).
+pub fn create_pipeline_set(
     device: &ash::Device,
-    render_pass: vk::RenderPass,
-    extent: vk::Extent2D,
-    descriptor_set_layout: vk::DescriptorSetLayout,
+    cache: vk::PipelineCache,
+    target: PipelineTarget,
+    samples: vk::SampleCountFlags,
+    set_layouts: &[vk::DescriptorSetLayout],
     vert_spv: &[u8],
     frag_spv: &[u8],
-) -> Result<Pipeline> {
-    let vert_mod = create_shader_module(device, vert_spv).context("vert shader module")?;
-    let frag_mod = create_shader_module(device, frag_spv).context("frag shader module")?;
+) -> Result<PipelineSet> {
+    let layout = create_pipeline_layout(device, set_layouts)?;
+    let mut set = PipelineSet {
+        layout,
+        pipelines: HashMap::new(),
+    };
+
+    let (bindings, attributes) = mesh_vertex_input();
+    let base = PipelineDesc {
+        bindings: &bindings,
+        attributes: &attributes,
+        samples,
+        ..PipelineDesc::new(target, layout, vert_spv, frag_spv)
+    };
+
+    for key in PipelineKey::ALL {
+        let desc = PipelineDesc {
+            cull: key.cull.to_vk(),
+            depth: Some(DepthTest {
+                // blended surfaces must not hide what is drawn behind them
+                // later
+                write: key.blend == BlendMode::Opaque,
+                compare: vk::CompareOp::LESS,
+            }),
+            color: Some(key.blend),
+            ..base
+        };
+        match create_pipeline(device, cache, &desc) {
+            Ok(pipeline) => {
+                set.pipelines.insert(key, pipeline);
+            }
+            Err(e) => {
+                set.destroy(device);
+                return Err(e.context(format!("pipeline variant {:?}", key)));
+            }
+        }
+    }
+
+    Ok(set)
+}
+
+/// No push constants: model matrices come in as instance attributes.
+pub fn create_pipeline_layout(
+    device: &ash::Device,
+    set_layouts: &[vk::DescriptorSetLayout],
+) -> Result<vk::PipelineLayout> {
+    let layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(set_layouts);
+
+    Ok(unsafe { device.create_pipeline_layout(&layout_info, None)? })
+}
+
+/// Depth test of a `PipelineDesc`.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthTest {
+    pub write: bool,
+    pub compare: vk::CompareOp,
+}
 
-    let main = std::ffi::CString::new("main")?;
-    let stages = [
+/// Shaders and fixed-function state of one graphics pipeline, for
+/// `create_pipeline`. Viewport and scissor are always dynamic.
+#[derive(Clone, Copy)]
+pub struct PipelineDesc<'a> {
+    pub target: PipelineTarget<'a>,
+    pub layout: vk::PipelineLayout,
+    pub vert_spv: &'a [u8],
+    /// `None` for depth-only pipelines.
+    pub frag_spv: Option<&'a [u8]>,
+    pub bindings: &'a [vk::VertexInputBindingDescription],
+    pub attributes: &'a [vk::VertexInputAttributeDescription],
+    pub topology: vk::PrimitiveTopology,
+    pub cull: vk::CullModeFlags,
+    /// Constant and slope-scaled depth bias factors.
+    pub depth_bias: Option<(f32, f32)>,
+    pub samples: vk::SampleCountFlags,
+    /// `None`: no depth test or writes.
+    pub depth: Option<DepthTest>,
+    /// Blending into the one color attachment; `None` for no color
+    /// attachments.
+    pub color: Option<BlendMode>,
+}
+
+impl<'a> PipelineDesc<'a> {
+    /// Triangle list without vertex input (e.g. a fullscreen triangle from
+    /// `gl_VertexIndex`), no culling, single sampled, no depth and one
+    /// opaque color attachment.
+    pub fn new(
+        target: PipelineTarget<'a>,
+        layout: vk::PipelineLayout,
+        vert_spv: &'a [u8],
+        frag_spv: &'a [u8],
+    ) -> Self {
+        Self {
+            target,
+            layout,
+            vert_spv,
+            frag_spv: Some(frag_spv),
+            bindings: &[],
+            attributes: &[],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            cull: vk::CullModeFlags::NONE,
+            depth_bias: None,
+            samples: vk::SampleCountFlags::TYPE_1,
+            depth: None,
+            color: Some(BlendMode::Opaque),
+        }
+    }
+}
+
+/// Vertex and instance bindings of the scene and shadow pipelines.
+pub fn mesh_vertex_input() -> (
+    [vk::VertexInputBindingDescription; 2],
+    Vec<vk::VertexInputAttributeDescription>,
+) {
+    let bindings = [
+        Vertex::binding_description(),
+        InstanceData::binding_description(),
+    ];
+    let attributes = Vertex::attribute_descriptions()
+        .into_iter()
+        .chain(InstanceData::attribute_descriptions())
+        .collect();
+    (bindings, attributes)
+}
+
+pub fn create_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    desc: &PipelineDesc,
+) -> Result<vk::Pipeline> {
+    let vert_mod = create_shader_module(device, desc.vert_spv).context("vert shader module")?;
+    let frag_mod = match desc
+        .frag_spv
+        .map(|spv| create_shader_module(device, spv))
+        .transpose()
+    {
+        Ok(module) => module,
+        Err(e) => {
+            unsafe { device.destroy_shader_module(vert_mod, None) };
+            return Err(e.context("frag shader module"));
+        }
+    };
+
+    let main = c"main";
+    let mut stages = vec![
         vk::PipelineShaderStageCreateInfo::default()
             .stage(vk::ShaderStageFlags::VERTEX)
             .module(vert_mod)
-            .name(&main),
-        vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(frag_mod)
-            .name(&main),
+            .name(main),
     ];
-
-    // No vertex buffers (gl_VertexIndex)
-
-    let binding = Vertex::binding_description();
-    let attrs = Vertex::attribute_descriptions();
+    if let Some(frag_mod) = frag_mod {
+        stages.push(
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(frag_mod)
+                .name(main),
+        );
+    }
 
     let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
-        .vertex_binding_descriptions(std::slice::from_ref(&binding))
-        .vertex_attribute_descriptions(&attrs);
-    let input_asm = vk::PipelineInputAssemblyStateCreateInfo::default()
-        .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+        .vertex_binding_descriptions(desc.bindings)
+        .vertex_attribute_descriptions(desc.attributes);
+    let input_asm = vk::PipelineInputAssemblyStateCreateInfo::default().topology(desc.topology);
 
     // Dynamic viewport/scissor
     let viewport_state = vk::PipelineViewportStateCreateInfo::default()
@@ -57,42 +247,44 @@ pub fn create_pipeline(
     let dyn_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
     let dynamic_state = vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dyn_states);
 
-    let raster = vk::PipelineRasterizationStateCreateInfo::default()
+    let mut raster = vk::PipelineRasterizationStateCreateInfo::default()
         .polygon_mode(vk::PolygonMode::FILL)
-        .cull_mode(vk::CullModeFlags::NONE)
+        .cull_mode(desc.cull)
         .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
         .line_width(1.0);
+    if let Some((constant, slope)) = desc.depth_bias {
+        raster = raster
+            .depth_bias_enable(true)
+            .depth_bias_constant_factor(constant)
+            .depth_bias_slope_factor(slope);
+    }
 
-    let multisample = vk::PipelineMultisampleStateCreateInfo::default()
-        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-    let depth = vk::PipelineDepthStencilStateCreateInfo::default()
-        .depth_test_enable(true)
-        .depth_write_enable(true)
-        .depth_compare_op(vk::CompareOp::LESS)
-        .depth_bounds_test_enable(false)
-        .stencil_test_enable(false);
-
-    let color_blend_att = vk::PipelineColorBlendAttachmentState::default()
-        .color_write_mask(vk::ColorComponentFlags::RGBA);
-
-    let color_blend = vk::PipelineColorBlendStateCreateInfo::default()
-        .attachments(std::slice::from_ref(&color_blend_att));
+    let multisample =
+        vk::PipelineMultisampleStateCreateInfo::default().rasterization_samples(desc.samples);
 
-    let push_range = vk::PushConstantRange {
-        stage_flags: vk::ShaderStageFlags::VERTEX,
-        offset: 0,
-        size: std::mem::size_of::<[[f32; 4]; 4]>() as u32,
+    let depth = match desc.depth {
+        Some(test) => vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(true)
+            .depth_write_enable(test.write)
+            .depth_compare_op(test.compare),
+        None => vk::PipelineDepthStencilStateCreateInfo::default(),
     };
 
-    let set_layouts = [descriptor_set_layout];
-    let push_ranges = [push_range];
-
-    let layout_info = vk::PipelineLayoutCreateInfo::default()
-        .set_layouts(&set_layouts)
-        .push_constant_ranges(&push_ranges);
-
-    let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+    let color_blend_att = desc.color.map(|blend| match blend {
+        BlendMode::Opaque => vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA),
+        BlendMode::AlphaBlend => vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD),
+    });
+    let color_blend =
+        vk::PipelineColorBlendStateCreateInfo::default().attachments(color_blend_att.as_slice());
 
     let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
         .stages(&stages)
@@ -102,23 +294,20 @@ pub fn create_pipeline(
         .dynamic_state(&dynamic_state)
         .rasterization_state(&raster)
         .multisample_state(&multisample)
-        .depth_stencil_state(&depth) // 👈 THIS LINE
+        .depth_stencil_state(&depth)
         .color_blend_state(&color_blend)
-        .layout(layout)
-        .render_pass(render_pass)
-        .subpass(0);
+        .layout(desc.layout);
+    let mut rendering = vk::PipelineRenderingCreateInfo::default();
+    let pipeline_info = desc.target.apply(pipeline_info, &mut rendering);
 
-    let pipelines = unsafe {
-        device.create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
-    }
-    .map_err(|(_, e)| e)?;
-    let pipeline = pipelines[0];
+    let pipelines = unsafe { device.create_graphics_pipelines(cache, &[pipeline_info], None) };
 
     unsafe {
         device.destroy_shader_module(vert_mod, None);
-        device.destroy_shader_module(frag_mod, None);
+        if let Some(frag_mod) = frag_mod {
+            device.destroy_shader_module(frag_mod, None);
+        }
     }
 
-    let _ = extent; // kept for future (swapchain recreate)
-    Ok(Pipeline { layout, pipeline })
+    Ok(pipelines.map_err(|(_, e)| e)?[0])
 }
@@ -0,0 +1,325 @@
+use crate::assets::shader_reload::compile_shader_file;
+use crate::assets::shaders::ShaderLibrary;
+use crate::renderer::graph::{
+    FrameContext, GraphPass, ImageDesc, ImageHandle, ImageSize, PassContext, PipelineContext,
+    RenderGraph,
+};
+use crate::renderer::pipeline::{PipelineDesc, create_pipeline};
+use crate::resources::descriptor::{
+    create_post_descriptor_pool, create_post_set_layout, write_post_set,
+};
+use crate::utils::config::{PostConfig, Tonemap};
+use anyhow::{Context, Result};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use std::path::Path;
+
+/// Format the scene pass renders into before tonemapping.
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+pub const FULLSCREEN_VERT: &str = "fullscreen.vert";
+const TONEMAP_FRAG: &str = "tonemap.frag";
+const FXAA_FRAG: &str = "fxaa.frag";
+const VIGNETTE_FRAG: &str = "vignette.frag";
+
+/// Push constants of every post shader, built-in or custom (see
+/// `shaders/tonemap.frag` for the GLSL side).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct PostParams {
+    /// 1 / target size in pixels.
+    pub texel: [f32; 2],
+    /// Seconds since the renderer was created.
+    pub time: f32,
+    pub exposure: f32,
+    /// 0 = no gamma encoding.
+    pub gamma: f32,
+    /// 0 none, 1 Reinhard, 2 ACES.
+    pub tonemap: u32,
+    pub vignette: f32,
+    pub _pad: f32,
+}
+
+impl PostParams {
+    /// `output_format` is the format of the LDR images in the chain;
+    /// sRGB formats are encoded by the hardware, so gamma is left off.
+    pub fn new(cfg: &PostConfig, output_format: vk::Format) -> Self {
+        let gamma = if cfg.gamma && !is_srgb(output_format) {
+            2.2
+        } else {
+            0.0
+        };
+
+        Self {
+            texel: [0.0; 2],
+            time: 0.0,
+            exposure: cfg.exposure,
+            gamma,
+            tonemap: match cfg.tonemap {
+                Tonemap::None => 0,
+                Tonemap::Reinhard => 1,
+                Tonemap::Aces => 2,
+            },
+            vignette: cfg.vignette_strength.clamp(0.0, 1.0),
+            _pad: 0.0,
+        }
+    }
+}
+
+fn is_srgb(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
+
+/// One step of the post chain. Each is a fullscreen pass that samples the
+/// previous step's output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostEffect {
+    /// Exposure, tonemapping and gamma; always first (HDR -> LDR).
+    Tonemap,
+    Fxaa,
+    Vignette,
+    /// A fragment shader from `ShaderLibrary`, by name.
+    Custom(String),
+}
+
+impl PostEffect {
+    pub fn shader(&self) -> &str {
+        match self {
+            PostEffect::Tonemap => TONEMAP_FRAG,
+            PostEffect::Fxaa => FXAA_FRAG,
+            PostEffect::Vignette => VIGNETTE_FRAG,
+            PostEffect::Custom(name) => name,
+        }
+    }
+}
+
+/// The enabled effects, in the order they run. Custom shaders missing from
+/// `shaders` (failed to load) are skipped.
+pub fn post_chain(cfg: &PostConfig, shaders: &ShaderLibrary) -> Vec<PostEffect> {
+    let mut chain = vec![PostEffect::Tonemap];
+    if cfg.fxaa {
+        chain.push(PostEffect::Fxaa);
+    }
+    if cfg.vignette {
+        chain.push(PostEffect::Vignette);
+    }
+    for path in &cfg.custom {
+        let name = shader_name(path);
+        if shaders.contains(&name) {
+            chain.push(PostEffect::Custom(name));
+        }
+    }
+    chain
+}
+
+/// `ShaderLibrary` key of a custom shader: its file name, so hot reload of
+/// the `shaders` directory picks it up.
+fn shader_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// Compiles the custom post shaders named in the config into `shaders`.
+/// Ones that fail are logged and left out of the chain.
+pub fn load_custom_shaders(cfg: &PostConfig, shaders: &mut ShaderLibrary) {
+    for path in &cfg.custom {
+        match compile_shader_file(Path::new(path)) {
+            Ok(spv) => {
+                log::info!("Loaded post shader {}", path);
+                shaders.insert(shader_name(path), spv);
+            }
+            Err(e) => log::error!("Skipping post shader {}: {:#}", path, e),
+        }
+    }
+}
+
+/// Adds one pass per enabled effect, reading `hdr` and ending in `output`.
+/// Intermediate images use `output_format`.
+pub fn add_post_chain(
+    graph: &mut RenderGraph,
+    hdr: ImageHandle,
+    output: ImageHandle,
+    output_format: vk::Format,
+    cfg: &PostConfig,
+    shaders: &ShaderLibrary,
+) {
+    let params = PostParams::new(cfg, output_format);
+    let chain = post_chain(cfg, shaders);
+
+    let mut input = hdr;
+    for (i, effect) in chain.iter().enumerate() {
+        let target = if i + 1 == chain.len() {
+            output
+        } else {
+            graph.create_image(
+                &format!("post_{}", i),
+                ImageDesc {
+                    format: output_format,
+                    size: ImageSize::Swapchain,
+                    samples: vk::SampleCountFlags::TYPE_1,
+                },
+            )
+        };
+
+        // every pixel is overwritten, so no clear
+        graph
+            .add_pass(effect.shader(), PostPass::new(effect.shader(), params))
+            .sample(input)
+            .color(target, None);
+        input = target;
+    }
+}
+
+/// Fullscreen pass running one post fragment shader over its single
+/// sampled input.
+pub struct PostPass {
+    frag: String,
+    params: PostParams,
+    set_layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    set: vk::DescriptorSet,
+    sampler: vk::Sampler,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl PostPass {
+    pub fn new(frag: &str, params: PostParams) -> Self {
+        Self {
+            frag: frag.to_string(),
+            params,
+            set_layout: vk::DescriptorSetLayout::null(),
+            pool: vk::DescriptorPool::null(),
+            set: vk::DescriptorSet::null(),
+            sampler: vk::Sampler::null(),
+            layout: vk::PipelineLayout::null(),
+            pipeline: vk::Pipeline::null(),
+        }
+    }
+
+    /// Descriptor set, sampler and layout; built once, kept across
+    /// pipeline rebuilds.
+    fn create_resources(&mut self, ctx: &PipelineContext) -> Result<()> {
+        let device = ctx.device;
+        let &(view, layout) = ctx
+            .sampled
+            .first()
+            .context("post pass has no input image")?;
+
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0.0);
+        self.sampler = unsafe { device.create_sampler(&sampler_info, None)? };
+
+        self.set_layout = create_post_set_layout(device)?;
+        self.pool = create_post_descriptor_pool(device, 1)?;
+
+        let alloc = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.pool)
+            .set_layouts(std::slice::from_ref(&self.set_layout));
+        self.set = unsafe { device.allocate_descriptor_sets(&alloc)? }[0];
+
+        write_post_set(
+            device,
+            self.set,
+            vk::DescriptorImageInfo::default()
+                .sampler(self.sampler)
+                .image_view(view)
+                .image_layout(layout),
+        );
+
+        let push_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .offset(0)
+            .size(std::mem::size_of::<PostParams>() as u32);
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(std::slice::from_ref(&self.set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_range));
+        self.layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+
+        Ok(())
+    }
+}
+
+impl GraphPass for PostPass {
+    fn shaders(&self) -> Vec<&str> {
+        vec![FULLSCREEN_VERT, &self.frag]
+    }
+
+    fn build_pipelines(&mut self, ctx: &PipelineContext) -> Result<()> {
+        if self.layout == vk::PipelineLayout::null() {
+            self.create_resources(ctx)?;
+        }
+
+        // fullscreen triangle: no vertex input, depth or blending
+        let desc = PipelineDesc::new(
+            ctx.target,
+            self.layout,
+            ctx.shaders.get(FULLSCREEN_VERT),
+            ctx.shaders.get(&self.frag),
+        );
+        let pipeline = create_pipeline(ctx.device, ctx.cache, &desc)?;
+
+        if self.pipeline != vk::Pipeline::null() {
+            unsafe {
+                ctx.device.destroy_pipeline(self.pipeline, None);
+            }
+        }
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    fn record(&self, ctx: &PassContext, frame: &FrameContext) {
+        let params = PostParams {
+            texel: [
+                1.0 / ctx.extent.width.max(1) as f32,
+                1.0 / ctx.extent.height.max(1) as f32,
+            ],
+            time: frame.time,
+            ..self.params
+        };
+
+        unsafe {
+            ctx.device
+                .cmd_bind_pipeline(ctx.cmd, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+            ctx.device.cmd_bind_descriptor_sets(
+                ctx.cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                0,
+                &[self.set],
+                &[],
+            );
+            ctx.device.cmd_push_constants(
+                ctx.cmd,
+                self.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&params),
+            );
+            ctx.device.cmd_draw(ctx.cmd, 3, 1, 0, 0);
+        }
+    }
+
+    fn destroy(&mut self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_pool(self.pool, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+        }
+        *self = Self::new(&self.frag, self.params);
+    }
+}
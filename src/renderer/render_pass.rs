@@ -1,71 +1,75 @@
 use anyhow::Result;
 use ash::vk;
 
-pub fn create_render_pass(
-    device: &ash::Device,
-    swapchain_format: vk::Format,
-    depth_format: vk::Format,
-) -> Result<vk::RenderPass> {
-    // 1️⃣ Color attachment (swapchain image)
-    let color_attachment = vk::AttachmentDescription::default()
-        .format(swapchain_format)
-        .samples(vk::SampleCountFlags::TYPE_1)
-        .load_op(vk::AttachmentLoadOp::CLEAR)
-        .store_op(vk::AttachmentStoreOp::STORE)
-        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-        .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+/// A single-subpass render pass. Attachments are numbered `colors`, then
+/// `depth`, then `resolves` (one per color, or none).
+pub struct RenderPassDesc<'a> {
+    pub colors: &'a [vk::AttachmentDescription],
+    pub depth: Option<vk::AttachmentDescription>,
+    /// Depth is only tested, not written (`DEPTH_STENCIL_READ_ONLY_OPTIMAL`).
+    pub depth_read_only: bool,
+    pub resolves: &'a [vk::AttachmentDescription],
+    pub dependencies: &'a [vk::SubpassDependency],
+}
+
+pub fn create_render_pass(device: &ash::Device, desc: &RenderPassDesc) -> Result<vk::RenderPass> {
+    if !desc.resolves.is_empty() && desc.resolves.len() != desc.colors.len() {
+        anyhow::bail!(
+            "{} resolve attachments for {} color attachments",
+            desc.resolves.len(),
+            desc.colors.len()
+        );
+    }
 
-    let color_ref = vk::AttachmentReference::default()
-        .attachment(0) // index in attachments array
-        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+    let color_count = desc.colors.len() as u32;
+    let depth_count = desc.depth.is_some() as u32;
 
-    // 2️⃣ Depth attachment
-    let depth_attachment = vk::AttachmentDescription::default()
-        .format(depth_format)
-        .samples(vk::SampleCountFlags::TYPE_1)
-        .load_op(vk::AttachmentLoadOp::CLEAR)
-        .store_op(vk::AttachmentStoreOp::DONT_CARE)
-        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-        .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+    let color_refs: Vec<vk::AttachmentReference> = (0..color_count)
+        .map(|i| {
+            vk::AttachmentReference::default()
+                .attachment(i)
+                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        })
+        .collect();
 
     let depth_ref = vk::AttachmentReference::default()
-        .attachment(1) // second attachment
-        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        .attachment(color_count)
+        .layout(if desc.depth_read_only {
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        } else {
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        });
 
-    // 3️⃣ Subpass uses BOTH color + depth
-    let subpass = vk::SubpassDescription::default()
-        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-        .color_attachments(std::slice::from_ref(&color_ref))
-        .depth_stencil_attachment(&depth_ref);
+    let resolve_refs: Vec<vk::AttachmentReference> = (0..desc.resolves.len() as u32)
+        .map(|i| {
+            vk::AttachmentReference::default()
+                .attachment(color_count + depth_count + i)
+                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        })
+        .collect();
 
-    // 4️⃣ Dependency (same as before, but correct)
-    let dep = vk::SubpassDependency::default()
-        .src_subpass(vk::SUBPASS_EXTERNAL)
-        .dst_subpass(0)
-        .src_stage_mask(
-            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-        )
-        .dst_stage_mask(
-            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-        )
-        .dst_access_mask(
-            vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-        );
+    let mut subpass = vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color_refs);
+    if desc.depth.is_some() {
+        subpass = subpass.depth_stencil_attachment(&depth_ref);
+    }
+    if !resolve_refs.is_empty() {
+        subpass = subpass.resolve_attachments(&resolve_refs);
+    }
 
-    // 5️⃣ Render pass = color + depth
-    let attachments = [color_attachment, depth_attachment];
+    let attachments: Vec<vk::AttachmentDescription> = desc
+        .colors
+        .iter()
+        .copied()
+        .chain(desc.depth)
+        .chain(desc.resolves.iter().copied())
+        .collect();
 
     let rp_info = vk::RenderPassCreateInfo::default()
         .attachments(&attachments)
         .subpasses(std::slice::from_ref(&subpass))
-        .dependencies(std::slice::from_ref(&dep));
+        .dependencies(desc.dependencies);
 
     let rp = unsafe { device.create_render_pass(&rp_info, None)? };
     Ok(rp)
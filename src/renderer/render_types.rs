@@ -1,12 +1,44 @@
-use glam::Mat4;
+use glam::{Mat4, Vec3};
 
 use super::mesh::Mesh;
+use crate::scene::material_store::MaterialId;
 
 pub struct FrameGlobals {
     pub view_proj: Mat4,
+    /// World -> shadow map clip space, see `shadow::fit_light_view_proj`.
+    pub light_view_proj: Mat4,
+    pub camera_pos: Vec3,
+    pub light: DirectionalLight,
+}
+
+/// Single directional light plus ambient term (Blinn-Phong).
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionalLight {
+    /// Direction the light travels, world space (world up is -Y).
+    pub direction: Vec3,
+    /// Linear color, already multiplied by intensity.
+    pub color: Vec3,
+    pub ambient: Vec3,
+    pub specular: f32,
+    pub shininess: f32,
+    /// Depth bias for the shadow map comparison.
+    pub shadow_bias: f32,
 }
 
 pub struct RenderItem<'a> {
     pub mesh: &'a Mesh,
+    pub material: MaterialId,
     pub model: Mat4,
+    /// 1.0 is fully opaque. Anything lower is drawn in the transparent
+    /// pass, whatever the material's blend mode.
+    pub opacity: f32,
+}
+
+/// What a frame draws. Shadow casters are culled against the light's
+/// frustum rather than the camera's, so objects just out of view still cast
+/// shadows into it.
+#[derive(Clone, Copy)]
+pub struct FrameItems<'a> {
+    pub items: &'a [RenderItem<'a>],
+    pub shadow_casters: &'a [RenderItem<'a>],
 }
@@ -1,51 +1,79 @@
 // ===================== src/renderer/renderer.rs =====================
 use super::{
-    command_buffers::*,
-    framebuffers::create_framebuffers,
-    pipeline::{Pipeline, create_pipeline},
-    render_pass::create_render_pass,
+    capture::{CapturedFrame, capture_image},
+    command_buffers::record_frame,
+    debug_draw::DebugPass,
+    frame_data::{FrameData, check_frames_in_flight, create_frames, write_frame_descriptors},
+    graph::{FrameContext, ImageDesc, ImageHandle, ImageSize, PassInputs, RenderGraph},
+    instancing::{DrawBatch, DrawList, build_batches},
+    offscreen::{OFFSCREEN_COLOR_FORMAT, OffscreenTarget},
+    post::{HDR_FORMAT, add_post_chain, load_custom_shaders},
+    scene_pass::{SceneDraws, ScenePass},
+    shadow::{ShadowDraws, ShadowPass, create_shadow_sampler},
+    text::TextPass,
 };
 use crate::assets::mesh::MeshData;
-use crate::core::{device::Device, surface::Surface, swapchain::Swapchain, sync::SyncObjects};
+use crate::core::{device::Device, swapchain::Swapchain, sync::SyncObjects};
 use crate::gfx::context::VkContext;
 use crate::renderer::error::RenderError;
+use crate::renderer::material::BlendMode;
 use crate::renderer::mesh::Mesh;
-use crate::renderer::render_types::{FrameGlobals, RenderItem};
-use crate::resources::buffer::{
-    GpuBuffer, UniformBufferObject, create_index_buffer_u32, create_uniform_buffer,
-    create_vertex_buffer,
-};
+use crate::renderer::render_types::{FrameGlobals, FrameItems};
+use crate::resources::upload::{UploadContext, UploadWait};
+use crate::scene::material_store::MaterialStore;
+use crate::utils::config::{Config, PostConfig};
 
-use crate::assets::shaders;
-use crate::resources::descriptor::{
-    allocate_descriptor_sets, create_descriptor_pool, create_descriptor_set_layout,
-    update_descriptor_sets,
-};
+use crate::assets::shaders::ShaderLibrary;
+use crate::resources::descriptor::create_descriptor_set_layout;
 use anyhow::Result;
 use ash::vk;
-use glam::Mat4;
 use std::time::Instant;
 
+/// Color the scene pass clears to.
+const CLEAR_COLOR: [f32; 4] = [0.05, 0.05, 0.08, 1.0];
+
 pub struct Renderer {
-    pub render_pass: vk::RenderPass,
-    pub pipeline: Pipeline,
-    pub framebuffers: Vec<vk::Framebuffer>,
-    pub commands: Commands,
+    /// Passes of a frame and the images between them (shadow map, depth,
+    /// HDR color, post chain); `draw_frame` just executes it.
+    pub graph: RenderGraph,
+    /// Per swapchain image semaphores and fences.
     pub sync: SyncObjects,
+    /// Ring of per-frame resources, `frames_in_flight` long.
+    pub frames: Vec<FrameData>,
+    /// Index into `frames` of the next frame to record.
     pub current_frame: usize,
     pub frames_in_flight: usize,
+    pub max_frames_in_flight: usize,
+    /// Color image index written by the last submitted frame.
+    pub last_image: Option<usize>,
+    /// Number of frames submitted so far (monotonic, survives rebuilds).
+    pub frame_count: u64,
+    /// Mesh draws recorded for the last frame, shadow and scene pass.
+    pub draw_count: usize,
+    /// Mesh uploads; each frame waits on whatever was flushed before it.
+    pub uploads: UploadContext,
 
-    pub uniform_buffers: Vec<GpuBuffer>,
-    pub uniform_mapped: Vec<*mut u8>,
     pub descriptor_set_layout: vk::DescriptorSetLayout,
+    /// Set 0 of every frame in `frames`.
     pub descriptor_pool: vk::DescriptorPool,
-    pub descriptor_sets: Vec<vk::DescriptorSet>,
-
-    // depth
-    pub depth_format: vk::Format,
-    pub depth_image: vk::Image,
-    pub depth_memory: vk::DeviceMemory,
-    pub depth_view: vk::ImageView,
+    /// Set 1 layout, owned by the `MaterialStore`.
+    pub material_set_layout: vk::DescriptorSetLayout,
+    /// SPIR-V the pipelines are built from (kept across rebuilds so
+    /// hot-reloaded shaders survive a resize).
+    pub shaders: ShaderLibrary,
+    /// Directional light shadow map (graph image), rendered before the
+    /// scene pass.
+    pub shadow_map: ImageHandle,
+    pub shadow_sampler: vk::Sampler,
+    pub shadow_resolution: u32,
+    /// Effects run after the scene pass, kept for rebuilds.
+    pub post: PostConfig,
+
+    /// Sample count actually in use (after clamping to the device).
+    pub msaa_samples: vk::SampleCountFlags,
+
+    // headless: color target owned by the renderer (None = swapchain images)
+    pub offscreen: Option<OffscreenTarget>,
 
     // camera / control
     pub extent: vk::Extent2D,
@@ -54,140 +82,279 @@ pub struct Renderer {
     pub start_time: Instant,
 }
 
-impl Renderer {
-    pub fn new(dev: &Device, swap: &Swapchain, frames_in_flight: usize) -> Result<Self> {
-        // --- depth resources (used by render pass + framebuffers) ---
-        let (depth_format, depth_image, depth_memory, depth_view) =
-            create_depth_resources(dev, swap.extent)?;
-
-        log::info!("Using depth format: {:?}", depth_format);
+/// Color image a frame is recorded into.
+#[derive(Clone, Copy)]
+enum FrameImage {
+    /// Acquired swapchain image, presented after submit.
+    Swapchain(usize),
+    Offscreen,
+}
 
-        // NOTE: your render_pass.rs must be updated to accept depth_format
-        let render_pass = create_render_pass(&dev.device, swap.format, depth_format)?;
+/// The images the frame graph ends in: swapchain images, or the offscreen
+/// target.
+struct ColorTarget<'a> {
+    format: vk::Format,
+    extent: vk::Extent2D,
+    images: &'a [vk::Image],
+    views: &'a [vk::ImageView],
+    /// Left for present or readback.
+    final_layout: vk::ImageLayout,
+}
 
-        // descriptors
-        let descriptor_set_layout = create_descriptor_set_layout(&dev.device)?;
+impl<'a> ColorTarget<'a> {
+    fn swapchain(swap: &'a Swapchain) -> Self {
+        Self {
+            format: swap.format,
+            extent: swap.extent,
+            images: &swap.images,
+            views: &swap.image_views,
+            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        }
+    }
+}
 
-        // NOTE: your pipeline.rs must enable depth testing (depthStencil state)
-        let pipeline = create_pipeline(
-            &dev.device,
-            render_pass,
-            swap.extent,
-            descriptor_set_layout,
-            shaders::triangle_vert_spv(),
-            shaders::triangle_frag_spv(),
-        )?;
+impl Renderer {
+    /// Fails if `cfg.renderer.frames_in_flight` is outside
+    /// `1..=max_frames_in_flight`.
+    pub fn new(
+        dev: &Device,
+        swap: &Swapchain,
+        material_set_layout: vk::DescriptorSetLayout,
+        cfg: &Config,
+    ) -> Result<Self> {
+        Self::build(
+            dev,
+            &ColorTarget::swapchain(swap),
+            None,
+            material_set_layout,
+            cfg,
+        )
+    }
 
-        // NOTE: your framebuffers.rs must attach BOTH color and depth:
-        // attachments = [color_view, depth_view]
-        let framebuffers = create_framebuffers(
-            &dev.device,
-            render_pass,
-            swap.extent,
-            &swap.image_views,
-            depth_view,
+    /// Headless renderer: draws into its own color + depth images instead of
+    /// swapchain image views. Frames are submitted with `draw_frame_offscreen`.
+    pub fn new_offscreen(
+        dev: &Device,
+        extent: vk::Extent2D,
+        material_set_layout: vk::DescriptorSetLayout,
+        cfg: &Config,
+    ) -> Result<Self> {
+        // fail before allocating the target; `build` checks it again
+        check_frames_in_flight(
+            cfg.renderer.frames_in_flight,
+            cfg.renderer.max_frames_in_flight,
         )?;
 
-        let mut uniform_buffers = Vec::with_capacity(swap.image_views.len());
-        let mut uniform_mapped = Vec::with_capacity(swap.image_views.len());
+        let target = OffscreenTarget::new(dev, extent, OFFSCREEN_COLOR_FORMAT)?;
+        let images = [target.image];
+        let views = [target.view];
+        let color = ColorTarget {
+            format: target.format,
+            extent,
+            images: &images,
+            views: &views,
+            // left ready for readback
+            final_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        };
 
-        for _ in 0..swap.image_views.len() {
-            let buf = create_uniform_buffer(&dev.device, &dev.memory_properties)?;
+        Self::build(dev, &color, Some(target), material_set_layout, cfg)
+    }
 
-            let ptr = unsafe {
-                dev.device.map_memory(
-                    buf.memory,
-                    0,
-                    std::mem::size_of::<UniformBufferObject>() as u64,
-                    vk::MemoryMapFlags::empty(),
-                )?
-            } as *mut u8;
+    fn build(
+        dev: &Device,
+        color: &ColorTarget,
+        offscreen: Option<OffscreenTarget>,
+        material_set_layout: vk::DescriptorSetLayout,
+        cfg: &Config,
+    ) -> Result<Self> {
+        let frames_in_flight = check_frames_in_flight(
+            cfg.renderer.frames_in_flight,
+            cfg.renderer.max_frames_in_flight,
+        )?;
+        let post = cfg.post.clone();
+        let shaders = post_shaders(&post);
+        let msaa_samples = pick_msaa_samples(dev, cfg.graphics.msaa_samples);
+        let shadow_resolution = cfg.shadows.resolution;
 
-            uniform_buffers.push(buf);
-            uniform_mapped.push(ptr);
-        }
+        let descriptor_set_layout = create_descriptor_set_layout(&dev.device)?;
 
-        // descriptor pool + sets
-        let descriptor_pool = create_descriptor_pool(&dev.device, swap.image_views.len() as u32)?;
-        let descriptor_sets = allocate_descriptor_sets(
-            &dev.device,
-            descriptor_pool,
-            descriptor_set_layout,
-            swap.image_views.len(),
+        let (graph, shadow_map) = build_graph(
+            dev,
+            color,
+            &[descriptor_set_layout, material_set_layout],
+            &shaders,
+            msaa_samples,
+            shadow_resolution,
+            &post,
         )?;
 
-        let uniform_vk_buffers: Vec<vk::Buffer> =
-            uniform_buffers.iter().map(|b| b.buffer).collect();
-        update_descriptor_sets(
-            &dev.device,
-            &descriptor_sets,
-            &uniform_vk_buffers,
-            std::mem::size_of::<UniformBufferObject>() as u64,
-        );
-
-        // commands
-        let pool = create_command_pool(&dev.device, dev.queues.graphics_family)?;
-        let buffers = allocate_command_buffers(&dev.device, pool, framebuffers.len() as u32)?;
+        let shadow_sampler = create_shadow_sampler(&dev.device)?;
 
-        let sync = SyncObjects::new(&dev.device, swap.image_views.len(), frames_in_flight)?;
+        let (descriptor_pool, frames) =
+            create_frames(dev, frames_in_flight, descriptor_set_layout)?;
+        let sync = SyncObjects::new(&dev.device, color.views.len())?;
 
-        Ok(Self {
-            render_pass,
-            pipeline,
-            framebuffers,
-            commands: Commands { pool, buffers },
+        let renderer = Self {
+            graph,
             sync,
+            frames,
             current_frame: 0,
             frames_in_flight,
+            max_frames_in_flight: cfg.renderer.max_frames_in_flight,
+            last_image: None,
+            frame_count: 0,
+            draw_count: 0,
+            uploads: UploadContext::new(dev)?,
 
-            uniform_buffers,
-            uniform_mapped,
             descriptor_set_layout,
             descriptor_pool,
-            descriptor_sets,
+            material_set_layout,
+            shaders,
+            shadow_map,
+            shadow_sampler,
+            shadow_resolution,
+            post,
 
-            depth_format,
-            depth_image,
-            depth_memory,
-            depth_view,
+            msaa_samples,
 
-            extent: swap.extent,
+            offscreen,
+
+            extent: color.extent,
 
             start_time: Instant::now(),
-        })
+        };
+        renderer.write_frame_descriptors(&dev.device);
+
+        Ok(renderer)
+    }
+
+    /// Points set 0 of every frame at the current graph's shadow map.
+    fn write_frame_descriptors(&self, device: &ash::Device) {
+        write_frame_descriptors(
+            device,
+            &self.frames,
+            vk::DescriptorImageInfo::default()
+                .sampler(self.shadow_sampler)
+                .image_view(self.graph.image_view(self.shadow_map, 0))
+                .image_layout(self.graph.sampled_layout(self.shadow_map)),
+        );
     }
 
-    pub fn upload_mesh(&self, dev: &Device, mesh: &MeshData) -> anyhow::Result<Mesh> {
-        let vertex_buffer = create_vertex_buffer(dev, &mesh.vertices)?;
-        let index_buffer = create_index_buffer_u32(dev, &mesh.indices)?;
+    /// Resizes the frame ring. Waits for the device to go idle; the
+    /// swapchain and render graph are left alone.
+    pub fn set_frames_in_flight(&mut self, dev: &Device, frames_in_flight: usize) -> Result<()> {
+        let frames_in_flight = check_frames_in_flight(frames_in_flight, self.max_frames_in_flight)?;
+        if frames_in_flight == self.frames_in_flight {
+            return Ok(());
+        }
+
+        unsafe {
+            dev.device.device_wait_idle()?;
+        }
+
+        self.destroy_frames(&dev.device);
+        let (descriptor_pool, frames) =
+            create_frames(dev, frames_in_flight, self.descriptor_set_layout)?;
+        self.descriptor_pool = descriptor_pool;
+        self.frames = frames;
+        self.write_frame_descriptors(&dev.device);
+
+        // the fences these pointed at are gone, and idle anyway
+        self.sync.images_in_flight.fill(vk::Fence::null());
+        self.current_frame = 0;
+        self.frames_in_flight = frames_in_flight;
+
+        log::info!("Frames in flight: {}", frames_in_flight);
+        Ok(())
+    }
+
+    fn destroy_frames(&mut self, device: &ash::Device) {
+        for frame in &mut self.frames {
+            frame.destroy(device);
+        }
+        self.frames.clear();
+
+        unsafe {
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+        }
+        self.descriptor_pool = vk::DescriptorPool::null();
+    }
+
+    /// Queues the mesh on the upload context. It can be drawn right away:
+    /// the next frame waits for the upload.
+    pub fn upload_mesh(&mut self, dev: &Device, mesh: &MeshData) -> anyhow::Result<Mesh> {
+        if mesh.vertices.is_empty() || mesh.indices.is_empty() {
+            anyhow::bail!("upload_mesh called with an empty mesh");
+        }
+        let vertex_buffer = self.uploads.upload_buffer(
+            dev,
+            bytemuck::cast_slice(&mesh.vertices),
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+        )?;
+        let index_buffer = match self.uploads.upload_buffer(
+            dev,
+            bytemuck::cast_slice(&mesh.indices),
+            vk::BufferUsageFlags::INDEX_BUFFER,
+        ) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                // the vertex copy is already recorded
+                self.uploads.discard(&dev.device, vertex_buffer);
+                return Err(e);
+            }
+        };
+        let (aabb, sphere) = mesh.bounds();
 
         Ok(Mesh {
             vertex_buffer,
             index_buffer,
             index_count: mesh.indices.len() as u32,
+            aabb,
+            sphere,
         })
     }
 
+    /// Waits until the frame slot the next `draw_frame` records into is no
+    /// longer in use and returns its index into `frames`. Per-slot host
+    /// data (see `DynamicMesh`) may be rewritten after this.
+    pub fn wait_frame_slot(&self, dev: &Device) -> Result<usize> {
+        let fence = self.frames[self.current_frame].fence;
+        unsafe {
+            dev.device.wait_for_fences(&[fence], true, u64::MAX)?;
+        }
+        Ok(self.current_frame)
+    }
+
+    /// Recycles upload batches of retired frames and takes the ones the
+    /// frame being recorded has to wait for. Call after waiting on the
+    /// frame's fence.
+    fn take_uploads(&mut self, dev: &Device) -> Result<UploadWait> {
+        // this slot's previous frame is done, and with it every older one
+        let completed = (self.frame_count + 1).saturating_sub(self.frames.len() as u64);
+        self.uploads.collect(&dev.device, completed);
+        self.uploads.take_wait(dev, self.frame_count)
+    }
+
     pub fn draw_frame(
         &mut self,
         dev: &Device,
-        _surface: &Surface,
         swap: &Swapchain,
+        materials: &MaterialStore,
         globals: FrameGlobals,
-        items: &[RenderItem],
+        items: FrameItems,
+        inputs: PassInputs,
     ) -> Result<(), RenderError> {
-        let frame = self.current_frame;
+        let frame = &self.frames[self.current_frame];
 
         unsafe {
-            dev.device
-                .wait_for_fences(&[self.sync.in_flight[frame]], true, u64::MAX)?;
+            dev.device.wait_for_fences(&[frame.fence], true, u64::MAX)?;
         }
 
         let (image_index, _) = match unsafe {
             swap.loader.acquire_next_image(
                 swap.swapchain,
                 u64::MAX,
-                self.sync.image_available[frame],
+                frame.image_available,
                 vk::Fence::null(),
             )
         } {
@@ -199,8 +366,89 @@ impl Renderer {
         };
 
         let idx = image_index as usize;
+        self.record_and_submit(
+            dev,
+            materials,
+            globals,
+            items,
+            inputs,
+            FrameImage::Swapchain(idx),
+        )?;
+
+        let signal_sems = [self.sync.render_finished[idx]];
+        let swapchains = [swap.swapchain];
+        let image_indices = [image_index];
+
+        let present = vk::PresentInfoKHR::default()
+            .wait_semaphores(&signal_sems)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        let present_queue = dev
+            .present_queue
+            .ok_or_else(|| RenderError::Other(anyhow::anyhow!("device has no present queue")))?;
+
+        match unsafe { swap.loader.queue_present(present_queue, &present) } {
+            Ok(_) => Ok(()),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR) => {
+                Err(RenderError::SwapchainOutOfDate)
+            }
+            Err(e) => Err(RenderError::Vulkan(e)),
+        }
+    }
+
+    /// Headless counterpart of `draw_frame`: no acquire, no present, just
+    /// render into the offscreen target and signal the frame fence.
+    pub fn draw_frame_offscreen(
+        &mut self,
+        dev: &Device,
+        materials: &MaterialStore,
+        globals: FrameGlobals,
+        items: FrameItems,
+        inputs: PassInputs,
+    ) -> Result<(), RenderError> {
+        if self.offscreen.is_none() {
+            return Err(RenderError::Other(anyhow::anyhow!(
+                "draw_frame_offscreen called on a swapchain renderer"
+            )));
+        }
+
+        unsafe {
+            let fence = self.frames[self.current_frame].fence;
+            dev.device.wait_for_fences(&[fence], true, u64::MAX)?;
+        }
+
+        self.record_and_submit(
+            dev,
+            materials,
+            globals,
+            items,
+            inputs,
+            FrameImage::Offscreen,
+        )
+    }
+
+    /// Records the current frame slot into `image` and submits it. The
+    /// slot's fence must already be signaled; a swapchain image also waits
+    /// on the slot's acquire semaphore and signals its `render_finished`.
+    fn record_and_submit(
+        &mut self,
+        dev: &Device,
+        materials: &MaterialStore,
+        globals: FrameGlobals,
+        items: FrameItems,
+        mut inputs: PassInputs,
+        image: FrameImage,
+    ) -> Result<(), RenderError> {
+        let idx = match image {
+            FrameImage::Swapchain(idx) => idx,
+            FrameImage::Offscreen => 0, // single offscreen color image
+        };
+        let uploads = self.take_uploads(dev).map_err(RenderError::Other)?;
+        let frame = &mut self.frames[self.current_frame];
 
-        // if swapchain image already in flight, wait
+        // if the image is already in flight (the offscreen target is shared
+        // by all frames), wait
         if self.sync.images_in_flight[idx] != vk::Fence::null() {
             unsafe {
                 dev.device
@@ -208,43 +456,65 @@ impl Renderer {
             }
         }
 
-        // update UBO for THIS swapchain image
-        self.update_uniform(&dev.device, idx, globals.view_proj)
+        frame.write_uniforms(&globals);
+
+        let mut instances = Vec::with_capacity(items.items.len() + items.shadow_casters.len());
+        let batches = build_batches(items.items, materials, globals.view_proj, &mut instances);
+        let shadow_batches = build_batches(
+            items.shadow_casters,
+            materials,
+            globals.light_view_proj,
+            &mut instances,
+        );
+        frame
+            .instance_buffer
+            .write(dev, &instances)
             .map_err(RenderError::Other)?;
+        self.draw_count = draw_count(&batches, &shadow_batches);
+        let instance_buffer = frame.instance_buffer.buffer();
+        inputs.insert(SceneDraws(DrawList {
+            instance_buffer,
+            batches,
+        }));
+        inputs.insert(ShadowDraws(DrawList {
+            instance_buffer,
+            batches: shadow_batches,
+        }));
 
         // mark image as in flight
-        self.sync.images_in_flight[idx] = self.sync.in_flight[frame];
-
-        unsafe {
-            dev.device.reset_fences(&[self.sync.in_flight[frame]])?;
-        }
-
-        let cmd = self.commands.buffers[idx];
+        self.sync.images_in_flight[idx] = frame.fence;
 
         unsafe {
+            dev.device.reset_fences(&[frame.fence])?;
             dev.device
-                .reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())?;
+                .reset_command_pool(frame.pool, vk::CommandPoolResetFlags::empty())?;
         }
 
-        // --- RECORD COMMAND BUFFER (this is the missing piece) ---
-
-        record_scene_cmd(
+        let frame_ctx = FrameContext {
+            descriptor_set: frame.descriptor_set,
+            materials,
+            time: self.start_time.elapsed().as_secs_f32(),
+            inputs: &inputs,
+        };
+        record_frame(
             &dev.device,
-            cmd,
-            self.render_pass,
-            self.framebuffers[idx],
-            swap.extent,
-            self.pipeline.pipeline,
-            self.pipeline.layout,
-            self.descriptor_sets[idx], // ✅ still per swapchain image
-            items,
+            frame.cmd,
+            &self.graph,
+            idx,
+            &frame_ctx,
+            &uploads,
         )
         .map_err(RenderError::Other)?;
 
-        let wait_sems = [self.sync.image_available[frame]];
-        let signal_sems = [self.sync.render_finished[idx]];
-        let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let cmd_bufs = [cmd];
+        let mut wait_sems = uploads.semaphores.clone();
+        let mut wait_stages = uploads.stages.clone();
+        let mut signal_sems = Vec::new();
+        if let FrameImage::Swapchain(_) = image {
+            wait_sems.push(frame.image_available);
+            wait_stages.push(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT);
+            signal_sems.push(self.sync.render_finished[idx]);
+        }
+        let cmd_bufs = [frame.cmd];
 
         let submit = vk::SubmitInfo::default()
             .wait_semaphores(&wait_sems)
@@ -254,92 +524,223 @@ impl Renderer {
 
         unsafe {
             dev.device
-                .queue_submit(dev.graphics_queue, &[submit], self.sync.in_flight[frame])?;
+                .queue_submit(dev.graphics_queue, &[submit], frame.fence)?;
         }
 
-        let swapchains = [swap.swapchain];
-        let image_indices = [image_index];
+        self.last_image = Some(idx);
+        self.frame_count += 1;
+        self.current_frame = (self.current_frame + 1) % self.frames.len();
 
-        let present = vk::PresentInfoKHR::default()
-            .wait_semaphores(&signal_sems)
-            .swapchains(&swapchains)
-            .image_indices(&image_indices);
+        Ok(())
+    }
 
-        match unsafe { swap.loader.queue_present(dev.present_queue, &present) } {
-            Ok(_) => {}
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR) => {
-                return Err(RenderError::SwapchainOutOfDate);
-            }
-            Err(e) => return Err(RenderError::Vulkan(e)),
+    /// Reads the last drawn frame back as RGBA8.
+    ///
+    /// Waits for the device to go idle first. `swap` is required unless the
+    /// renderer is offscreen; swapchain images are read in `PRESENT_SRC_KHR`
+    /// right after presentation, so they need `TRANSFER_SRC` usage.
+    pub fn capture_frame(&self, dev: &Device, swap: Option<&Swapchain>) -> Result<CapturedFrame> {
+        let idx = self
+            .last_image
+            .ok_or_else(|| anyhow::anyhow!("capture_frame: no frame has been drawn yet"))?;
+
+        unsafe {
+            dev.device.device_wait_idle()?;
+        }
+
+        if let Some(target) = &self.offscreen {
+            return capture_image(
+                dev,
+                target.image,
+                target.format,
+                target.extent,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            );
         }
 
-        self.current_frame = (self.current_frame + 1) % self.sync.in_flight.len();
+        let swap = swap.ok_or_else(|| anyhow::anyhow!("capture_frame: swapchain required"))?;
+        if !swap.image_usage.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+            anyhow::bail!("capture_frame: swapchain images do not support TRANSFER_SRC");
+        }
 
-        Ok(())
+        capture_image(
+            dev,
+            swap.images[idx],
+            swap.format,
+            swap.extent,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        )
     }
 
     pub fn destroy(&mut self, dev: &ash::Device) {
+        self.destroy_frames(dev);
+        self.uploads.destroy(dev);
+
         unsafe {
-            for (i, b) in self.uniform_buffers.iter().enumerate() {
-                if !self.uniform_mapped.is_empty() {
-                    dev.unmap_memory(b.memory);
-                }
-            }
-            self.uniform_mapped.clear();
+            dev.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+
+            // passes, render passes, framebuffers, graph-owned images
+            self.graph.destroy(dev);
+            dev.destroy_sampler(self.shadow_sampler, None);
 
-            // uniform buffers
-            for b in &self.uniform_buffers {
-                b.destroy(dev);
+            // offscreen color target
+            if let Some(target) = self.offscreen.take() {
+                target.destroy(dev);
             }
-            self.uniform_buffers.clear();
+        }
 
-            // descriptors
-            dev.destroy_descriptor_pool(self.descriptor_pool, None);
-            dev.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        self.sync.destroy(dev);
+    }
+}
 
-            // framebuffers
-            for &fb in &self.framebuffers {
-                dev.destroy_framebuffer(fb, None);
-            }
-            self.framebuffers.clear();
+/// The frame graph: shadow pass, HDR scene pass, debug lines (with MSAA
+/// resolve), the post chain ending in `color` and text over it. Returns it
+/// compiled, with the shadow map.
+fn build_graph(
+    dev: &Device,
+    color: &ColorTarget,
+    set_layouts: &[vk::DescriptorSetLayout],
+    shaders: &ShaderLibrary,
+    msaa_samples: vk::SampleCountFlags,
+    shadow_resolution: u32,
+    post: &PostConfig,
+) -> Result<(RenderGraph, ImageHandle)> {
+    let depth_format = dev.pick_depth_format()?;
+    let shadow_format = dev.pick_shadow_depth_format()?;
+    log::info!("Using depth format: {:?}", depth_format);
+
+    let mut graph = RenderGraph::new();
+
+    let backbuffer = graph.import_image(
+        "backbuffer",
+        color.format,
+        color.extent,
+        color.images,
+        color.views,
+        color.final_layout,
+    );
+    let shadow_size = shadow_resolution.max(1);
+    let shadow_map = graph.create_image(
+        "shadow_map",
+        ImageDesc {
+            format: shadow_format,
+            size: ImageSize::Fixed(vk::Extent2D {
+                width: shadow_size,
+                height: shadow_size,
+            }),
+            samples: vk::SampleCountFlags::TYPE_1,
+        },
+    );
+    let depth = graph.create_image(
+        "depth",
+        ImageDesc {
+            format: depth_format,
+            size: ImageSize::Swapchain,
+            samples: msaa_samples,
+        },
+    );
+
+    // the scene renders in HDR; the post chain maps it to the backbuffer
+    let hdr = graph.create_image(
+        "hdr",
+        ImageDesc {
+            format: HDR_FORMAT,
+            size: ImageSize::Swapchain,
+            samples: vk::SampleCountFlags::TYPE_1,
+        },
+    );
+    // multisampled HDR color, resolved into `hdr`
+    let msaa_color = (msaa_samples != vk::SampleCountFlags::TYPE_1).then(|| {
+        graph.create_image(
+            "msaa_color",
+            ImageDesc {
+                format: HDR_FORMAT,
+                size: ImageSize::Swapchain,
+                samples: msaa_samples,
+            },
+        )
+    });
+
+    // vertex and index buffers, copied by the upload context's transfer
+    // submits before the frame
+    let meshes = graph.buffer(
+        "meshes",
+        Some((
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+        )),
+    );
+    let mesh_stage = vk::PipelineStageFlags::VERTEX_INPUT;
+    let mesh_access = vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::INDEX_READ;
+
+    graph
+        .add_pass("shadow", ShadowPass::default())
+        .depth(shadow_map, Some(1.0))
+        .buffer(meshes, mesh_stage, mesh_access);
+
+    let scene_color = msaa_color.unwrap_or(hdr);
+    graph
+        .add_pass("scene", ScenePass::default())
+        .color(scene_color, Some(CLEAR_COLOR))
+        .depth(depth, Some(1.0))
+        .sample(shadow_map)
+        .buffer(meshes, mesh_stage, mesh_access);
+
+    // debug lines over the scene, tested against its depth; ends the
+    // multisampled part of the frame
+    let debug = graph
+        .add_pass("debug", DebugPass::default())
+        .color(scene_color, None)
+        .depth_read(depth);
+    if msaa_color.is_some() {
+        debug.resolve(hdr);
+    }
 
-            // depth
-            dev.destroy_image_view(self.depth_view, None);
-            dev.destroy_image(self.depth_image, None);
-            dev.free_memory(self.depth_memory, None);
+    add_post_chain(&mut graph, hdr, backbuffer, color.format, post, shaders);
 
-            // pipeline + renderpass
-            dev.destroy_pipeline(self.pipeline.pipeline, None);
-            dev.destroy_pipeline_layout(self.pipeline.layout, None);
-            dev.destroy_render_pass(self.render_pass, None);
+    // text over the finished image
+    graph
+        .add_pass("text", TextPass::default())
+        .color(backbuffer, None);
 
-            // command pool
-            dev.destroy_command_pool(self.commands.pool, None);
-        }
+    graph.set_output(backbuffer);
+    graph.compile(dev, color.extent, shaders, set_layouts)?;
 
-        self.sync.destroy(dev);
-    }
+    Ok((graph, shadow_map))
+}
 
-    fn update_uniform(&self, _device: &ash::Device, idx: usize, view_proj: Mat4) -> Result<()> {
-        let ubo = UniformBufferObject {
-            view_proj: view_proj.to_cols_array_2d(),
-        };
+/// Mesh draws recorded: every scene batch, and the opaque shadow batches.
+fn draw_count(batches: &[DrawBatch], shadow_batches: &[DrawBatch]) -> usize {
+    let casters = shadow_batches
+        .iter()
+        .filter(|b| b.pipeline.blend == BlendMode::Opaque)
+        .count();
+    batches.len() + casters
+}
 
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                bytemuck::bytes_of(&ubo).as_ptr(),
-                self.uniform_mapped[idx],
-                std::mem::size_of::<UniformBufferObject>(),
-            );
-        }
+/// Embedded shaders plus the custom post shaders named in `post`.
+fn post_shaders(post: &PostConfig) -> ShaderLibrary {
+    let mut shaders = ShaderLibrary::embedded();
+    load_custom_shaders(post, &mut shaders);
+    shaders
+}
 
-        Ok(())
+/// Clamps the configured sample count to the device, warning if it had to.
+fn pick_msaa_samples(dev: &Device, requested: u32) -> vk::SampleCountFlags {
+    let samples = dev.clamp_sample_count(requested);
+    if samples.as_raw() != requested.max(1) {
+        log::warn!(
+            "msaa_samples = {} not supported, using {}",
+            requested,
+            samples.as_raw()
+        );
     }
+    samples
 }
 
 // ----------------------- depth helpers -----------------------
 
-fn find_memory_type_fallback(
+pub fn find_memory_type_fallback(
     mem_props: &vk::PhysicalDeviceMemoryProperties,
     type_bits: u32,
     candidates: &[vk::MemoryPropertyFlags],
@@ -355,90 +756,71 @@ fn find_memory_type_fallback(
             }
         }
     }
-    anyhow::bail!("No suitable memory type found for depth buffer");
-}
-
-fn create_depth_resources(
-    dev: &Device,
-    extent: vk::Extent2D,
-) -> Result<(vk::Format, vk::Image, vk::DeviceMemory, vk::ImageView)> {
-    let format = dev.pick_depth_format()?; // ✅ FIXED
-
-    let image_info = vk::ImageCreateInfo::default()
-        .image_type(vk::ImageType::TYPE_2D)
-        .format(format)
-        .extent(vk::Extent3D {
-            width: extent.width,
-            height: extent.height,
-            depth: 1,
-        })
-        .mip_levels(1)
-        .array_layers(1)
-        .samples(vk::SampleCountFlags::TYPE_1)
-        .tiling(vk::ImageTiling::OPTIMAL)
-        .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
-        .sharing_mode(vk::SharingMode::EXCLUSIVE)
-        .initial_layout(vk::ImageLayout::UNDEFINED);
-
-    let image = unsafe { dev.device.create_image(&image_info, None)? };
-    let reqs = unsafe { dev.device.get_image_memory_requirements(image) };
-
-    #[cfg(target_os = "macos")]
-    let candidates = &[vk::MemoryPropertyFlags::DEVICE_LOCAL][..];
-
-    #[cfg(not(target_os = "macos"))]
-    let candidates = &[
-        vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-    ][..];
-
-    let mem_index =
-        find_memory_type_fallback(&dev.memory_properties, reqs.memory_type_bits, candidates)?;
-
-    let alloc = vk::MemoryAllocateInfo::default()
-        .allocation_size(reqs.size)
-        .memory_type_index(mem_index);
-
-    let memory = unsafe { dev.device.allocate_memory(&alloc, None)? };
-    unsafe { dev.device.bind_image_memory(image, memory, 0)? };
-
-    let aspect = match format {
-        vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => {
-            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
-        }
-        _ => vk::ImageAspectFlags::DEPTH,
-    };
-
-    let view_info = vk::ImageViewCreateInfo::default()
-        .image(image)
-        .view_type(vk::ImageViewType::TYPE_2D)
-        .format(format)
-        .subresource_range(
-            vk::ImageSubresourceRange::default()
-                .aspect_mask(aspect)
-                .level_count(1)
-                .layer_count(1),
-        );
-
-    let view = unsafe { dev.device.create_image_view(&view_info, None)? };
-
-    Ok((format, image, memory, view))
+    anyhow::bail!("No suitable memory type found for image");
 }
 
 impl Renderer {
+    /// Rebuilds the render graph and per-image sync for a new swapchain.
+    /// The frame ring (command buffers, UBOs, descriptor sets) is kept.
     pub fn rebuild_for_swapchain(
         &mut self,
         context: &VkContext,
         swapchain: &Swapchain,
     ) -> anyhow::Result<()> {
+        let dev = &context.device;
         unsafe {
-            context.device.device.device_wait_idle()?;
+            dev.device.device_wait_idle()?;
         }
 
-        let frames_in_flight = self.frames_in_flight;
+        self.graph.destroy(&dev.device);
+        self.sync.destroy(&dev.device);
+
+        let (graph, shadow_map) = build_graph(
+            dev,
+            &ColorTarget::swapchain(swapchain),
+            &[self.descriptor_set_layout, self.material_set_layout],
+            &self.shaders,
+            self.msaa_samples,
+            self.shadow_resolution,
+            &self.post,
+        )?;
+        self.graph = graph;
+        self.shadow_map = shadow_map;
+        self.sync = SyncObjects::new(&dev.device, swapchain.image_views.len())?;
+        self.write_frame_descriptors(&dev.device);
+
+        self.extent = swapchain.extent;
+        self.last_image = None;
 
-        self.destroy(&context.device.device);
-        *self = Renderer::new(&context.device, swapchain, frames_in_flight)?;
+        Ok(())
+    }
+
+    /// Swaps in recompiled SPIR-V (`name` is the source file name, e.g.
+    /// `"lit.frag"`) and rebuilds the passes whose pipelines use it. A pass
+    /// whose pipelines fail to build keeps its old pipelines and SPIR-V.
+    pub fn reload_shaders(&mut self, dev: &Device, updates: Vec<(String, Vec<u8>)>) -> Result<()> {
+        let names: Vec<String> = updates.iter().map(|(name, _)| name.clone()).collect();
+
+        let mut shaders = self.shaders.clone();
+        for (name, spv) in updates {
+            shaders.insert(name, spv);
+        }
+
+        if !self.graph.uses_any_shader(&names) {
+            self.shaders = shaders;
+            return Ok(());
+        }
+
+        // old pipelines may still be referenced by in-flight command buffers
+        unsafe {
+            dev.device.device_wait_idle()?;
+        }
+
+        for name in self.graph.rebuild_pipelines(&dev.device, &shaders, &names) {
+            let spv = self.shaders.get(&name).to_vec();
+            shaders.insert(name, spv);
+        }
+        self.shaders = shaders;
 
         Ok(())
     }
@@ -0,0 +1,118 @@
+use crate::renderer::graph::{FrameContext, GraphPass, PassContext, PipelineContext};
+use crate::renderer::instancing::DrawList;
+use crate::renderer::pipeline::{PipelineSet, create_pipeline_set};
+use anyhow::Result;
+use ash::vk;
+
+/// Shader sources the scene pipelines are built from.
+pub const LIT_VERT: &str = "lit.vert";
+pub const LIT_FRAG: &str = "lit.frag";
+
+/// Batches of `FrameItems::items`, the scene pass's input.
+pub struct SceneDraws(pub DrawList);
+
+/// Lit forward pass over `SceneDraws`: opaque batches first, then
+/// transparent ones back to front (`build_batches`).
+#[derive(Default)]
+pub struct ScenePass {
+    pipelines: Option<PipelineSet>,
+}
+
+impl GraphPass for ScenePass {
+    fn shaders(&self) -> Vec<&str> {
+        vec![LIT_VERT, LIT_FRAG]
+    }
+
+    fn build_pipelines(&mut self, ctx: &PipelineContext) -> Result<()> {
+        let pipelines = create_pipeline_set(
+            ctx.device,
+            ctx.cache,
+            ctx.target,
+            ctx.samples,
+            ctx.set_layouts,
+            ctx.shaders.get(LIT_VERT),
+            ctx.shaders.get(LIT_FRAG),
+        )?;
+
+        if let Some(mut old) = self.pipelines.replace(pipelines) {
+            old.destroy(ctx.device);
+        }
+        Ok(())
+    }
+
+    fn record(&self, ctx: &PassContext, frame: &FrameContext) {
+        let (Some(pipelines), Some(SceneDraws(draws))) =
+            (&self.pipelines, frame.input::<SceneDraws>())
+        else {
+            return;
+        };
+        let (device, cmd) = (ctx.device, ctx.cmd);
+        let pipeline_layout = pipelines.layout;
+
+        unsafe {
+            // Global descriptor set (camera + lighting) once per pass;
+            // all variants share the layout, so it survives pipeline switches
+            device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+
+            let mut bound_pipeline = None;
+            let mut bound_material = None;
+
+            for batch in &draws.batches {
+                if bound_pipeline != Some(batch.pipeline) {
+                    device.cmd_bind_pipeline(
+                        cmd,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipelines.get(batch.pipeline),
+                    );
+                    bound_pipeline = Some(batch.pipeline);
+                }
+
+                if bound_material != Some(batch.material) {
+                    device.cmd_bind_descriptor_sets(
+                        cmd,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline_layout,
+                        1,
+                        &[frame.materials.descriptor_set(batch.material)],
+                        &[],
+                    );
+                    bound_material = Some(batch.material);
+                }
+
+                device.cmd_bind_vertex_buffers(
+                    cmd,
+                    0,
+                    &[batch.mesh.vertex_buffer, draws.instance_buffer],
+                    &[0, 0],
+                );
+                device.cmd_bind_index_buffer(
+                    cmd,
+                    batch.mesh.index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+                device.cmd_draw_indexed(
+                    cmd,
+                    batch.mesh.index_count,
+                    batch.instance_count,
+                    0,
+                    0,
+                    batch.first_instance,
+                );
+            }
+        }
+    }
+
+    fn destroy(&mut self, device: &ash::Device) {
+        if let Some(mut pipelines) = self.pipelines.take() {
+            pipelines.destroy(device);
+        }
+    }
+}
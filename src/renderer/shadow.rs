@@ -0,0 +1,190 @@
+use crate::engine::camera::Camera;
+use crate::renderer::graph::{FrameContext, GraphPass, PassContext, PipelineContext};
+use crate::renderer::instancing::DrawList;
+use crate::renderer::material::BlendMode;
+use crate::renderer::pipeline::{
+    DepthTest, PipelineDesc, create_pipeline, create_pipeline_layout, mesh_vertex_input,
+};
+use anyhow::Result;
+use ash::vk;
+use glam::{Mat4, Vec3};
+
+/// Shader source the shadow pipeline is built from.
+pub const SHADOW_VERT: &str = "shadow.vert";
+
+/// Batches of `FrameItems::shadow_casters`, the shadow pass's input.
+pub struct ShadowDraws(pub DrawList);
+
+/// Depth-only pass from the directional light into the shadow map, which
+/// the scene pass samples with a comparison sampler (PCF). Only opaque
+/// batches of `ShadowDraws` cast shadows.
+#[derive(Default)]
+pub struct ShadowPass {
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl GraphPass for ShadowPass {
+    fn shaders(&self) -> Vec<&str> {
+        vec![SHADOW_VERT]
+    }
+
+    fn build_pipelines(&mut self, ctx: &PipelineContext) -> Result<()> {
+        // set 0 only: the vertex shader reads `light_view_proj` from it
+        if self.layout == vk::PipelineLayout::null() {
+            self.layout = create_pipeline_layout(ctx.device, &ctx.set_layouts[..1])?;
+        }
+        // no culling: open meshes (the floor plane) must still cast shadows;
+        // slope-scaled bias takes care of most of the acne
+        let (bindings, attributes) = mesh_vertex_input();
+        let desc = PipelineDesc {
+            frag_spv: None,
+            bindings: &bindings,
+            attributes: &attributes,
+            depth_bias: Some((1.25, 1.75)),
+            depth: Some(DepthTest {
+                write: true,
+                compare: vk::CompareOp::LESS,
+            }),
+            color: None,
+            ..PipelineDesc::new(ctx.target, self.layout, ctx.shaders.get(SHADOW_VERT), &[])
+        };
+        let pipeline = create_pipeline(ctx.device, ctx.cache, &desc)?;
+
+        if self.pipeline != vk::Pipeline::null() {
+            unsafe {
+                ctx.device.destroy_pipeline(self.pipeline, None);
+            }
+        }
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    fn record(&self, ctx: &PassContext, frame: &FrameContext) {
+        let Some(ShadowDraws(draws)) = frame.input::<ShadowDraws>() else {
+            return;
+        };
+        let (device, cmd) = (ctx.device, ctx.cmd);
+
+        unsafe {
+            device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                0,
+                &[frame.descriptor_set],
+                &[],
+            );
+
+            for batch in draws
+                .batches
+                .iter()
+                .filter(|b| b.pipeline.blend == BlendMode::Opaque)
+            {
+                device.cmd_bind_vertex_buffers(
+                    cmd,
+                    0,
+                    &[batch.mesh.vertex_buffer, draws.instance_buffer],
+                    &[0, 0],
+                );
+                device.cmd_bind_index_buffer(
+                    cmd,
+                    batch.mesh.index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+                device.cmd_draw_indexed(
+                    cmd,
+                    batch.mesh.index_count,
+                    batch.instance_count,
+                    0,
+                    0,
+                    batch.first_instance,
+                );
+            }
+        }
+    }
+
+    fn destroy(&mut self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+        }
+        *self = Self::default();
+    }
+}
+
+/// Comparison sampler for the shadow map; outside the map counts as lit
+/// (border depth 1.0).
+pub fn create_shadow_sampler(device: &ash::Device) -> Result<vk::Sampler> {
+    let sampler_info = vk::SamplerCreateInfo::default()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .compare_enable(true)
+        .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+        .max_lod(0.0);
+
+    Ok(unsafe { device.create_sampler(&sampler_info, None)? })
+}
+
+/// Orthographic light projection covering the camera frustum up to
+/// `distance`. The box is fitted to a sphere around that slice, so it does
+/// not change size as the camera turns, and its center is snapped to whole
+/// shadow-map texels so shadow edges don't shimmer while the camera moves.
+pub fn fit_light_view_proj(
+    direction: Vec3,
+    camera: &Camera,
+    aspect: f32,
+    distance: f32,
+    resolution: u32,
+) -> Mat4 {
+    let far = distance.min(camera.far).max(camera.near + 0.01);
+    let proj = Mat4::perspective_rh_gl(camera.fov_deg.to_radians(), aspect, camera.near, far);
+    let inv = (proj * camera.view()).inverse();
+
+    let mut corners = [Vec3::ZERO; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let ndc = Vec3::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+        );
+        *corner = inv.project_point3(ndc);
+    }
+
+    let center = corners.iter().copied().sum::<Vec3>() / 8.0;
+    let radius = corners
+        .iter()
+        .map(|c| c.distance(center))
+        .fold(0.0, f32::max);
+    // quantized so float noise doesn't change the texel size every frame
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let dir = direction.try_normalize().unwrap_or(Vec3::Y);
+    let up = if dir.dot(Vec3::Y).abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+
+    // snap the center in light space
+    let texel = 2.0 * radius / resolution.max(1) as f32;
+    let rotation = Mat4::look_at_rh(Vec3::ZERO, dir, up);
+    let mut snapped = rotation.transform_point3(center);
+    snapped.x = (snapped.x / texel).floor() * texel;
+    snapped.y = (snapped.y / texel).floor() * texel;
+    let center = rotation.inverse().transform_point3(snapped);
+
+    // start well behind the slice so casters outside it still land in the map
+    let eye = center - dir * radius * 2.0;
+    let view = Mat4::look_at_rh(eye, center, up);
+    let proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, radius * 4.0);
+
+    proj * view
+}
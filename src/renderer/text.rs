@@ -0,0 +1,345 @@
+use crate::assets::font::BitmapFont;
+use crate::core::device::Device;
+use crate::renderer::dynamic_mesh::DynamicMesh;
+use crate::renderer::graph::{FrameContext, GraphPass, PassContext, PipelineContext};
+use crate::renderer::material::BlendMode;
+use crate::renderer::mesh::MeshBuffers;
+use crate::renderer::pipeline::{PipelineDesc, create_pipeline};
+use crate::renderer::renderer::Renderer;
+use crate::renderer::texture::Texture;
+use crate::resources::buffer::Vertex;
+use crate::resources::descriptor::{
+    create_post_descriptor_pool, create_post_set_layout, write_post_set,
+};
+use crate::resources::sampler::SamplerDesc;
+use anyhow::Result;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec3};
+
+/// Shader sources the text pipeline is built from.
+pub const TEXT_VERT: &str = "text.vert";
+pub const TEXT_FRAG: &str = "text.frag";
+
+/// Immediate-mode screen-space text in a bitmap font. Strings added with
+/// `text` are drawn over the finished frame (after the post chain) in the
+/// next `draw_frame` only. Colors are written to the backbuffer as is.
+pub struct TextDraw {
+    font: BitmapFont,
+    atlas: Texture,
+    set_layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    /// Binds `atlas`; compatible with `TextPass`'s set 0.
+    set: vk::DescriptorSet,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    mesh: DynamicMesh,
+}
+
+/// Text quads of one frame, the text pass's input.
+#[derive(Clone, Copy)]
+pub struct TextQuads {
+    pub mesh: MeshBuffers,
+    /// The font atlas.
+    pub descriptor_set: vk::DescriptorSet,
+}
+
+impl TextDraw {
+    /// Uses the embedded 8x8 font.
+    pub fn new(dev: &Device) -> Result<Self> {
+        Self::with_font(dev, BitmapFont::embedded())
+    }
+
+    pub fn with_font(dev: &Device, font: BitmapFont) -> Result<Self> {
+        // glyphs are drawn at whole multiples of their size, so no
+        // filtering and no mips
+        let sampler = SamplerDesc {
+            max_lod: 0.0,
+            ..SamplerDesc::nearest().clamped()
+        };
+        let atlas = Texture::from_data(dev, &font.atlas, &sampler)?;
+
+        let device = &dev.device;
+        let set_layout = create_post_set_layout(device)?;
+        let pool = create_post_descriptor_pool(device, 1)?;
+        let alloc = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(std::slice::from_ref(&set_layout));
+        let set = unsafe { device.allocate_descriptor_sets(&alloc)? }[0];
+        write_post_set(device, set, atlas.descriptor_info());
+
+        Ok(Self {
+            font,
+            atlas,
+            set_layout,
+            pool,
+            set,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            mesh: DynamicMesh::new(),
+        })
+    }
+
+    /// Draws `text` with its top-left corner at `pos`, in pixels from the
+    /// top-left of the target. Glyphs are `scale` times their size in the
+    /// font; `'\n'` starts a new line.
+    pub fn text(&mut self, pos: Vec2, text: &str, scale: f32, color: Vec3) {
+        layout_text(
+            &self.font,
+            pos,
+            text,
+            scale,
+            color,
+            &mut self.vertices,
+            &mut self.indices,
+        );
+    }
+
+    /// Size in pixels of `text` drawn at `scale`.
+    pub fn measure(&self, text: &str, scale: f32) -> Vec2 {
+        let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let rows = text.lines().count();
+        Vec2::new(
+            (columns as u32 * self.font.glyph_width) as f32,
+            (rows as u32 * self.font.glyph_height) as f32,
+        ) * scale
+    }
+
+    /// Uploads this frame's quads (see `DynamicMesh::prepare`). `None` when
+    /// there is no text.
+    pub fn prepare(&mut self, dev: &Device, renderer: &Renderer) -> Result<Option<TextQuads>> {
+        self.mesh.update_vertices(&self.vertices);
+        self.mesh.update_indices(&self.indices);
+
+        let descriptor_set = self.set;
+        Ok(self.mesh.prepare(dev, renderer)?.map(|mesh| TextQuads {
+            mesh: mesh.buffers(),
+            descriptor_set,
+        }))
+    }
+
+    /// Drops the text of the frame just drawn.
+    pub fn end_frame(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    /// The GPU must be done with every frame that drew text.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        self.mesh.destroy(device);
+        self.atlas.destroy(device);
+        unsafe {
+            device.destroy_descriptor_pool(self.pool, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
+
+/// Appends two triangles per visible glyph of `text`. Quads are in pixels
+/// (`Vertex::pos` xy), with the atlas coordinates in `Vertex::uv`.
+fn layout_text(
+    font: &BitmapFont,
+    pos: Vec2,
+    text: &str,
+    scale: f32,
+    color: Vec3,
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+) {
+    let size = Vec2::new(font.glyph_width as f32, font.glyph_height as f32) * scale;
+
+    for (row, line) in text.lines().enumerate() {
+        for (column, c) in line.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let min = pos + Vec2::new(column as f32, row as f32) * size;
+            let max = min + size;
+            let [u0, v0, u1, v1] = font.glyph_uv(font.glyph(c));
+
+            let base = vertices.len() as u32;
+            for (x, y, u, v) in [
+                (min.x, min.y, u0, v0),
+                (max.x, min.y, u1, v0),
+                (max.x, max.y, u1, v1),
+                (min.x, max.y, u0, v1),
+            ] {
+                vertices.push(Vertex {
+                    pos: [x, y, 0.0],
+                    color: color.to_array(),
+                    normal: [0.0; 3],
+                    uv: [u, v],
+                });
+            }
+            indices.extend([0, 1, 2, 2, 3, 0].map(|i| base + i));
+        }
+    }
+}
+
+/// Push constants of `text.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct TextParams {
+    /// 1 / target size in pixels.
+    texel: [f32; 2],
+}
+
+/// Draws this frame's `TextQuads` alpha blended over its color attachment.
+#[derive(Default)]
+pub struct TextPass {
+    set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl TextPass {
+    fn create_layout(&mut self, device: &ash::Device) -> Result<()> {
+        self.set_layout = create_post_set_layout(device)?;
+
+        let push_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .offset(0)
+            .size(std::mem::size_of::<TextParams>() as u32);
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(std::slice::from_ref(&self.set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_range));
+        self.layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+
+        Ok(())
+    }
+}
+
+impl GraphPass for TextPass {
+    fn shaders(&self) -> Vec<&str> {
+        vec![TEXT_VERT, TEXT_FRAG]
+    }
+
+    fn build_pipelines(&mut self, ctx: &PipelineContext) -> Result<()> {
+        if self.layout == vk::PipelineLayout::null() {
+            self.create_layout(ctx.device)?;
+        }
+
+        // position, color and uv of `Vertex`; no depth, single sampled
+        // (drawn after the post chain)
+        let bindings = [Vertex::binding_description()];
+        let [pos, color, _, uv] = Vertex::attribute_descriptions();
+        let attributes = [pos, color, uv];
+        let desc = PipelineDesc {
+            bindings: &bindings,
+            attributes: &attributes,
+            color: Some(BlendMode::AlphaBlend),
+            ..PipelineDesc::new(
+                ctx.target,
+                self.layout,
+                ctx.shaders.get(TEXT_VERT),
+                ctx.shaders.get(TEXT_FRAG),
+            )
+        };
+        let pipeline = create_pipeline(ctx.device, ctx.cache, &desc)?;
+
+        unsafe {
+            ctx.device.destroy_pipeline(self.pipeline, None);
+        }
+        self.pipeline = pipeline;
+        Ok(())
+    }
+
+    fn record(&self, ctx: &PassContext, frame: &FrameContext) {
+        let Some(text) = frame.input::<TextQuads>() else {
+            return;
+        };
+        let (device, cmd) = (ctx.device, ctx.cmd);
+        let params = TextParams {
+            texel: [
+                1.0 / ctx.extent.width.max(1) as f32,
+                1.0 / ctx.extent.height.max(1) as f32,
+            ],
+        };
+
+        unsafe {
+            device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                0,
+                &[text.descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                cmd,
+                self.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&params),
+            );
+            device.cmd_bind_vertex_buffers(cmd, 0, &[text.mesh.vertex_buffer], &[0]);
+            device.cmd_bind_index_buffer(cmd, text.mesh.index_buffer, 0, vk::IndexType::UINT32);
+            device.cmd_draw_indexed(cmd, text.mesh.index_count, 1, 0, 0, 0);
+        }
+    }
+
+    fn destroy(&mut self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_descriptor_set_layout(self.set_layout, None);
+        }
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(text: &str, scale: f32) -> (Vec<Vertex>, Vec<u32>) {
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+        layout_text(
+            &BitmapFont::embedded(),
+            Vec2::new(10.0, 20.0),
+            text,
+            scale,
+            Vec3::ONE,
+            &mut vertices,
+            &mut indices,
+        );
+        (vertices, indices)
+    }
+
+    #[test]
+    fn spaces_and_newlines_emit_no_quads() {
+        let (vertices, indices) = layout("ab c\nd", 1.0);
+        assert_eq!(vertices.len(), 4 * 4);
+        assert_eq!(indices.len(), 4 * 6);
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+    }
+
+    #[test]
+    fn glyphs_advance_by_their_scaled_size() {
+        let (vertices, _) = layout("a b\nc", 2.0);
+
+        // top-left corners of "a", "b" (after the space) and "c"
+        assert_eq!(vertices[0].pos, [10.0, 20.0, 0.0]);
+        assert_eq!(vertices[4].pos, [42.0, 20.0, 0.0]);
+        assert_eq!(vertices[8].pos, [10.0, 36.0, 0.0]);
+        // bottom-right of "a"
+        assert_eq!(vertices[2].pos, [26.0, 36.0, 0.0]);
+    }
+
+    #[test]
+    fn embedded_font_covers_printable_ascii() {
+        let font = BitmapFont::embedded();
+        assert_eq!(font.glyph(' '), 0);
+        assert_eq!(font.glyph('~'), 94);
+        // unknown characters fall back to '?'
+        assert_eq!(font.glyph('\u{e9}'), font.glyph('?'));
+
+        let [u0, v0, u1, v1] = font.glyph_uv(font.glyph('~'));
+        assert!(u0 < u1 && v0 < v1 && u1 <= 1.0 && v1 <= 1.0);
+        assert_eq!(
+            font.atlas.rgba.len(),
+            (font.atlas.width * font.atlas.height * 4) as usize
+        );
+    }
+}
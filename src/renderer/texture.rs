@@ -0,0 +1,72 @@
+use crate::assets::texture::{TextureData, load_texture};
+use crate::core::device::Device;
+use crate::resources::{
+    image::{GpuImage, create_texture_image},
+    image_view::create_image_view,
+    sampler::{SamplerDesc, create_sampler},
+};
+use anyhow::Result;
+use ash::vk;
+use std::path::Path;
+
+/// Color textures are stored as sRGB so sampling returns linear values.
+pub const TEXTURE_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Sampled image ready to be bound as a combined image sampler.
+pub struct Texture {
+    pub image: GpuImage,
+    pub view: vk::ImageView,
+    pub sampler: vk::Sampler,
+}
+
+impl Texture {
+    pub fn from_data(dev: &Device, data: &TextureData, sampler: &SamplerDesc) -> Result<Self> {
+        let image = create_texture_image(
+            dev,
+            data.width,
+            data.height,
+            &data.rgba,
+            TEXTURE_FORMAT,
+            true,
+        )?;
+        let view = create_image_view(
+            &dev.device,
+            image.image,
+            image.format,
+            vk::ImageAspectFlags::COLOR,
+            image.mip_levels,
+        )?;
+        let sampler = create_sampler(dev, sampler)?;
+
+        Ok(Self {
+            image,
+            view,
+            sampler,
+        })
+    }
+
+    pub fn load(dev: &Device, path: impl AsRef<Path>, sampler: &SamplerDesc) -> Result<Self> {
+        Self::from_data(dev, &load_texture(path)?, sampler)
+    }
+
+    /// 1x1 white, bound when a mesh has no texture so the shader can
+    /// always sample.
+    pub fn white(dev: &Device) -> Result<Self> {
+        Self::from_data(dev, &TextureData::solid([255; 4]), &SamplerDesc::nearest())
+    }
+
+    pub fn descriptor_info(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo::default()
+            .sampler(self.sampler)
+            .image_view(self.view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+    }
+
+    pub fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_image_view(self.view, None);
+        }
+        self.image.destroy(device);
+    }
+}
@@ -0,0 +1,576 @@
+use anyhow::{Context, Result};
+use ash::vk;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Size of the device memory blocks resources are sub-allocated from.
+/// Requests over half of it get a dedicated block.
+const BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+/// Buffers and linear images vs optimal-tiling images. The two may not
+/// share a `bufferImageGranularity` page within a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Linear,
+    Optimal,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    /// `None` = free.
+    kind: Option<ResourceKind>,
+}
+
+impl Region {
+    fn end(&self) -> vk::DeviceSize {
+        self.offset + self.size
+    }
+}
+
+/// Free-list bookkeeping of one memory block: sorted regions covering the
+/// whole block, adjacent free regions merged. No Vulkan calls.
+#[derive(Debug)]
+pub struct BlockLayout {
+    size: vk::DeviceSize,
+    regions: Vec<Region>,
+    used: vk::DeviceSize,
+}
+
+impl BlockLayout {
+    pub fn new(size: vk::DeviceSize) -> Self {
+        Self {
+            size,
+            regions: vec![Region {
+                offset: 0,
+                size,
+                kind: None,
+            }],
+            used: 0,
+        }
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// Bytes in live allocations (alignment padding not included).
+    pub fn used(&self) -> vk::DeviceSize {
+        self.used
+    }
+
+    pub fn allocation_count(&self) -> usize {
+        self.regions.iter().filter(|r| r.kind.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocation_count() == 0
+    }
+
+    /// Best-fit allocation of `size` bytes at a multiple of `alignment`.
+    /// Neighbours of the other `kind` are kept `granularity` bytes apart
+    /// (both must be powers of two). Returns the offset, `None` if nothing
+    /// fits.
+    pub fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+        kind: ResourceKind,
+        granularity: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        if size == 0 {
+            return None;
+        }
+
+        // (region index, offset, leftover bytes)
+        let mut best: Option<(usize, vk::DeviceSize, vk::DeviceSize)> = None;
+
+        for (i, region) in self.regions.iter().enumerate() {
+            if region.kind.is_some() {
+                continue;
+            }
+
+            let mut offset = align_up(region.offset, alignment);
+            // free regions never touch each other, so neighbours are in use
+            let prev = i.checked_sub(1).map(|p| self.regions[p]);
+            if prev.is_some_and(|p| {
+                p.kind != Some(kind) && same_page(p.end() - 1, offset, granularity)
+            }) {
+                offset = align_up(offset, granularity);
+            }
+
+            let end = offset + size;
+            if end > region.end() {
+                continue;
+            }
+            let next = self.regions.get(i + 1);
+            if next
+                .is_some_and(|n| n.kind != Some(kind) && same_page(end - 1, n.offset, granularity))
+            {
+                continue;
+            }
+
+            let leftover = region.size - size;
+            if best.is_none_or(|(_, _, best_leftover)| leftover < best_leftover) {
+                best = Some((i, offset, leftover));
+            }
+        }
+
+        let (i, offset, _) = best?;
+        let region = self.regions[i];
+
+        let mut parts = Vec::with_capacity(3);
+        if offset > region.offset {
+            parts.push(Region {
+                offset: region.offset,
+                size: offset - region.offset,
+                kind: None,
+            });
+        }
+        parts.push(Region {
+            offset,
+            size,
+            kind: Some(kind),
+        });
+        if offset + size < region.end() {
+            parts.push(Region {
+                offset: offset + size,
+                size: region.end() - (offset + size),
+                kind: None,
+            });
+        }
+        self.regions.splice(i..=i, parts);
+
+        self.used += size;
+        Some(offset)
+    }
+
+    /// Frees the allocation starting at `offset`; false if there is none.
+    pub fn free(&mut self, offset: vk::DeviceSize) -> bool {
+        let Some(i) = self
+            .regions
+            .iter()
+            .position(|r| r.offset == offset && r.kind.is_some())
+        else {
+            return false;
+        };
+
+        self.used -= self.regions[i].size;
+        self.regions[i].kind = None;
+
+        if self.regions.get(i + 1).is_some_and(|r| r.kind.is_none()) {
+            self.regions[i].size += self.regions[i + 1].size;
+            self.regions.remove(i + 1);
+        }
+        if i > 0 && self.regions[i - 1].kind.is_none() {
+            self.regions[i - 1].size += self.regions[i].size;
+            self.regions.remove(i);
+        }
+        true
+    }
+}
+
+fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    let alignment = alignment.max(1);
+    value.div_ceil(alignment) * alignment
+}
+
+/// True if bytes `a` and `b` fall in the same `granularity` page.
+fn same_page(a: vk::DeviceSize, b: vk::DeviceSize, granularity: vk::DeviceSize) -> bool {
+    let page = !(granularity.max(1) - 1);
+    a & page == b & page
+}
+
+/// Used and free space of one memory heap.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeapStats {
+    pub block_count: usize,
+    pub allocation_count: usize,
+    /// Bytes of device memory allocated in blocks.
+    pub reserved: vk::DeviceSize,
+    /// Bytes of that in live allocations.
+    pub used: vk::DeviceSize,
+}
+
+impl HeapStats {
+    pub fn free(&self) -> vk::DeviceSize {
+        self.reserved - self.used
+    }
+}
+
+struct Block {
+    memory: vk::DeviceMemory,
+    memory_type: u32,
+    layout: BlockLayout,
+    /// Whole block, persistently mapped; null unless host visible.
+    mapped: *mut u8,
+    /// Sized for a single large resource; freed as soon as it is empty.
+    dedicated: bool,
+}
+
+struct AllocatorState {
+    device: ash::Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    granularity: vk::DeviceSize,
+    /// Indexed by `Allocation::block`; `None` slots are reused.
+    blocks: Vec<Option<Block>>,
+}
+
+/// Sub-allocates buffer and image memory from large per-memory-type
+/// blocks, so the number of `vkAllocateMemory` calls stays far below
+/// `maxMemoryAllocationCount`. Cloning shares the allocator.
+#[derive(Clone)]
+pub struct GpuAllocator {
+    state: Rc<RefCell<AllocatorState>>,
+}
+
+/// A range of a memory block. Freed by `free` (the `destroy` of whatever
+/// owns it), not on drop.
+pub struct Allocation {
+    pub memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    mapped: *mut u8,
+    block: usize,
+    allocator: GpuAllocator,
+}
+
+impl Allocation {
+    /// Start of the allocation in mapped memory; null unless the memory
+    /// type is host visible.
+    pub fn mapped_ptr(&self) -> *mut u8 {
+        self.mapped
+    }
+
+    pub fn free(&self) {
+        self.allocator.free(self.block, self.offset);
+    }
+}
+
+impl GpuAllocator {
+    pub fn new(
+        device: &ash::Device,
+        memory_properties: vk::PhysicalDeviceMemoryProperties,
+        properties: &vk::PhysicalDeviceProperties,
+    ) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(AllocatorState {
+                device: device.clone(),
+                memory_properties,
+                granularity: properties.limits.buffer_image_granularity.max(1),
+                blocks: Vec::new(),
+            })),
+        }
+    }
+
+    /// Memory for a resource with `reqs`, from memory type `memory_type`.
+    pub fn allocate(
+        &self,
+        reqs: vk::MemoryRequirements,
+        memory_type: u32,
+        kind: ResourceKind,
+    ) -> Result<Allocation> {
+        let mut state = self.state.borrow_mut();
+        let (block, offset) = state.allocate(reqs, memory_type, kind)?;
+        let b = state.blocks[block]
+            .as_ref()
+            .expect("allocated from a freed block");
+
+        let mapped = if b.mapped.is_null() {
+            std::ptr::null_mut()
+        } else {
+            unsafe { b.mapped.add(offset as usize) }
+        };
+
+        Ok(Allocation {
+            memory: b.memory,
+            offset,
+            mapped,
+            block,
+            allocator: self.clone(),
+        })
+    }
+
+    fn free(&self, block: usize, offset: vk::DeviceSize) {
+        self.state.borrow_mut().free(block, offset);
+    }
+
+    /// Indexed by memory heap.
+    pub fn stats(&self) -> Vec<HeapStats> {
+        let state = self.state.borrow();
+        let mut stats =
+            vec![HeapStats::default(); state.memory_properties.memory_heap_count as usize];
+
+        for block in state.blocks.iter().flatten() {
+            let heap = state.memory_properties.memory_types[block.memory_type as usize].heap_index;
+            let s = &mut stats[heap as usize];
+            s.block_count += 1;
+            s.allocation_count += block.layout.allocation_count();
+            s.reserved += block.layout.size();
+            s.used += block.layout.used();
+        }
+        stats
+    }
+
+    pub fn log_stats(&self) {
+        const MIB: f64 = 1024.0 * 1024.0;
+        for (heap, s) in self.stats().iter().enumerate() {
+            if s.block_count == 0 {
+                continue;
+            }
+            log::info!(
+                "GPU heap {}: {:.1} MiB used, {:.1} MiB free in {} blocks ({} allocations)",
+                heap,
+                s.used as f64 / MIB,
+                s.free() as f64 / MIB,
+                s.block_count,
+                s.allocation_count
+            );
+        }
+    }
+
+    /// Frees every block. Allocations still alive at this point are leaks
+    /// and get logged.
+    pub fn destroy(&self) {
+        let mut state = self.state.borrow_mut();
+        let leaked: usize = state
+            .blocks
+            .iter()
+            .flatten()
+            .map(|b| b.layout.allocation_count())
+            .sum();
+        if leaked > 0 {
+            log::warn!("{} GPU allocations still alive at shutdown", leaked);
+        }
+
+        let blocks: Vec<Block> = state.blocks.drain(..).flatten().collect();
+        for block in blocks {
+            state.free_block(block);
+        }
+    }
+}
+
+impl AllocatorState {
+    fn allocate(
+        &mut self,
+        reqs: vk::MemoryRequirements,
+        memory_type: u32,
+        kind: ResourceKind,
+    ) -> Result<(usize, vk::DeviceSize)> {
+        let granularity = self.granularity;
+
+        if reqs.size > self.block_size(memory_type) / 2 {
+            let index = self.new_block(memory_type, reqs.size, true)?;
+            let block = self.blocks[index].as_mut().unwrap();
+            let offset = block
+                .layout
+                .allocate(reqs.size, reqs.alignment, kind, granularity)
+                .context("dedicated block too small")?;
+            return Ok((index, offset));
+        }
+
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            let Some(block) = block else { continue };
+            if block.memory_type != memory_type || block.dedicated {
+                continue;
+            }
+            if let Some(offset) =
+                block
+                    .layout
+                    .allocate(reqs.size, reqs.alignment, kind, granularity)
+            {
+                return Ok((index, offset));
+            }
+        }
+
+        let index = self.new_block(memory_type, self.block_size(memory_type), false)?;
+        let block = self.blocks[index].as_mut().unwrap();
+        let offset = block
+            .layout
+            .allocate(reqs.size, reqs.alignment, kind, granularity)
+            .context("fresh block too small")?;
+        Ok((index, offset))
+    }
+
+    /// `BLOCK_SIZE`, or an eighth of the heap for small heaps.
+    fn block_size(&self, memory_type: u32) -> vk::DeviceSize {
+        let heap = self.memory_properties.memory_types[memory_type as usize].heap_index;
+        let heap_size = self.memory_properties.memory_heaps[heap as usize].size;
+        BLOCK_SIZE.min(heap_size / 8).max(1)
+    }
+
+    fn new_block(
+        &mut self,
+        memory_type: u32,
+        size: vk::DeviceSize,
+        dedicated: bool,
+    ) -> Result<usize> {
+        let info = vk::MemoryAllocateInfo::default()
+            .allocation_size(size)
+            .memory_type_index(memory_type);
+        let memory = unsafe { self.device.allocate_memory(&info, None) }
+            .with_context(|| format!("Failed to allocate {} bytes of GPU memory", size))?;
+
+        let flags = self.memory_properties.memory_types[memory_type as usize].property_flags;
+        let mapped = if flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            match unsafe {
+                self.device
+                    .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+            } {
+                Ok(ptr) => ptr as *mut u8,
+                Err(e) => {
+                    unsafe { self.device.free_memory(memory, None) };
+                    return Err(e).context("Failed to map GPU memory block");
+                }
+            }
+        } else {
+            std::ptr::null_mut()
+        };
+
+        let block = Block {
+            memory,
+            memory_type,
+            layout: BlockLayout::new(size),
+            mapped,
+            dedicated,
+        };
+
+        match self.blocks.iter().position(Option::is_none) {
+            Some(index) => {
+                self.blocks[index] = Some(block);
+                Ok(index)
+            }
+            None => {
+                self.blocks.push(Some(block));
+                Ok(self.blocks.len() - 1)
+            }
+        }
+    }
+
+    fn free(&mut self, index: usize, offset: vk::DeviceSize) {
+        let Some(block) = self.blocks.get_mut(index).and_then(Option::as_mut) else {
+            log::error!("GPU memory freed twice (block {})", index);
+            return;
+        };
+        if !block.layout.free(offset) {
+            log::error!(
+                "GPU memory freed twice (block {}, offset {})",
+                index,
+                offset
+            );
+            return;
+        }
+        if !block.layout.is_empty() {
+            return;
+        }
+
+        // keep one empty block per memory type around for reuse
+        let memory_type = block.memory_type;
+        let dedicated = block.dedicated;
+        let has_other = self.blocks.iter().enumerate().any(|(i, b)| {
+            i != index
+                && b.as_ref()
+                    .is_some_and(|b| b.memory_type == memory_type && !b.dedicated)
+        });
+        if dedicated || has_other {
+            let block = self.blocks[index].take().unwrap();
+            self.free_block(block);
+        }
+    }
+
+    fn free_block(&self, block: Block) {
+        unsafe {
+            if !block.mapped.is_null() {
+                self.device.unmap_memory(block.memory);
+            }
+            self.device.free_memory(block.memory, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_aligned_and_disjoint() {
+        let mut block = BlockLayout::new(1024);
+        let a = block.allocate(100, 16, ResourceKind::Linear, 1).unwrap();
+        let b = block.allocate(100, 256, ResourceKind::Linear, 1).unwrap();
+        let c = block.allocate(10, 64, ResourceKind::Linear, 1).unwrap();
+
+        assert_eq!(a, 0);
+        assert_eq!(b % 256, 0);
+        assert_eq!(c % 64, 0);
+        for (x, y) in [(a, b), (a, c), (b, c)] {
+            let (lo, hi) = (x.min(y), x.max(y));
+            assert!(lo + 100 <= hi);
+        }
+        assert_eq!(block.used(), 210);
+        assert_eq!(block.allocation_count(), 3);
+    }
+
+    #[test]
+    fn freeing_merges_neighbours() {
+        let mut block = BlockLayout::new(300);
+        let a = block.allocate(100, 1, ResourceKind::Linear, 1).unwrap();
+        let b = block.allocate(100, 1, ResourceKind::Linear, 1).unwrap();
+        let c = block.allocate(100, 1, ResourceKind::Linear, 1).unwrap();
+        assert!(block.allocate(1, 1, ResourceKind::Linear, 1).is_none());
+
+        assert!(block.free(a));
+        assert!(block.free(c));
+        assert!(block.free(b));
+        assert!(!block.free(b));
+        assert!(block.is_empty());
+
+        // one region again: the whole block fits
+        assert_eq!(block.allocate(300, 1, ResourceKind::Linear, 1), Some(0));
+    }
+
+    #[test]
+    fn best_fit_prefers_the_tightest_hole() {
+        let mut block = BlockLayout::new(1000);
+        let a = block.allocate(300, 1, ResourceKind::Linear, 1).unwrap();
+        let _ = block.allocate(10, 1, ResourceKind::Linear, 1).unwrap();
+        let c = block.allocate(100, 1, ResourceKind::Linear, 1).unwrap();
+        let _ = block.allocate(10, 1, ResourceKind::Linear, 1).unwrap();
+        block.free(a);
+        block.free(c);
+
+        assert_eq!(block.allocate(90, 1, ResourceKind::Linear, 1), Some(c));
+    }
+
+    #[test]
+    fn linear_and_optimal_resources_do_not_share_a_page() {
+        let mut block = BlockLayout::new(4096);
+        let buffer = block.allocate(100, 4, ResourceKind::Linear, 1024).unwrap();
+        let image = block.allocate(100, 4, ResourceKind::Optimal, 1024).unwrap();
+        assert_eq!(buffer, 0);
+        assert_eq!(image, 1024);
+
+        // same kind may share the page
+        let buffer2 = block.allocate(100, 4, ResourceKind::Linear, 1024).unwrap();
+        assert_eq!(buffer2, 100);
+
+        // an image after a buffer skips to the next page
+        let mut block = BlockLayout::new(2048);
+        let buffer = block.allocate(1000, 1, ResourceKind::Linear, 1024).unwrap();
+        let image = block.allocate(100, 1, ResourceKind::Optimal, 1024).unwrap();
+        assert_eq!(image, 1024);
+
+        // buffers may end right before the image's page, but not after it
+        assert!(block.free(buffer));
+        assert_eq!(block.allocate(1024, 1, ResourceKind::Linear, 1024), Some(0));
+        assert!(block.allocate(10, 1, ResourceKind::Linear, 1024).is_none());
+    }
+
+    #[test]
+    fn out_of_space_returns_none() {
+        let mut block = BlockLayout::new(256);
+        assert!(block.allocate(512, 1, ResourceKind::Linear, 1).is_none());
+        assert!(block.allocate(0, 1, ResourceKind::Linear, 1).is_none());
+        assert!(block.allocate(200, 1, ResourceKind::Linear, 1).is_some());
+        assert!(block.allocate(100, 1, ResourceKind::Linear, 1).is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::core::device::Device;
+use crate::resources::allocator::{Allocation, ResourceKind};
 use anyhow::Result;
 use ash::vk;
 use bytemuck::{Pod, Zeroable};
@@ -8,6 +9,9 @@ use bytemuck::{Pod, Zeroable};
 pub struct Vertex {
     pub pos: [f32; 3],
     pub color: [f32; 3],
+    pub normal: [f32; 3],
+    /// Texture coordinates, origin at the top-left of the image.
+    pub uv: [f32; 2],
 }
 
 impl Vertex {
@@ -18,7 +22,7 @@ impl Vertex {
             .input_rate(vk::VertexInputRate::VERTEX)
     }
 
-    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
+    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
         [
             vk::VertexInputAttributeDescription::default()
                 .binding(0)
@@ -30,31 +34,87 @@ impl Vertex {
                 .location(1)
                 .format(vk::Format::R32G32B32_SFLOAT)
                 .offset(12),
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(2)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset(24),
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(3)
+                .format(vk::Format::R32G32_SFLOAT)
+                .offset(36),
         ]
     }
 }
 
+/// Per-instance vertex data (binding 1, `VertexInputRate::INSTANCE`).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct InstanceData {
+    pub model: [[f32; 4]; 4],
+    /// Multiplied into the fragment alpha.
+    pub opacity: f32,
+}
+
+impl InstanceData {
+    pub fn binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(1)
+            .stride(std::mem::size_of::<InstanceData>() as u32)
+            .input_rate(vk::VertexInputRate::INSTANCE)
+    }
+
+    /// A mat4 attribute takes one location per column (4..=7), opacity
+    /// is location 8.
+    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 5] {
+        std::array::from_fn(|i| {
+            let attr = vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(4 + i as u32);
+            if i < 4 {
+                attr.format(vk::Format::R32G32B32A32_SFLOAT)
+                    .offset(16 * i as u32)
+            } else {
+                attr.format(vk::Format::R32_SFLOAT).offset(64)
+            }
+        })
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct UniformBufferObject {
     pub view_proj: [[f32; 4]; 4],
+    /// Light space (shadow map) projection.
+    pub light_view_proj: [[f32; 4]; 4],
 }
 
-pub struct GpuBuffer {
-    pub buffer: vk::Buffer,
-    pub memory: vk::DeviceMemory,
-    pub size: vk::DeviceSize,
+/// std140 layout of `Lighting` in lit.frag (set 0, binding 1).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightingUbo {
+    /// xyz: direction the light travels (world space), w: shadow bias
+    pub direction: [f32; 4],
+    /// rgb: light color * intensity, w: specular strength
+    pub color: [f32; 4],
+    /// rgb: ambient color, w: unused
+    pub ambient: [f32; 4],
+    /// xyz: camera position (world space), w: specular shininess
+    pub camera_pos: [f32; 4],
 }
 
-pub fn create_vertex_buffer(dev: &Device, vertices: &[Vertex]) -> Result<GpuBuffer> {
-    if vertices.is_empty() {
-        anyhow::bail!("create_vertex_buffer called with 0 vertices");
-    }
-
-    let size = (std::mem::size_of::<Vertex>() * vertices.len()) as u64;
-    let bytes = bytemuck::cast_slice(vertices); // &[u8]
+/// std140 layout of `MaterialParams` in lit.frag (set 1, binding 0).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUbo {
+    pub base_color: [f32; 4],
+}
 
-    create_device_local_buffer_with_staging(dev, size, vk::BufferUsageFlags::VERTEX_BUFFER, bytes)
+pub struct GpuBuffer {
+    pub buffer: vk::Buffer,
+    pub allocation: Allocation,
+    pub size: vk::DeviceSize,
 }
 
 fn find_memory_type(
@@ -75,105 +135,42 @@ fn find_memory_type(
 }
 
 impl GpuBuffer {
-    /// Destroys the Vulkan buffer and frees its memory.
-    ///
-    /// # Safety / Requirements
-    /// The GPU must not be using this buffer anymore (e.g., device idle or proper sync).
-    pub fn destroy(&self, device: &ash::Device) {
-        unsafe {
-            device.destroy_buffer(self.buffer, None);
-            device.free_memory(self.memory, None);
-        }
+    /// Start of the buffer in persistently mapped memory; null unless it
+    /// was created `HOST_VISIBLE`.
+    pub fn mapped_ptr(&self) -> *mut u8 {
+        self.allocation.mapped_ptr()
     }
-}
 
-pub fn create_device_local_buffer_with_staging(
-    dev: &Device,
-    size: vk::DeviceSize,
-    usage: vk::BufferUsageFlags,
-    src_bytes: &[u8],
-) -> Result<GpuBuffer> {
-    if src_bytes.is_empty() || size == 0 {
-        anyhow::bail!("staging upload called with empty data");
-    }
-    if src_bytes.len() as u64 != size {
-        anyhow::bail!(
-            "size mismatch: size={} but src_bytes={}",
-            size,
-            src_bytes.len()
+    /// Copies `bytes` to the start of a host-visible buffer.
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        let dst = self.mapped_ptr();
+        assert!(
+            !dst.is_null(),
+            "write_bytes on a buffer that is not host visible"
+        );
+        assert!(
+            bytes.len() as u64 <= self.size,
+            "write_bytes past the end of the buffer"
         );
-    }
-
-    // ✅ macOS / MoltenVK safe path: avoid DEVICE_LOCAL (Metal heaps)
-    #[cfg(target_os = "macos")]
-    {
-        let buf = create_buffer(
-            &dev.device,
-            &dev.memory_properties,
-            size,
-            usage,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        )?;
-
         unsafe {
-            let data = dev
-                .device
-                .map_memory(buf.memory, 0, size, vk::MemoryMapFlags::empty())?;
-            std::ptr::copy_nonoverlapping(src_bytes.as_ptr(), data as *mut u8, src_bytes.len());
-            dev.device.unmap_memory(buf.memory);
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len());
         }
-
-        return Ok(buf);
     }
 
-    // ✅ non-macOS path: staging -> DEVICE_LOCAL
-    #[cfg(not(target_os = "macos"))]
-    {
-        // 1) staging buffer (CPU visible)
-        let staging = create_buffer(
-            &dev.device,
-            &dev.memory_properties,
-            size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        )?;
-
-        unsafe {
-            let data =
-                dev.device
-                    .map_memory(staging.memory, 0, size, vk::MemoryMapFlags::empty())?;
-            std::ptr::copy_nonoverlapping(src_bytes.as_ptr(), data as *mut u8, src_bytes.len());
-            dev.device.unmap_memory(staging.memory);
-        }
-
-        // 2) device-local buffer (GPU only)
-        let dst = create_buffer(
-            &dev.device,
-            &dev.memory_properties,
-            size,
-            usage | vk::BufferUsageFlags::TRANSFER_DST,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        )?;
-
-        // 3) copy staging -> device local
-        let cmd = dev.begin_one_time_commands()?;
+    /// Destroys the Vulkan buffer and frees its memory.
+    ///
+    /// # Safety / Requirements
+    /// The GPU must not be using this buffer anymore (e.g., device idle or proper sync).
+    pub fn destroy(&self, device: &ash::Device) {
         unsafe {
-            let region = vk::BufferCopy::default().size(size);
-            dev.device
-                .cmd_copy_buffer(cmd, staging.buffer, dst.buffer, &[region]);
+            device.destroy_buffer(self.buffer, None);
         }
-        dev.end_one_time_commands(cmd)?;
-
-        // 4) cleanup staging
-        staging.destroy(&dev.device);
-
-        Ok(dst)
+        self.allocation.free();
     }
 }
 
 pub fn create_buffer(
-    device: &ash::Device,
-    mem_props: &vk::PhysicalDeviceMemoryProperties,
+    dev: &Device,
     size: vk::DeviceSize,
     usage: vk::BufferUsageFlags,
     props: vk::MemoryPropertyFlags,
@@ -187,45 +184,36 @@ pub fn create_buffer(
         .usage(usage)
         .sharing_mode(vk::SharingMode::EXCLUSIVE);
 
+    let device = &dev.device;
     let buffer = unsafe { device.create_buffer(&buffer_info, None)? };
     let reqs = unsafe { device.get_buffer_memory_requirements(buffer) };
 
-    let mem_index = find_memory_type(mem_props, reqs.memory_type_bits, props)?;
-
-    let alloc = vk::MemoryAllocateInfo::default()
-        .allocation_size(reqs.size)
-        .memory_type_index(mem_index);
-
-    let memory = unsafe { device.allocate_memory(&alloc, None)? };
-    unsafe { device.bind_buffer_memory(buffer, memory, 0)? };
+    let allocation = find_memory_type(&dev.memory_properties, reqs.memory_type_bits, props)
+        .and_then(|mem_index| {
+            dev.allocator
+                .allocate(reqs, mem_index, ResourceKind::Linear)
+        });
+    let allocation = match allocation {
+        Ok(allocation) => allocation,
+        Err(e) => {
+            unsafe { device.destroy_buffer(buffer, None) };
+            return Err(e);
+        }
+    };
+    unsafe { device.bind_buffer_memory(buffer, allocation.memory, allocation.offset)? };
 
     Ok(GpuBuffer {
         buffer,
-        memory,
+        allocation,
         size,
     })
 }
 
-pub fn create_uniform_buffer(
-    device: &ash::Device,
-    mem_props: &vk::PhysicalDeviceMemoryProperties,
-) -> Result<GpuBuffer> {
+pub fn create_uniform_buffer(dev: &Device, size: vk::DeviceSize) -> Result<GpuBuffer> {
     create_buffer(
-        device,
-        mem_props,
-        std::mem::size_of::<UniformBufferObject>() as u64,
+        dev,
+        size,
         vk::BufferUsageFlags::UNIFORM_BUFFER,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
     )
 }
-
-pub fn create_index_buffer_u32(dev: &Device, indices: &[u32]) -> Result<GpuBuffer> {
-    if indices.is_empty() {
-        anyhow::bail!("create_index_buffer called with 0 indices");
-    }
-
-    let size = (std::mem::size_of::<u32>() * indices.len()) as u64;
-    let bytes = bytemuck::cast_slice(indices); // &[u8]
-
-    create_device_local_buffer_with_staging(dev, size, vk::BufferUsageFlags::INDEX_BUFFER, bytes)
-}
@@ -1,26 +1,44 @@
 use anyhow::Result;
 use ash::vk;
 
+/// Set 0, per frame. Binding 0: camera UBO (vertex), binding 1: lighting
+/// UBO (fragment), binding 2: shadow map (fragment).
 pub fn create_descriptor_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
-    let binding = vk::DescriptorSetLayoutBinding::default()
-        .binding(0)
-        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-        .descriptor_count(1)
-        .stage_flags(vk::ShaderStageFlags::VERTEX);
+    let bindings = [
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX),
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(2)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+    ];
 
-    let info =
-        vk::DescriptorSetLayoutCreateInfo::default().bindings(std::slice::from_ref(&binding));
+    let info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
 
     Ok(unsafe { device.create_descriptor_set_layout(&info, None)? })
 }
 
 pub fn create_descriptor_pool(device: &ash::Device, count: u32) -> Result<vk::DescriptorPool> {
-    let pool_size = vk::DescriptorPoolSize::default()
-        .ty(vk::DescriptorType::UNIFORM_BUFFER)
-        .descriptor_count(count);
+    let pool_sizes = [
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(count * 2), // camera + lighting per set
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(count), // shadow map
+    ];
 
     let info = vk::DescriptorPoolCreateInfo::default()
-        .pool_sizes(std::slice::from_ref(&pool_size))
+        .pool_sizes(&pool_sizes)
         .max_sets(count);
 
     Ok(unsafe { device.create_descriptor_pool(&info, None)? })
@@ -45,6 +63,9 @@ pub fn update_descriptor_sets(
     sets: &[vk::DescriptorSet],
     uniform_buffers: &[vk::Buffer],
     range: vk::DeviceSize,
+    light_buffers: &[vk::Buffer],
+    light_range: vk::DeviceSize,
+    shadow_map: vk::DescriptorImageInfo,
 ) {
     for (i, &set) in sets.iter().enumerate() {
         let buffer_info = vk::DescriptorBufferInfo::default()
@@ -52,14 +73,144 @@ pub fn update_descriptor_sets(
             .offset(0)
             .range(range);
 
-        let write = vk::WriteDescriptorSet::default()
+        let light_info = vk::DescriptorBufferInfo::default()
+            .buffer(light_buffers[i])
+            .offset(0)
+            .range(light_range);
+
+        let writes = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(std::slice::from_ref(&buffer_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(1)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(std::slice::from_ref(&light_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(2)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(std::slice::from_ref(&shadow_map)),
+        ];
+
+        unsafe {
+            device.update_descriptor_sets(&writes, &[]);
+        }
+    }
+}
+
+/// Set 1, per material. Binding 0: material UBO, binding 1: base color
+/// texture (both fragment).
+pub fn create_material_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
+    let bindings = [
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+    ];
+
+    let info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+
+    Ok(unsafe { device.create_descriptor_set_layout(&info, None)? })
+}
+
+pub fn create_material_descriptor_pool(
+    device: &ash::Device,
+    count: u32,
+) -> Result<vk::DescriptorPool> {
+    let pool_sizes = [
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(count),
+        vk::DescriptorPoolSize::default()
+            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(count),
+    ];
+
+    let info = vk::DescriptorPoolCreateInfo::default()
+        .pool_sizes(&pool_sizes)
+        .max_sets(count);
+
+    Ok(unsafe { device.create_descriptor_pool(&info, None)? })
+}
+
+pub fn write_material_set(
+    device: &ash::Device,
+    set: vk::DescriptorSet,
+    uniform_buffer: vk::Buffer,
+    range: vk::DeviceSize,
+    texture: vk::DescriptorImageInfo,
+) {
+    let buffer_info = vk::DescriptorBufferInfo::default()
+        .buffer(uniform_buffer)
+        .offset(0)
+        .range(range);
+
+    let writes = [
+        vk::WriteDescriptorSet::default()
             .dst_set(set)
             .dst_binding(0)
             .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .buffer_info(std::slice::from_ref(&buffer_info));
+            .buffer_info(std::slice::from_ref(&buffer_info)),
+        vk::WriteDescriptorSet::default()
+            .dst_set(set)
+            .dst_binding(1)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(std::slice::from_ref(&texture)),
+    ];
 
-        unsafe {
-            device.update_descriptor_sets(std::slice::from_ref(&write), &[]);
-        }
+    unsafe {
+        device.update_descriptor_sets(&writes, &[]);
+    }
+}
+
+/// Post-processing passes: binding 0, the input image (fragment).
+pub fn create_post_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
+    let binding = vk::DescriptorSetLayoutBinding::default()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+
+    let info =
+        vk::DescriptorSetLayoutCreateInfo::default().bindings(std::slice::from_ref(&binding));
+
+    Ok(unsafe { device.create_descriptor_set_layout(&info, None)? })
+}
+
+pub fn create_post_descriptor_pool(device: &ash::Device, count: u32) -> Result<vk::DescriptorPool> {
+    let pool_size = vk::DescriptorPoolSize::default()
+        .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(count);
+
+    let info = vk::DescriptorPoolCreateInfo::default()
+        .pool_sizes(std::slice::from_ref(&pool_size))
+        .max_sets(count);
+
+    Ok(unsafe { device.create_descriptor_pool(&info, None)? })
+}
+
+pub fn write_post_set(
+    device: &ash::Device,
+    set: vk::DescriptorSet,
+    input: vk::DescriptorImageInfo,
+) {
+    let write = vk::WriteDescriptorSet::default()
+        .dst_set(set)
+        .dst_binding(0)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .image_info(std::slice::from_ref(&input));
+
+    unsafe {
+        device.update_descriptor_sets(&[write], &[]);
     }
 }
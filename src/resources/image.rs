@@ -0,0 +1,352 @@
+use crate::core::device::Device;
+use crate::renderer::renderer::find_memory_type_fallback;
+use crate::resources::allocator::{Allocation, ResourceKind};
+use crate::resources::buffer::create_buffer;
+use anyhow::Result;
+use ash::vk;
+
+pub struct GpuImage {
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub mip_levels: u32,
+}
+
+impl GpuImage {
+    /// The GPU must not be using the image anymore.
+    pub fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_image(self.image, None);
+        }
+        self.allocation.free();
+    }
+}
+
+/// Full mip chain length for a `width` x `height` image.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// 2D, optimal tiling, in `UNDEFINED` layout.
+pub fn create_image(
+    dev: &Device,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+    samples: vk::SampleCountFlags,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+) -> Result<GpuImage> {
+    let info = vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .extent(vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        })
+        .mip_levels(mip_levels)
+        .array_layers(1)
+        .samples(samples)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED);
+
+    let image = unsafe { dev.device.create_image(&info, None)? };
+    let reqs = unsafe { dev.device.get_image_memory_requirements(image) };
+
+    let mem_index = find_memory_type_fallback(
+        &dev.memory_properties,
+        reqs.memory_type_bits,
+        &[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::empty(),
+        ],
+    )?;
+
+    let allocation = match dev
+        .allocator
+        .allocate(reqs, mem_index, ResourceKind::Optimal)
+    {
+        Ok(allocation) => allocation,
+        Err(e) => {
+            unsafe { dev.device.destroy_image(image, None) };
+            return Err(e);
+        }
+    };
+    unsafe {
+        dev.device
+            .bind_image_memory(image, allocation.memory, allocation.offset)?
+    };
+
+    Ok(GpuImage {
+        image,
+        allocation,
+        format,
+        extent,
+        mip_levels,
+    })
+}
+
+/// Records a layout transition for mips `base_mip .. base_mip + level_count`
+/// of a color image. Only the transitions the upload path needs are
+/// supported.
+pub fn transition_image_layout(
+    device: &ash::Device,
+    cmd: vk::CommandBuffer,
+    image: vk::Image,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    base_mip: u32,
+    level_count: u32,
+) -> Result<()> {
+    use vk::ImageLayout as L;
+
+    let (src_access, dst_access, src_stage, dst_stage) = match (old_layout, new_layout) {
+        (L::UNDEFINED, L::TRANSFER_DST_OPTIMAL) => (
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+        (L::TRANSFER_DST_OPTIMAL, L::TRANSFER_SRC_OPTIMAL) => (
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+        (L::TRANSFER_DST_OPTIMAL, L::SHADER_READ_ONLY_OPTIMAL) => (
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        (L::TRANSFER_SRC_OPTIMAL, L::SHADER_READ_ONLY_OPTIMAL) => (
+            vk::AccessFlags::TRANSFER_READ,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        _ => anyhow::bail!(
+            "unsupported layout transition {:?} -> {:?}",
+            old_layout,
+            new_layout
+        ),
+    };
+
+    let barrier = vk::ImageMemoryBarrier::default()
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access)
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(
+            vk::ImageSubresourceRange::default()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .base_mip_level(base_mip)
+                .level_count(level_count)
+                .layer_count(1),
+        );
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            cmd,
+            src_stage,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+    }
+
+    Ok(())
+}
+
+/// Uploads tightly packed RGBA8 pixels through a staging buffer and leaves
+/// the image in `SHADER_READ_ONLY_OPTIMAL`. With `mipmaps` the full chain is
+/// generated with linear blits, if the format supports it.
+pub fn create_texture_image(
+    dev: &Device,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    format: vk::Format,
+    mipmaps: bool,
+) -> Result<GpuImage> {
+    let size = width as u64 * height as u64 * 4;
+    if size == 0 {
+        anyhow::bail!("create_texture_image called with an empty image");
+    }
+    if rgba.len() as u64 != size {
+        anyhow::bail!(
+            "texture size mismatch: {}x{} needs {} bytes, got {}",
+            width,
+            height,
+            size,
+            rgba.len()
+        );
+    }
+
+    let mip_levels = if !mipmaps {
+        1
+    } else if dev.supports_linear_blit(format) {
+        mip_level_count(width, height)
+    } else {
+        log::warn!(
+            "{:?} does not support linear blits, skipping mipmaps",
+            format
+        );
+        1
+    };
+
+    // 1) staging buffer (CPU visible)
+    let staging = create_buffer(
+        dev,
+        size,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )?;
+
+    staging.write_bytes(rgba);
+
+    // 2) device-local image; TRANSFER_SRC so the mip blits can read it
+    let extent = vk::Extent2D { width, height };
+    let image = create_image(
+        dev,
+        extent,
+        mip_levels,
+        vk::SampleCountFlags::TYPE_1,
+        format,
+        vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | vk::ImageUsageFlags::SAMPLED,
+    )?;
+
+    // 3) copy staging -> mip 0, then build the rest of the chain
+    let cmd = dev.begin_one_time_commands()?;
+    transition_image_layout(
+        &dev.device,
+        cmd,
+        image.image,
+        vk::ImageLayout::UNDEFINED,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        0,
+        mip_levels,
+    )?;
+
+    unsafe {
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .layer_count(1),
+            )
+            .image_extent(vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            });
+
+        dev.device.cmd_copy_buffer_to_image(
+            cmd,
+            staging.buffer,
+            image.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region],
+        );
+    }
+
+    generate_mipmaps(&dev.device, cmd, &image)?;
+    dev.end_one_time_commands(cmd)?;
+
+    // 4) cleanup staging
+    staging.destroy(&dev.device);
+
+    Ok(image)
+}
+
+/// Expects every mip in `TRANSFER_DST_OPTIMAL` with mip 0 filled in. Each
+/// level is blitted from the one above it, and all of them end up in
+/// `SHADER_READ_ONLY_OPTIMAL`.
+fn generate_mipmaps(device: &ash::Device, cmd: vk::CommandBuffer, image: &GpuImage) -> Result<()> {
+    let mut w = image.extent.width as i32;
+    let mut h = image.extent.height as i32;
+
+    for level in 1..image.mip_levels {
+        transition_image_layout(
+            device,
+            cmd,
+            image.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            level - 1,
+            1,
+        )?;
+
+        let next_w = (w / 2).max(1);
+        let next_h = (h / 2).max(1);
+
+        let blit = vk::ImageBlit::default()
+            .src_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(level - 1)
+                    .layer_count(1),
+            )
+            .src_offsets([vk::Offset3D::default(), vk::Offset3D { x: w, y: h, z: 1 }])
+            .dst_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(level)
+                    .layer_count(1),
+            )
+            .dst_offsets([
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: next_w,
+                    y: next_h,
+                    z: 1,
+                },
+            ]);
+
+        unsafe {
+            device.cmd_blit_image(
+                cmd,
+                image.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[blit],
+                vk::Filter::LINEAR,
+            );
+        }
+
+        transition_image_layout(
+            device,
+            cmd,
+            image.image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            level - 1,
+            1,
+        )?;
+
+        w = next_w;
+        h = next_h;
+    }
+
+    // last level was only ever written to
+    transition_image_layout(
+        device,
+        cmd,
+        image.image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        image.mip_levels - 1,
+        1,
+    )
+}
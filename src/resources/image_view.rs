@@ -0,0 +1,23 @@
+use anyhow::Result;
+use ash::vk;
+
+pub fn create_image_view(
+    device: &ash::Device,
+    image: vk::Image,
+    format: vk::Format,
+    aspect: vk::ImageAspectFlags,
+    mip_levels: u32,
+) -> Result<vk::ImageView> {
+    let info = vk::ImageViewCreateInfo::default()
+        .image(image)
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(format)
+        .subresource_range(
+            vk::ImageSubresourceRange::default()
+                .aspect_mask(aspect)
+                .level_count(mip_levels)
+                .layer_count(1),
+        );
+
+    Ok(unsafe { device.create_image_view(&info, None)? })
+}
@@ -1,6 +1,8 @@
+pub mod allocator;
 pub mod buffer;
 pub mod descriptor;
 pub mod image;
 pub mod image_view;
 pub mod sampler;
 
+pub mod upload;
@@ -0,0 +1,71 @@
+use crate::core::device::Device;
+use anyhow::Result;
+use ash::vk;
+
+/// Everything needed to build a `vk::Sampler`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDesc {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode: vk::SamplerAddressMode,
+    /// Requested max anisotropy; clamped to the device limit and ignored
+    /// when `samplerAnisotropy` is not enabled.
+    pub anisotropy: Option<f32>,
+    pub max_lod: f32,
+}
+
+impl Default for SamplerDesc {
+    /// Trilinear, repeating, 8x anisotropic.
+    fn default() -> Self {
+        Self {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode: vk::SamplerAddressMode::REPEAT,
+            anisotropy: Some(8.0),
+            max_lod: vk::LOD_CLAMP_NONE,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// Pixel-art / lookup textures: no filtering at all.
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            anisotropy: None,
+            ..Self::default()
+        }
+    }
+
+    pub fn clamped(mut self) -> Self {
+        self.address_mode = vk::SamplerAddressMode::CLAMP_TO_EDGE;
+        self
+    }
+}
+
+pub fn create_sampler(dev: &Device, desc: &SamplerDesc) -> Result<vk::Sampler> {
+    let anisotropy = desc
+        .anisotropy
+        .filter(|_| dev.features.sampler_anisotropy == vk::TRUE)
+        .map(|a| a.clamp(1.0, dev.properties.limits.max_sampler_anisotropy));
+
+    let info = vk::SamplerCreateInfo::default()
+        .mag_filter(desc.mag_filter)
+        .min_filter(desc.min_filter)
+        .mipmap_mode(desc.mipmap_mode)
+        .address_mode_u(desc.address_mode)
+        .address_mode_v(desc.address_mode)
+        .address_mode_w(desc.address_mode)
+        .anisotropy_enable(anisotropy.is_some())
+        .max_anisotropy(anisotropy.unwrap_or(1.0))
+        .compare_enable(false)
+        .min_lod(0.0)
+        .max_lod(desc.max_lod)
+        .border_color(vk::BorderColor::INT_OPAQUE_BLACK);
+
+    Ok(unsafe { dev.device.create_sampler(&info, None)? })
+}
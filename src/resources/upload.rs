@@ -0,0 +1,560 @@
+use crate::core::device::Device;
+use crate::renderer::command_buffers::{allocate_command_buffers, create_command_pool};
+use crate::resources::buffer::{GpuBuffer, create_buffer};
+use anyhow::Result;
+use ash::vk;
+use std::collections::VecDeque;
+
+/// Size of the first staging ring; it at least doubles whenever an upload
+/// does not fit.
+const INITIAL_STAGING_SIZE: vk::DeviceSize = 4 * 1024 * 1024;
+/// Alignment of every staging allocation.
+const STAGING_ALIGNMENT: vk::DeviceSize = 16;
+
+/// Identifies one submitted batch of uploads; later batches compare greater.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UploadTicket(u64);
+
+/// Bookkeeping for a ring buffer of `capacity` bytes. `head` and `tail` only
+/// ever grow; the byte offset is the position modulo `capacity`.
+#[derive(Debug)]
+pub struct RingAllocator {
+    capacity: u64,
+    head: u64,
+    tail: u64,
+}
+
+impl RingAllocator {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Bytes allocated and not yet released.
+    pub fn used(&self) -> u64 {
+        self.head - self.tail
+    }
+
+    /// Reserves `size` contiguous bytes at a multiple of `alignment`
+    /// (a power of two dividing the capacity) and returns their offset, or
+    /// `None` if the free part of the ring is too small. Never splits an
+    /// allocation across the end of the buffer.
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        if size == 0 || size > self.capacity {
+            return None;
+        }
+
+        let mut start = self.head.next_multiple_of(alignment);
+        let offset = start % self.capacity;
+        if offset + size > self.capacity {
+            // skip the tail end of the buffer
+            start += self.capacity - offset;
+        }
+        if start + size - self.tail > self.capacity {
+            return None;
+        }
+
+        self.head = start + size;
+        Some(start % self.capacity)
+    }
+
+    /// Position to `release_to` once everything allocated so far is free.
+    pub fn head(&self) -> u64 {
+        self.head
+    }
+
+    /// Frees every allocation made before `head()` returned `position`.
+    pub fn release_to(&mut self, position: u64) {
+        self.tail = self.tail.max(position.min(self.head));
+    }
+}
+
+/// Recycled per-batch command buffer and sync objects.
+struct BatchSync {
+    cmd: vk::CommandBuffer,
+    fence: vk::Fence,
+    /// Signaled by the transfer submit, waited on by a graphics submit.
+    semaphore: vk::Semaphore,
+}
+
+/// A buffer written by a batch, handed over to the graphics queue.
+struct Handoff {
+    buffer: vk::Buffer,
+    stage: vk::PipelineStageFlags,
+    access: vk::AccessFlags,
+}
+
+/// Batch still being recorded.
+struct OpenBatch {
+    ticket: UploadTicket,
+    sync: BatchSync,
+    handoffs: Vec<Handoff>,
+}
+
+struct SubmittedBatch {
+    ticket: UploadTicket,
+    sync: BatchSync,
+    handoffs: Vec<Handoff>,
+    /// Staging generation and ring position to release when retired.
+    ring_end: (u32, u64),
+    /// Frame whose graphics submit waits on `sync.semaphore`.
+    waited_by: Option<u64>,
+}
+
+/// What a graphics submit needs to use freshly uploaded buffers: wait on
+/// `semaphores` and record `record_acquires` before any reads.
+#[derive(Default)]
+pub struct UploadWait {
+    pub semaphores: Vec<vk::Semaphore>,
+    pub stages: Vec<vk::PipelineStageFlags>,
+    /// Queue family ownership acquires; empty without a dedicated transfer
+    /// family.
+    acquires: Vec<vk::BufferMemoryBarrier<'static>>,
+    acquire_stage: vk::PipelineStageFlags,
+}
+
+impl UploadWait {
+    /// Acquire half of the ownership transfers. Its source stages match the
+    /// semaphore wait stages, so it chains after the transfer submit.
+    pub fn record_acquires(&self, device: &ash::Device, cmd: vk::CommandBuffer) {
+        if self.acquires.is_empty() {
+            return;
+        }
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cmd,
+                self.acquire_stage,
+                self.acquire_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &self.acquires,
+                &[],
+            );
+        }
+    }
+}
+
+/// Batched buffer uploads on the transfer queue.
+///
+/// Data is copied into a persistently mapped staging ring and recorded into
+/// the open batch; `flush` submits it, signaling a semaphore the next frame
+/// waits on (see `take_wait`). The renderer never blocks on an upload.
+///
+/// Images still use `Device::begin_one_time_commands`: mip generation blits
+/// need a graphics queue.
+pub struct UploadContext {
+    pool: vk::CommandPool,
+    queue: vk::Queue,
+    family: u32,
+    graphics_family: u32,
+
+    staging: GpuBuffer,
+    ring: RingAllocator,
+    /// Bumped every time the staging buffer is replaced.
+    generation: u32,
+    /// Outgrown staging buffers and discarded uploads, freed once the batch
+    /// with this ticket (the last one that may copy from or into them) has
+    /// been collected.
+    retired: Vec<(GpuBuffer, UploadTicket)>,
+
+    open: Option<OpenBatch>,
+    submitted: VecDeque<SubmittedBatch>,
+    free_sync: Vec<BatchSync>,
+    next_ticket: u64,
+}
+
+impl UploadContext {
+    pub fn new(dev: &Device) -> Result<Self> {
+        let family = dev.queues.upload_family();
+        let pool = create_command_pool(&dev.device, family)?;
+
+        Ok(Self {
+            pool,
+            queue: dev.transfer_queue,
+            family,
+            graphics_family: dev.queues.graphics_family,
+            staging: create_staging(dev, INITIAL_STAGING_SIZE)?,
+            ring: RingAllocator::new(INITIAL_STAGING_SIZE),
+            generation: 0,
+            retired: Vec::new(),
+            open: None,
+            submitted: VecDeque::new(),
+            free_sync: Vec::new(),
+            next_ticket: 0,
+        })
+    }
+
+    /// Creates a device-local buffer and records the copy of `bytes` into
+    /// it. The buffer may be used by any frame submitted after the next
+    /// `take_wait`, with the wait and acquires applied.
+    pub fn upload_buffer(
+        &mut self,
+        dev: &Device,
+        bytes: &[u8],
+        usage: vk::BufferUsageFlags,
+    ) -> Result<GpuBuffer> {
+        if bytes.is_empty() {
+            anyhow::bail!("upload_buffer called with empty data");
+        }
+        let size = bytes.len() as vk::DeviceSize;
+
+        // MoltenVK: avoid DEVICE_LOCAL (Metal heaps), write a host-visible
+        // buffer directly
+        if cfg!(target_os = "macos") {
+            let buf = create_buffer(
+                dev,
+                size,
+                usage,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )?;
+            buf.write_bytes(bytes);
+            return Ok(buf);
+        }
+
+        let dst = create_buffer(
+            dev,
+            size,
+            usage | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let result = self.stage(dev, bytes).and_then(|offset| {
+            let cmd = self.open_batch(dev)?;
+            let region = vk::BufferCopy::default().src_offset(offset).size(size);
+            unsafe {
+                dev.device
+                    .cmd_copy_buffer(cmd, self.staging.buffer, dst.buffer, &[region]);
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            dst.destroy(&dev.device);
+            return Err(e);
+        }
+
+        let (stage, access) = first_use(usage);
+        if let Some(batch) = &mut self.open {
+            batch.handoffs.push(Handoff {
+                buffer: dst.buffer,
+                stage,
+                access,
+            });
+        }
+
+        Ok(dst)
+    }
+
+    /// Submits the open batch, if any, and returns its ticket.
+    pub fn flush(&mut self, dev: &Device) -> Result<Option<UploadTicket>> {
+        let Some(batch) = self.open.take() else {
+            return Ok(None);
+        };
+        let device = &dev.device;
+
+        // release half of the ownership transfers
+        let releases: Vec<vk::BufferMemoryBarrier> = batch
+            .handoffs
+            .iter()
+            .filter(|_| self.family != self.graphics_family)
+            .map(|h| {
+                ownership_barrier(h.buffer, self.family, self.graphics_family)
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            })
+            .collect();
+
+        unsafe {
+            if !releases.is_empty() {
+                device.cmd_pipeline_barrier(
+                    batch.sync.cmd,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &releases,
+                    &[],
+                );
+            }
+            device.end_command_buffer(batch.sync.cmd)?;
+
+            device.reset_fences(&[batch.sync.fence])?;
+            let cmds = [batch.sync.cmd];
+            let signal = [batch.sync.semaphore];
+            let submit = vk::SubmitInfo::default()
+                .command_buffers(&cmds)
+                .signal_semaphores(&signal);
+            device.queue_submit(self.queue, &[submit], batch.sync.fence)?;
+        }
+
+        let ticket = batch.ticket;
+        self.submitted.push_back(SubmittedBatch {
+            ticket,
+            sync: batch.sync,
+            handoffs: batch.handoffs,
+            ring_end: (self.generation, self.ring.head()),
+            waited_by: None,
+        });
+
+        Ok(Some(ticket))
+    }
+
+    /// Flushes, then hands every submitted batch no frame waits on yet to
+    /// `frame` (a `Renderer::frame_count`).
+    pub fn take_wait(&mut self, dev: &Device, frame: u64) -> Result<UploadWait> {
+        self.flush(dev)?;
+
+        let mut wait = UploadWait::default();
+        for batch in self.submitted.iter_mut().filter(|b| b.waited_by.is_none()) {
+            batch.waited_by = Some(frame);
+
+            let stage = batch
+                .handoffs
+                .iter()
+                .fold(vk::PipelineStageFlags::empty(), |acc, h| acc | h.stage);
+            wait.semaphores.push(batch.sync.semaphore);
+            wait.stages.push(stage);
+
+            if self.family != self.graphics_family {
+                wait.acquire_stage |= stage;
+                wait.acquires.extend(batch.handoffs.iter().map(|h| {
+                    ownership_barrier(h.buffer, self.family, self.graphics_family)
+                        .dst_access_mask(h.access)
+                }));
+            }
+        }
+
+        Ok(wait)
+    }
+
+    /// Recycles batches waited on by frames before `completed_frame`, which
+    /// must all have finished on the GPU. Their transfers finished first.
+    pub fn collect(&mut self, device: &ash::Device, completed_frame: u64) {
+        while self
+            .submitted
+            .front()
+            .is_some_and(|b| b.waited_by.is_some_and(|f| f < completed_frame))
+        {
+            let batch = self.submitted.pop_front().unwrap();
+
+            let (generation, position) = batch.ring_end;
+            if generation == self.generation {
+                self.ring.release_to(position);
+            }
+            self.retired.retain(|(buffer, ticket)| {
+                let done = *ticket <= batch.ticket;
+                if done {
+                    buffer.destroy(device);
+                }
+                !done
+            });
+
+            self.free_sync.push(batch.sync);
+        }
+    }
+
+    /// Gives up a buffer returned by `upload_buffer`. It is destroyed once
+    /// the batch that copies into it has been collected, since its copy and
+    /// ownership transfer still reference it until then.
+    pub fn discard(&mut self, device: &ash::Device, buffer: GpuBuffer) {
+        self.retire(device, buffer);
+    }
+
+    /// Destroys `buffer` after the newest batch, open or submitted, the
+    /// last that may use it.
+    fn retire(&mut self, device: &ash::Device, buffer: GpuBuffer) {
+        match self.next_ticket.checked_sub(1) {
+            Some(last) => self.retired.push((buffer, UploadTicket(last))),
+            None => buffer.destroy(device),
+        }
+    }
+
+    /// The device must be idle.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        let open = self.open.take().map(|b| b.sync);
+        let submitted = self.submitted.drain(..).map(|b| b.sync);
+        for sync in self.free_sync.drain(..).chain(open).chain(submitted) {
+            unsafe {
+                device.destroy_fence(sync.fence, None);
+                device.destroy_semaphore(sync.semaphore, None);
+            }
+        }
+        for (buffer, _) in self.retired.drain(..) {
+            buffer.destroy(device);
+        }
+        self.staging.destroy(device);
+        unsafe {
+            device.destroy_command_pool(self.pool, None);
+        }
+    }
+
+    /// Copies `bytes` into the staging ring, growing it if they do not fit.
+    fn stage(&mut self, dev: &Device, bytes: &[u8]) -> Result<vk::DeviceSize> {
+        let size = bytes.len() as vk::DeviceSize;
+
+        let offset = match self.ring.allocate(size, STAGING_ALIGNMENT) {
+            Some(offset) => offset,
+            None => {
+                self.grow(dev, size)?;
+                self.ring
+                    .allocate(size, STAGING_ALIGNMENT)
+                    .expect("fresh staging ring fits the upload")
+            }
+        };
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                self.staging.mapped_ptr().add(offset as usize),
+                bytes.len(),
+            );
+        }
+        Ok(offset)
+    }
+
+    /// Replaces the staging buffer with one at least twice as large and
+    /// able to hold `min_size`. Copies already recorded keep reading the
+    /// old buffer until the open batch retires.
+    fn grow(&mut self, dev: &Device, min_size: vk::DeviceSize) -> Result<()> {
+        let capacity = (self.ring.capacity() * 2).max(min_size.next_power_of_two());
+        let in_use = self.ring.used();
+        let staging = create_staging(dev, capacity)?;
+
+        let old = std::mem::replace(&mut self.staging, staging);
+        self.retire(&dev.device, old);
+
+        self.ring = RingAllocator::new(capacity);
+        self.generation = self.generation.wrapping_add(1);
+
+        log::info!(
+            "Staging ring grown to {} MiB ({} KiB were in use)",
+            capacity / (1024 * 1024),
+            in_use / 1024
+        );
+        Ok(())
+    }
+
+    /// Command buffer of the open batch, beginning a new one if needed.
+    fn open_batch(&mut self, dev: &Device) -> Result<vk::CommandBuffer> {
+        if let Some(batch) = &self.open {
+            return Ok(batch.sync.cmd);
+        }
+
+        let sync = match self.free_sync.pop() {
+            Some(sync) => sync,
+            None => create_batch_sync(&dev.device, self.pool)?,
+        };
+        let begin = vk::CommandBufferBeginInfo::default()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe { dev.device.begin_command_buffer(sync.cmd, &begin)? };
+
+        let cmd = sync.cmd;
+        self.open = Some(OpenBatch {
+            ticket: UploadTicket(self.next_ticket),
+            sync,
+            handoffs: Vec::new(),
+        });
+        self.next_ticket += 1;
+
+        Ok(cmd)
+    }
+}
+
+fn create_staging(dev: &Device, size: vk::DeviceSize) -> Result<GpuBuffer> {
+    create_buffer(
+        dev,
+        size,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    )
+}
+
+fn create_batch_sync(device: &ash::Device, pool: vk::CommandPool) -> Result<BatchSync> {
+    let cmd = allocate_command_buffers(device, pool, 1)?[0];
+
+    unsafe {
+        Ok(BatchSync {
+            cmd,
+            fence: device.create_fence(&vk::FenceCreateInfo::default(), None)?,
+            semaphore: device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?,
+        })
+    }
+}
+
+fn ownership_barrier(buffer: vk::Buffer, from: u32, to: u32) -> vk::BufferMemoryBarrier<'static> {
+    vk::BufferMemoryBarrier::default()
+        .src_queue_family_index(from)
+        .dst_queue_family_index(to)
+        .buffer(buffer)
+        .offset(0)
+        .size(vk::WHOLE_SIZE)
+}
+
+/// Earliest stage and access that may read a buffer created with `usage`.
+fn first_use(usage: vk::BufferUsageFlags) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+    use vk::{AccessFlags as A, BufferUsageFlags as U, PipelineStageFlags as S};
+
+    let shaders = S::VERTEX_SHADER | S::FRAGMENT_SHADER | S::COMPUTE_SHADER;
+    let uses = [
+        (U::VERTEX_BUFFER, S::VERTEX_INPUT, A::VERTEX_ATTRIBUTE_READ),
+        (U::INDEX_BUFFER, S::VERTEX_INPUT, A::INDEX_READ),
+        (
+            U::INDIRECT_BUFFER,
+            S::DRAW_INDIRECT,
+            A::INDIRECT_COMMAND_READ,
+        ),
+        (U::UNIFORM_BUFFER, shaders, A::UNIFORM_READ),
+        (U::STORAGE_BUFFER, shaders, A::SHADER_READ),
+        (U::TRANSFER_SRC, S::TRANSFER, A::TRANSFER_READ),
+    ];
+
+    let (stage, access) = uses
+        .iter()
+        .filter(|(flag, _, _)| usage.contains(*flag))
+        .fold((S::empty(), A::empty()), |(s, a), (_, stage, access)| {
+            (s | *stage, a | *access)
+        });
+
+    if stage.is_empty() {
+        (S::ALL_COMMANDS, A::MEMORY_READ)
+    } else {
+        (stage, access)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_wraps_instead_of_splitting() {
+        let mut ring = RingAllocator::new(256);
+        assert_eq!(ring.allocate(100, 16), Some(0));
+        assert_eq!(ring.allocate(100, 16), Some(112));
+
+        // 212..256 is too short for 64 bytes and the start is still in use
+        assert_eq!(ring.allocate(64, 16), None);
+
+        ring.release_to(112);
+        assert_eq!(ring.allocate(64, 16), Some(0));
+        assert_eq!(ring.used(), 256 - 112 + 64);
+    }
+
+    #[test]
+    fn released_ring_is_reused() {
+        let mut ring = RingAllocator::new(128);
+        assert_eq!(ring.allocate(128, 16), Some(0));
+        assert_eq!(ring.allocate(1, 16), None);
+
+        let end = ring.head();
+        ring.release_to(end);
+        assert_eq!(ring.used(), 0);
+        assert_eq!(ring.allocate(128, 16), Some(0));
+        assert_eq!(ring.allocate(129, 16), None);
+    }
+}
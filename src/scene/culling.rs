@@ -0,0 +1,290 @@
+use glam::{Mat4, Vec3, Vec4};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// A degenerate box at the origin when `points` is empty.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut iter = points.into_iter();
+        let Some(first) = iter.next() else {
+            return Self {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+            };
+        };
+
+        iter.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |b, p| Self {
+                min: b.min.min(p),
+                max: b.max.max(p),
+            },
+        )
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half size along each axis.
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Box around this box after `m` (which may rotate / scale it).
+    pub fn transformed(&self, m: Mat4) -> Self {
+        let center = m.transform_point3(self.center());
+        let e = self.extents();
+
+        // |M| * e gives the new half size (Arvo)
+        let extents = m.x_axis.truncate().abs() * e.x
+            + m.y_axis.truncate().abs() * e.y
+            + m.z_axis.truncate().abs() * e.z;
+
+        Self {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Centered on the box of `points`, just large enough to hold them.
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let center = Aabb::from_points(points.iter().copied()).center();
+        let radius = points
+            .iter()
+            .map(|p| p.distance(center))
+            .fold(0.0, f32::max);
+
+        Self { center, radius }
+    }
+
+    /// Conservative under non-uniform scale: uses the largest axis scale.
+    pub fn transformed(&self, m: Mat4) -> Self {
+        let scale = m
+            .x_axis
+            .truncate()
+            .length()
+            .max(m.y_axis.truncate().length())
+            .max(m.z_axis.truncate().length());
+
+        Self {
+            center: m.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// `normal . p + d = 0`, normal pointing into the frustum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub d: f32,
+}
+
+impl Plane {
+    fn from_vec4(v: Vec4) -> Self {
+        let len = v.truncate().length();
+        Self {
+            normal: v.truncate() / len,
+            d: v.w / len,
+        }
+    }
+
+    /// Signed distance, positive on the inside.
+    pub fn distance(&self, p: Vec3) -> f32 {
+        self.normal.dot(p) + self.d
+    }
+}
+
+/// The six clip planes of a view-projection matrix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Gribb/Hartmann plane extraction. Assumes Vulkan's `0 <= z <= w`
+    /// clip depth; for a GL-style `-w..w` matrix this puts the near plane
+    /// where Vulkan clips anyway.
+    pub fn from_view_proj(m: Mat4) -> Self {
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+
+        Self {
+            planes: [
+                Plane::from_vec4(r3 + r0),
+                Plane::from_vec4(r3 - r0),
+                Plane::from_vec4(r3 + r1),
+                Plane::from_vec4(r3 - r1),
+                Plane::from_vec4(r2),
+                Plane::from_vec4(r3 - r2),
+            ],
+        }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.distance(sphere.center) >= -sphere.radius)
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let extents = aabb.extents();
+
+        self.planes.iter().all(|p| {
+            // projected half size of the box onto the plane normal
+            let r = extents.dot(p.normal.abs());
+            p.distance(center) >= -r
+        })
+    }
+}
+
+/// Per-frame culling result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Camera at the origin looking down -Z, 90° vertical fov, square.
+    fn frustum() -> Frustum {
+        let proj = Mat4::perspective_rh(90f32.to_radians(), 1.0, 0.1, 100.0);
+        Frustum::from_view_proj(proj)
+    }
+
+    fn sphere(center: Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    fn cube_at(center: Vec3, half: f32) -> Aabb {
+        Aabb {
+            min: center - Vec3::splat(half),
+            max: center + Vec3::splat(half),
+        }
+    }
+
+    #[test]
+    fn planes_are_normalized_and_face_inward() {
+        let f = frustum();
+        let inside = Vec3::new(0.0, 0.0, -10.0);
+
+        for p in &f.planes {
+            assert!((p.normal.length() - 1.0).abs() < 1e-5);
+            assert!(p.distance(inside) > 0.0);
+        }
+    }
+
+    #[test]
+    fn sphere_in_front_is_visible() {
+        assert!(frustum().intersects_sphere(&sphere(Vec3::new(0.0, 0.0, -5.0), 1.0)));
+    }
+
+    #[test]
+    fn sphere_behind_camera_is_culled() {
+        assert!(!frustum().intersects_sphere(&sphere(Vec3::new(0.0, 0.0, 5.0), 1.0)));
+    }
+
+    #[test]
+    fn sphere_beyond_far_plane_is_culled() {
+        let f = frustum();
+        assert!(!f.intersects_sphere(&sphere(Vec3::new(0.0, 0.0, -102.0), 1.0)));
+        // straddling the far plane still counts
+        assert!(f.intersects_sphere(&sphere(Vec3::new(0.0, 0.0, -100.5), 1.0)));
+    }
+
+    #[test]
+    fn sphere_outside_side_planes_is_culled() {
+        let f = frustum();
+        // at z = -10 the 90° frustum spans x, y in [-10, 10]
+        assert!(!f.intersects_sphere(&sphere(Vec3::new(-13.0, 0.0, -10.0), 1.0)));
+        assert!(!f.intersects_sphere(&sphere(Vec3::new(13.0, 0.0, -10.0), 1.0)));
+        assert!(!f.intersects_sphere(&sphere(Vec3::new(0.0, -13.0, -10.0), 1.0)));
+        assert!(!f.intersects_sphere(&sphere(Vec3::new(0.0, 13.0, -10.0), 1.0)));
+        assert!(f.intersects_sphere(&sphere(Vec3::new(10.5, 0.0, -10.0), 1.0)));
+    }
+
+    #[test]
+    fn aabb_tests_match_spheres() {
+        let f = frustum();
+        assert!(f.intersects_aabb(&cube_at(Vec3::new(0.0, 0.0, -5.0), 1.0)));
+        assert!(!f.intersects_aabb(&cube_at(Vec3::new(0.0, 0.0, 5.0), 1.0)));
+        assert!(!f.intersects_aabb(&cube_at(Vec3::new(-13.0, 0.0, -10.0), 1.0)));
+        assert!(f.intersects_aabb(&cube_at(Vec3::new(10.5, 0.0, -10.0), 1.0)));
+    }
+
+    #[test]
+    fn view_matrix_moves_the_frustum() {
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 20.0), Vec3::ZERO, Vec3::Y);
+        let proj = Mat4::perspective_rh(90f32.to_radians(), 1.0, 0.1, 100.0);
+        let f = Frustum::from_view_proj(proj * view);
+
+        assert!(f.intersects_sphere(&sphere(Vec3::ZERO, 1.0)));
+        assert!(!f.intersects_sphere(&sphere(Vec3::new(0.0, 0.0, 25.0), 1.0)));
+    }
+
+    #[test]
+    fn aabb_from_points_and_transform() {
+        let b = Aabb::from_points([
+            Vec3::new(-1.0, 0.0, 2.0),
+            Vec3::new(1.0, -3.0, 0.0),
+            Vec3::new(0.0, 1.0, 1.0),
+        ]);
+        assert_eq!(b.min, Vec3::new(-1.0, -3.0, 0.0));
+        assert_eq!(b.max, Vec3::new(1.0, 1.0, 2.0));
+
+        let unit = cube_at(Vec3::ZERO, 1.0);
+        let m = Mat4::from_translation(Vec3::new(5.0, 0.0, 0.0))
+            * Mat4::from_rotation_y(45f32.to_radians());
+        let t = unit.transformed(m);
+
+        let s = 2f32.sqrt();
+        assert!((t.min - Vec3::new(5.0 - s, -1.0, -s)).length() < 1e-5);
+        assert!((t.max - Vec3::new(5.0 + s, 1.0, s)).length() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_transform_uses_largest_scale() {
+        let s = sphere(Vec3::new(1.0, 0.0, 0.0), 1.0);
+        let m = Mat4::from_scale(Vec3::new(2.0, 3.0, 1.0));
+        let t = s.transformed(m);
+
+        assert_eq!(t.center, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(t.radius, 3.0);
+    }
+
+    #[test]
+    fn sphere_from_points_contains_them() {
+        let points = [
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(0.5, -1.0, 0.0),
+        ];
+        let s = BoundingSphere::from_points(&points);
+
+        assert_eq!(s.center, Vec3::ZERO);
+        for p in points {
+            assert!(p.distance(s.center) <= s.radius + 1e-6);
+        }
+    }
+}
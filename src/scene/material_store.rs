@@ -0,0 +1,149 @@
+use crate::core::device::Device;
+use crate::renderer::material::Material;
+use crate::renderer::texture::Texture;
+use crate::resources::buffer::{GpuBuffer, MaterialUbo, create_buffer};
+use crate::resources::descriptor::{
+    allocate_descriptor_sets, create_material_descriptor_pool, create_material_set_layout,
+    write_material_set,
+};
+use crate::resources::sampler::SamplerDesc;
+use anyhow::Result;
+use ash::vk;
+use std::path::Path;
+
+/// Sets per descriptor pool; a new pool is created when one runs out.
+const MATERIALS_PER_POOL: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextureId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialId(pub u32);
+
+impl MaterialId {
+    /// White, opaque, untextured. Always present.
+    pub const DEFAULT: MaterialId = MaterialId(0);
+}
+
+struct MaterialSlot {
+    material: Material,
+    ubo: GpuBuffer,
+    set: vk::DescriptorSet,
+}
+
+/// Owns textures and materials plus the descriptor set layout for set 1.
+/// Materials are immutable once added and live until `destroy`, so a
+/// `MaterialId` from this store is always valid.
+pub struct MaterialStore {
+    layout: vk::DescriptorSetLayout,
+    pools: Vec<vk::DescriptorPool>,
+    /// Sets allocated from the last pool.
+    pool_used: u32,
+    textures: Vec<Texture>,
+    materials: Vec<MaterialSlot>,
+}
+
+impl MaterialStore {
+    pub fn new(dev: &Device) -> Result<Self> {
+        let layout = create_material_set_layout(&dev.device)?;
+
+        let mut store = Self {
+            layout,
+            pools: Vec::new(),
+            pool_used: 0,
+            textures: Vec::new(),
+            materials: Vec::new(),
+        };
+
+        // TextureId(0): bound for untextured materials
+        store.add_texture(Texture::white(dev)?);
+        store.add(dev, Material::default())?;
+
+        Ok(store)
+    }
+
+    pub fn layout(&self) -> vk::DescriptorSetLayout {
+        self.layout
+    }
+
+    pub fn add_texture(&mut self, texture: Texture) -> TextureId {
+        self.textures.push(texture);
+        TextureId((self.textures.len() - 1) as u32)
+    }
+
+    /// Loads a PNG/TGA file with the default (trilinear, repeat) sampler.
+    pub fn load_texture(&mut self, dev: &Device, path: impl AsRef<Path>) -> Result<TextureId> {
+        let texture = Texture::load(dev, path, &SamplerDesc::default())?;
+        Ok(self.add_texture(texture))
+    }
+
+    pub fn add(&mut self, dev: &Device, material: Material) -> Result<MaterialId> {
+        let texture = match material.base_color_texture {
+            Some(id) => self
+                .textures
+                .get(id.0 as usize)
+                .ok_or_else(|| anyhow::anyhow!("unknown texture {:?}", id))?,
+            None => &self.textures[0],
+        };
+
+        let size = std::mem::size_of::<MaterialUbo>() as u64;
+        let ubo = create_buffer(
+            dev,
+            size,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let params = MaterialUbo {
+            base_color: material.base_color.to_array(),
+        };
+
+        ubo.write_bytes(bytemuck::bytes_of(&params));
+
+        if self.pools.is_empty() || self.pool_used == MATERIALS_PER_POOL {
+            self.pools.push(create_material_descriptor_pool(
+                &dev.device,
+                MATERIALS_PER_POOL,
+            )?);
+            self.pool_used = 0;
+        }
+
+        let pool = *self.pools.last().unwrap();
+        let set = allocate_descriptor_sets(&dev.device, pool, self.layout, 1)?[0];
+        self.pool_used += 1;
+
+        write_material_set(
+            &dev.device,
+            set,
+            ubo.buffer,
+            size,
+            texture.descriptor_info(),
+        );
+
+        self.materials.push(MaterialSlot { material, ubo, set });
+        Ok(MaterialId((self.materials.len() - 1) as u32))
+    }
+
+    pub fn get(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0 as usize].material
+    }
+
+    pub fn descriptor_set(&self, id: MaterialId) -> vk::DescriptorSet {
+        self.materials[id.0 as usize].set
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device) {
+        for slot in self.materials.drain(..) {
+            slot.ubo.destroy(device);
+        }
+        for texture in self.textures.drain(..) {
+            texture.destroy(device);
+        }
+        unsafe {
+            for pool in self.pools.drain(..) {
+                device.destroy_descriptor_pool(pool, None);
+            }
+            device.destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
@@ -1,38 +1,137 @@
 use crate::renderer::mesh::Mesh;
 use crate::{assets::mesh::MeshData, core::device::Device, renderer::renderer::Renderer};
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct MeshId(pub usize);
+use thiserror::Error;
 
-pub struct MeshStore {
-    meshes: Vec<Mesh>,
+/// Generational handle: `index` is the slot, `generation` is bumped every
+/// time the slot is freed, so handles to removed meshes go stale instead of
+/// silently pointing at whatever reuses the slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshId {
+    pub index: u32,
+    pub generation: u32,
 }
 
-impl MeshStore {
+#[derive(Debug, Error)]
+pub enum MeshStoreError {
+    #[error("stale mesh handle {0:?}")]
+    StaleHandle(MeshId),
+}
+
+struct Slot<M> {
+    generation: u32,
+    mesh: Option<M>,
+}
+
+/// A removed mesh waiting for the frames that may still read it.
+struct PendingDestroy<M> {
+    mesh: M,
+    /// Destroy once `Renderer::frame_count` reaches this.
+    retire_at: u64,
+}
+
+/// Slots of GPU meshes. Generic over the mesh type only so the handle and
+/// retirement bookkeeping can be tested without a device.
+pub struct MeshStore<M = Mesh> {
+    slots: Vec<Slot<M>>,
+    free: Vec<u32>,
+    pending: Vec<PendingDestroy<M>>,
+}
+
+impl<M> MeshStore<M> {
     pub fn new() -> Self {
-        Self { meshes: Vec::new() }
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            pending: Vec::new(),
+        }
     }
 
-    pub fn add(&mut self, mesh: Mesh) -> MeshId {
-        let id = MeshId(self.meshes.len());
-        self.meshes.push(mesh);
-        id
+    pub fn add(&mut self, mesh: M) -> MeshId {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.mesh = Some(mesh);
+            return MeshId {
+                index,
+                generation: slot.generation,
+            };
+        }
+
+        self.slots.push(Slot {
+            generation: 0,
+            mesh: Some(mesh),
+        });
+        MeshId {
+            index: (self.slots.len() - 1) as u32,
+            generation: 0,
+        }
     }
 
-    pub fn get(&self, id: MeshId) -> &Mesh {
-        &self.meshes[id.0]
+    pub fn get(&self, id: MeshId) -> Result<&M, MeshStoreError> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.mesh.as_ref())
+            .ok_or(MeshStoreError::StaleHandle(id))
     }
 
-    pub fn destroy_all(&mut self, device: &ash::Device) {
-        for m in self.meshes.drain(..) {
-            m.destroy(device);
+    /// Invalidates `id` and frees its slot; the mesh itself is kept until
+    /// `retire` is called with a frame count of at least `retire_at`.
+    fn remove_at(&mut self, id: MeshId, retire_at: u64) -> Result<(), MeshStoreError> {
+        self.get(id)?;
+
+        let slot = &mut self.slots[id.index as usize];
+        let mesh = slot.mesh.take().ok_or(MeshStoreError::StaleHandle(id))?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+
+        self.pending.push(PendingDestroy { mesh, retire_at });
+        Ok(())
+    }
+
+    /// Hands removed meshes whose `retire_at` has been reached to `destroy`.
+    fn retire(&mut self, frame_count: u64, mut destroy: impl FnMut(M)) {
+        let (done, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|p| frame_count >= p.retire_at);
+        self.pending = pending;
+
+        for p in done {
+            destroy(p.mesh);
         }
     }
 }
 
 impl MeshStore {
+    /// Invalidates `id` right away. The GPU buffers are kept until every
+    /// frame that was in flight at this point has retired; see
+    /// `collect_garbage`.
+    pub fn remove(&mut self, id: MeshId, renderer: &Renderer) -> Result<(), MeshStoreError> {
+        self.remove_at(id, renderer.frame_count + renderer.frames_in_flight as u64)
+    }
+
+    /// Destroys removed meshes that no submitted frame can still use.
+    /// Call once per frame, e.g. before building render items.
+    pub fn collect_garbage(&mut self, renderer: &Renderer, device: &ash::Device) {
+        self.retire(renderer.frame_count, |mesh| mesh.destroy(device));
+    }
+
+    pub fn destroy_all(&mut self, device: &ash::Device) {
+        for slot in &mut self.slots {
+            if let Some(m) = slot.mesh.take() {
+                m.destroy(device);
+            }
+        }
+        self.slots.clear();
+        self.free.clear();
+
+        for p in self.pending.drain(..) {
+            p.mesh.destroy(device);
+        }
+    }
+
     pub fn upload(
         &mut self,
-        renderer: &Renderer,
+        renderer: &mut Renderer,
         dev: &Device,
         mesh: &MeshData,
     ) -> anyhow::Result<MeshId> {
@@ -40,3 +139,66 @@ impl MeshStore {
         Ok(self.add(gpu))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reused_slots_bump_the_generation() {
+        let mut store = MeshStore::new();
+        let a = store.add("a");
+        let b = store.add("b");
+        store.remove_at(a, 0).unwrap();
+
+        let c = store.add("c");
+        assert_eq!(c.index, a.index);
+        assert_eq!(c.generation, a.generation + 1);
+        assert_eq!(store.get(c).copied().unwrap(), "c");
+        assert_eq!(store.get(b).copied().unwrap(), "b");
+    }
+
+    #[test]
+    fn stale_handles_are_rejected() {
+        let mut store = MeshStore::new();
+        let a = store.add("a");
+        store.remove_at(a, 0).unwrap();
+
+        assert!(matches!(store.get(a), Err(MeshStoreError::StaleHandle(id)) if id == a));
+        assert!(matches!(
+            store.remove_at(a, 0),
+            Err(MeshStoreError::StaleHandle(_))
+        ));
+
+        // still stale once the slot holds a new mesh
+        store.add("b");
+        assert!(store.get(a).is_err());
+
+        let out_of_range = MeshId {
+            index: 7,
+            generation: 0,
+        };
+        assert!(store.get(out_of_range).is_err());
+    }
+
+    #[test]
+    fn removed_meshes_retire_after_the_frames_in_flight() {
+        let mut store = MeshStore::new();
+        let a = store.add("a");
+        let b = store.add("b");
+
+        // removed at frame 5 with 2 frames in flight
+        store.remove_at(a, 5 + 2).unwrap();
+        store.remove_at(b, 5 + 3).unwrap();
+
+        let mut destroyed = Vec::new();
+        store.retire(6, |m| destroyed.push(m));
+        assert!(destroyed.is_empty());
+
+        store.retire(7, |m| destroyed.push(m));
+        assert_eq!(destroyed, ["a"]);
+
+        store.retire(8, |m| destroyed.push(m));
+        assert_eq!(destroyed, ["a", "b"]);
+    }
+}
@@ -2,3 +2,5 @@ pub mod transform;
 pub mod scene;
 pub mod mesh_store;
 
+pub mod material_store;
+pub mod culling;
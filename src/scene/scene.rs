@@ -1,33 +1,405 @@
-use crate::{engine::camera::Camera, renderer::render_types::RenderItem};
+use crate::{
+    assets::gltf_import::ImportedScene, core::device::Device, engine::camera::Camera,
+    renderer::render_types::RenderItem, renderer::renderer::Renderer,
+};
+use glam::Mat4;
 
 use super::{
+    culling::{CullStats, Frustum},
+    material_store::MaterialId,
     mesh_store::{MeshId, MeshStore},
     transform::Transform,
 };
 
 pub struct Object {
-    pub mesh: MeshId,
+    /// `None` for pure transform nodes (groups, attachment points).
+    pub mesh: Option<MeshId>,
+    pub material: MaterialId,
+    /// Local transform, relative to the parent (or world for roots).
     pub transform: Transform,
+    /// See `RenderItem::opacity`.
+    pub opacity: f32,
+
+    parent: Option<usize>,
+    children: Vec<usize>,
+    world: Mat4,
+    dirty: bool,
+}
+
+impl Object {
+    pub fn new(mesh: Option<MeshId>, transform: Transform) -> Self {
+        Self {
+            mesh,
+            material: MaterialId::DEFAULT,
+            transform,
+            opacity: 1.0,
+            parent: None,
+            children: Vec::new(),
+            world: Mat4::IDENTITY,
+            dirty: true,
+        }
+    }
+
+    pub fn with_material(mut self, material: MaterialId) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    /// World matrix as of the last `Scene::update_transforms`.
+    pub fn world_matrix(&self) -> Mat4 {
+        self.world
+    }
 }
 
+/// Objects form a forest: each object has an optional parent, and its world
+/// matrix is `parent.world * transform`. Mutable access goes through
+/// `object_mut` / `character_mut`, which mark the object dirty so that
+/// `update_transforms` only recomputes the affected subtrees.
 pub struct Scene {
     pub camera: Camera,
-    pub objects: Vec<Object>,
+    objects: Vec<Object>,
     pub character: usize, // index into objects
 }
 
 impl Scene {
+    pub fn new(camera: Camera) -> Self {
+        Self {
+            camera,
+            objects: Vec::new(),
+            character: 0,
+        }
+    }
+
+    /// Adds a root object and returns its index.
+    pub fn add(&mut self, object: Object) -> usize {
+        self.objects.push(object);
+        self.objects.len() - 1
+    }
+
+    /// Adds `object` as a child of `parent`; its transform is local to it.
+    pub fn add_child(&mut self, parent: usize, object: Object) -> usize {
+        let idx = self.add(object);
+        self.objects[idx].parent = Some(parent);
+        self.objects[parent].children.push(idx);
+        idx
+    }
+
+    pub fn object(&self, idx: usize) -> &Object {
+        &self.objects[idx]
+    }
+
+    pub fn object_mut(&mut self, idx: usize) -> &mut Object {
+        let obj = &mut self.objects[idx];
+        obj.dirty = true;
+        obj
+    }
+
     pub fn character_mut(&mut self) -> &mut Object {
-        &mut self.objects[self.character]
+        self.object_mut(self.character)
+    }
+
+    /// Moves `child` under `parent` (`None` makes it a root).
+    ///
+    /// With `keep_world` the local transform is adjusted so the object stays
+    /// where it is; otherwise it keeps its local transform and moves with the
+    /// new parent. World matrices must be up to date for `keep_world`.
+    pub fn set_parent(
+        &mut self,
+        child: usize,
+        parent: Option<usize>,
+        keep_world: bool,
+    ) -> anyhow::Result<()> {
+        if let Some(p) = parent
+            && self.is_ancestor_or_self(child, p)
+        {
+            anyhow::bail!("set_parent: {} is an ancestor of {}", child, p);
+        }
+
+        if keep_world {
+            let parent_world = parent.map_or(Mat4::IDENTITY, |p| self.objects[p].world);
+            let local = parent_world.inverse() * self.objects[child].world;
+            self.objects[child].transform = Transform::from_matrix(local);
+        }
+
+        if let Some(old) = self.objects[child].parent {
+            self.objects[old].children.retain(|&c| c != child);
+        }
+        if let Some(p) = parent {
+            self.objects[p].children.push(child);
+        }
+
+        let obj = &mut self.objects[child];
+        obj.parent = parent;
+        obj.dirty = true;
+        Ok(())
     }
 
-    pub fn render_items<'a>(&'a self, meshes: &'a MeshStore) -> Vec<RenderItem<'a>> {
-        self.objects
-            .iter()
-            .map(|obj| RenderItem {
-                mesh: meshes.get(obj.mesh),
-                model: obj.transform.model_matrix(),
-            })
-            .collect()
+    /// Makes `child` a root object, keeping its world placement.
+    pub fn detach(&mut self, child: usize) -> anyhow::Result<()> {
+        self.set_parent(child, None, true)
+    }
+
+    fn is_ancestor_or_self(&self, ancestor: usize, mut idx: usize) -> bool {
+        loop {
+            if idx == ancestor {
+                return true;
+            }
+            match self.objects[idx].parent {
+                Some(p) => idx = p,
+                None => return false,
+            }
+        }
+    }
+
+    /// Recomputes cached world matrices for dirty objects and everything
+    /// below them. Clean subtrees are skipped.
+    pub fn update_transforms(&mut self) {
+        for idx in 0..self.objects.len() {
+            if self.objects[idx].parent.is_none() {
+                self.update_subtree(idx, Mat4::IDENTITY, false);
+            }
+        }
+    }
+
+    fn update_subtree(&mut self, idx: usize, parent_world: Mat4, parent_changed: bool) {
+        let obj = &mut self.objects[idx];
+        let changed = parent_changed || obj.dirty;
+
+        if changed {
+            obj.world = parent_world * obj.transform.model_matrix();
+            obj.dirty = false;
+        }
+
+        let world = obj.world;
+        for i in 0..self.objects[idx].children.len() {
+            let child = self.objects[idx].children[i];
+            self.update_subtree(child, world, changed);
+        }
+    }
+
+    /// Emits world matrices for objects inside `frustum`; call
+    /// `update_transforms` first. Objects that still refer to a removed
+    /// mesh are skipped with a warning.
+    pub fn render_items<'a>(
+        &'a self,
+        meshes: &'a MeshStore,
+        frustum: &Frustum,
+    ) -> (Vec<RenderItem<'a>>, CullStats) {
+        let mut items = Vec::new();
+        let mut stats = CullStats::default();
+
+        for obj in &self.objects {
+            let Some(id) = obj.mesh else { continue };
+            let mesh = match meshes.get(id) {
+                Ok(mesh) => mesh,
+                Err(e) => {
+                    log::warn!("Not drawing object: {}", e);
+                    continue;
+                }
+            };
+
+            // sphere first (cheap), then the tighter box
+            let visible = frustum.intersects_sphere(&mesh.sphere.transformed(obj.world))
+                && frustum.intersects_aabb(&mesh.aabb.transformed(obj.world));
+
+            if !visible {
+                stats.culled += 1;
+                continue;
+            }
+
+            stats.drawn += 1;
+            items.push(RenderItem {
+                mesh,
+                material: obj.material,
+                model: obj.world,
+                opacity: obj.opacity,
+            });
+        }
+
+        (items, stats)
+    }
+
+    /// Uploads every primitive of `imported` and recreates its node
+    /// hierarchy as objects, with the glTF roots attached to `parent`.
+    /// Nodes with several primitives get one child object per extra
+    /// primitive. Returns the indices of all new objects. If an upload
+    /// fails, the meshes uploaded so far are removed again.
+    pub fn instantiate(
+        &mut self,
+        imported: &ImportedScene,
+        parent: Option<usize>,
+        meshes: &mut MeshStore,
+        renderer: &mut Renderer,
+        dev: &Device,
+    ) -> anyhow::Result<Vec<usize>> {
+        let mut mesh_ids: Vec<Vec<MeshId>> = Vec::with_capacity(imported.meshes.len());
+        for primitives in &imported.meshes {
+            let mut ids = Vec::with_capacity(primitives.len());
+            for data in primitives {
+                match meshes.upload(renderer, dev, data) {
+                    Ok(id) => ids.push(id),
+                    Err(e) => {
+                        for &id in mesh_ids.iter().flatten().chain(&ids) {
+                            meshes.remove(id, renderer)?;
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+            mesh_ids.push(ids);
+        }
+
+        // imported.nodes is pre-order, so parents are spawned first
+        let mut node_objects: Vec<usize> = Vec::with_capacity(imported.nodes.len());
+        let mut spawned = Vec::new();
+
+        for node in &imported.nodes {
+            let ids = node.mesh.map_or(&[][..], |m| &mesh_ids[m][..]);
+            let object = Object::new(ids.first().copied(), node.transform.clone());
+
+            let idx = match node.parent.map(|p| node_objects[p]).or(parent) {
+                Some(p) => self.add_child(p, object),
+                None => self.add(object),
+            };
+            node_objects.push(idx);
+            spawned.push(idx);
+
+            for &extra in ids.iter().skip(1) {
+                let child = self.add_child(idx, Object::new(Some(extra), Transform::identity()));
+                spawned.push(child);
+            }
+        }
+
+        Ok(spawned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Quat, Vec3};
+
+    fn scene() -> Scene {
+        Scene::new(Camera {
+            yaw: 0.0,
+            pitch: 0.0,
+            pos: Vec3::ZERO,
+            target: Vec3::Z,
+            fov_deg: 60.0,
+            near: 0.1,
+            far: 100.0,
+        })
+    }
+
+    fn at(x: f32, y: f32, z: f32) -> Object {
+        let mut tf = Transform::identity();
+        tf.position = Vec3::new(x, y, z);
+        Object::new(None, tf)
+    }
+
+    fn position(scene: &Scene, idx: usize) -> Vec3 {
+        scene.object(idx).world_matrix().w_axis.truncate()
+    }
+
+    #[test]
+    fn child_world_is_parent_times_local() {
+        let mut scene = scene();
+        let mut parent_tf = Transform::identity();
+        parent_tf.position = Vec3::new(1.0, 0.0, 0.0);
+        parent_tf.rotation = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        parent_tf.scale = Vec3::splat(2.0);
+        let parent = scene.add(Object::new(None, parent_tf.clone()));
+        let child = scene.add_child(parent, at(0.0, 0.0, 1.0));
+        scene.update_transforms();
+
+        let expected = parent_tf.model_matrix() * scene.object(child).transform.model_matrix();
+        assert!(
+            scene
+                .object(child)
+                .world_matrix()
+                .abs_diff_eq(expected, 1e-6)
+        );
+        // +Z rotated a quarter turn about Y is +X, then scaled by 2
+        assert!(position(&scene, child).abs_diff_eq(Vec3::new(3.0, 0.0, 0.0), 1e-6));
+
+        assert_eq!(scene.object(child).parent(), Some(parent));
+        assert_eq!(scene.object(parent).children(), [child]);
+    }
+
+    #[test]
+    fn only_dirty_subtrees_are_recomputed() {
+        let mut scene = scene();
+        let a = scene.add(at(0.0, 0.0, 0.0));
+        let a_child = scene.add_child(a, at(0.0, 1.0, 0.0));
+        let b = scene.add(at(5.0, 0.0, 0.0));
+        scene.update_transforms();
+
+        // bypass `object_mut`, so `b` is not marked dirty
+        scene.objects[b].transform.position = Vec3::new(9.0, 0.0, 0.0);
+        scene.object_mut(a).transform.position = Vec3::new(0.0, 0.0, 2.0);
+        scene.update_transforms();
+
+        assert_eq!(position(&scene, a), Vec3::new(0.0, 0.0, 2.0));
+        assert_eq!(position(&scene, a_child), Vec3::new(0.0, 1.0, 2.0));
+        // clean subtree skipped: still the old world matrix
+        assert_eq!(position(&scene, b), Vec3::new(5.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn reparenting_can_keep_the_world_placement() {
+        let mut scene = scene();
+        let parent = scene.add(at(2.0, 0.0, 0.0));
+        let kept = scene.add(at(1.0, 1.0, 0.0));
+        let moved = scene.add(at(1.0, 1.0, 0.0));
+        scene.update_transforms();
+
+        scene.set_parent(kept, Some(parent), true).unwrap();
+        scene.set_parent(moved, Some(parent), false).unwrap();
+        scene.update_transforms();
+
+        assert!(position(&scene, kept).abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+        assert_eq!(
+            scene.object(kept).transform.position,
+            Vec3::new(-1.0, 1.0, 0.0)
+        );
+        assert_eq!(position(&scene, moved), Vec3::new(3.0, 1.0, 0.0));
+        assert_eq!(scene.object(parent).children(), [kept, moved]);
+
+        scene.detach(kept).unwrap();
+        scene.update_transforms();
+        assert_eq!(scene.object(kept).parent(), None);
+        assert_eq!(scene.object(parent).children(), [moved]);
+        assert!(position(&scene, kept).abs_diff_eq(Vec3::new(1.0, 1.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut scene = scene();
+        let root = scene.add(at(0.0, 0.0, 0.0));
+        let child = scene.add_child(root, at(0.0, 0.0, 0.0));
+        let grandchild = scene.add_child(child, at(0.0, 0.0, 0.0));
+
+        assert!(scene.set_parent(root, Some(grandchild), false).is_err());
+        assert!(scene.set_parent(child, Some(child), false).is_err());
+        // nothing changed
+        assert_eq!(scene.object(root).parent(), None);
+        assert_eq!(scene.object(child).children(), [grandchild]);
+
+        scene.set_parent(grandchild, Some(root), false).unwrap();
+        assert_eq!(scene.object(root).children(), [child, grandchild]);
+        assert!(scene.object(child).children().is_empty());
     }
 }
@@ -16,6 +16,17 @@ impl Transform {
         }
     }
 
+    /// Decomposes an affine matrix. Shear (non-uniform scale under a
+    /// rotated parent) cannot be represented and is lost.
+    pub fn from_matrix(m: Mat4) -> Self {
+        let (scale, rotation, position) = m.to_scale_rotation_translation();
+        Self {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
     pub fn model_matrix(&self) -> Mat4 {
         Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
     }
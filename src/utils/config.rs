@@ -10,6 +10,13 @@ pub struct Config {
     pub controls: ControlsConfig,
     pub game: GameConfig,
     pub graphics: GraphicsConfig,
+    pub lighting: LightingConfig,
+    #[serde(default)]
+    pub shadows: ShadowConfig,
+    #[serde(default)]
+    pub post: PostConfig,
+    #[serde(default)]
+    pub debug: DebugConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -22,6 +29,33 @@ pub struct WindowConfig {
 #[derive(Debug, Deserialize, Clone)]
 pub struct RendererConfig {
     pub frames_in_flight: usize,
+    /// Upper bound for `frames_in_flight`, checked by `Renderer::new`.
+    #[serde(default = "default_max_frames_in_flight")]
+    pub max_frames_in_flight: usize,
+
+    /// Render offscreen without a window (CI / software Vulkan).
+    #[serde(default)]
+    pub headless: bool,
+    /// Number of frames `Engine::run` renders in headless mode.
+    #[serde(default = "default_headless_frames")]
+    pub headless_frames: u32,
+
+    /// Dev mode: recompile `shaders/*.vert|*.frag` when they change.
+    #[serde(default)]
+    pub shader_hot_reload: bool,
+
+    /// Use dynamic rendering (Vulkan 1.3 or `VK_KHR_dynamic_rendering`)
+    /// instead of render passes and framebuffers, where supported.
+    #[serde(default)]
+    pub dynamic_rendering: bool,
+}
+
+fn default_max_frames_in_flight() -> usize {
+    3
+}
+
+fn default_headless_frames() -> u32 {
+    60
 }
 
 pub fn load_config(path: &str) -> Result<Config> {
@@ -53,9 +87,125 @@ pub struct ControlsConfig {
 #[derive(Debug, Deserialize, Clone)]
 pub struct GameConfig {
     pub arena_size: f32,
+    /// PNG/TGA tiled once per world unit over the floor; a checkerboard
+    /// when unset or unreadable.
+    #[serde(default)]
+    pub floor_texture: Option<String>,
+    /// Models placed in the arena at startup.
+    #[serde(default)]
+    pub models: Vec<ModelConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ModelConfig {
+    /// `.gltf`, `.glb` or `.obj` file.
+    pub path: String,
+    #[serde(default)]
+    pub position: [f32; 3],
+    /// Uniform scale.
+    #[serde(default = "default_model_scale")]
+    pub scale: f32,
+}
+
+fn default_model_scale() -> f32 {
+    1.0
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct GraphicsConfig {
     pub clear_color: [f32; 4],
+    /// MSAA sample count; 1 disables it. Clamped to what the device
+    /// supports for color + depth attachments.
+    #[serde(default = "default_msaa_samples")]
+    pub msaa_samples: u32,
+}
+
+fn default_msaa_samples() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct DebugConfig {
+    /// Show `Engine::debug` shapes (gizmos); off, they are discarded.
+    #[serde(default)]
+    pub draw: bool,
+    /// FPS, frame time, draw count and swapchain info in the top-left
+    /// corner.
+    #[serde(default)]
+    pub stats: bool,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LightingConfig {
+    /// Direction the sun light travels (world up is -Y).
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub ambient: [f32; 3],
+    pub specular: f32,
+    pub shininess: f32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShadowConfig {
+    /// Shadow map width and height in texels.
+    pub resolution: u32,
+    /// Depth bias (light clip space) applied when comparing against the
+    /// shadow map; grows at grazing angles.
+    pub bias: f32,
+    /// How far from the camera shadows are rendered, in world units.
+    pub distance: f32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            bias: 0.002,
+            distance: 40.0,
+        }
+    }
+}
+
+/// HDR -> display chain run after the scene pass, in this order:
+/// exposure + tonemapping + gamma, FXAA, vignette, then `custom`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PostConfig {
+    /// Scene color multiplier applied before tonemapping.
+    pub exposure: f32,
+    pub tonemap: Tonemap,
+    /// Gamma-encode the output. Only applies to UNORM outputs; sRGB
+    /// swapchain images are encoded by the hardware.
+    pub gamma: bool,
+    pub fxaa: bool,
+    pub vignette: bool,
+    /// How much the corners are darkened, 0..1.
+    pub vignette_strength: f32,
+    /// Extra fullscreen fragment shaders (GLSL paths), run last, in order.
+    pub custom: Vec<String>,
+}
+
+impl Default for PostConfig {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemap: Tonemap::Aces,
+            gamma: true,
+            fxaa: false,
+            vignette: false,
+            vignette_strength: 0.3,
+            custom: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Tonemap {
+    /// Clamp only.
+    None,
+    Reinhard,
+    Aces,
 }
@@ -0,0 +1,184 @@
+use crate::renderer::capture::CapturedFrame;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Result of a per-pixel comparison between two RGBA8 images.
+#[derive(Debug, Clone, Copy)]
+pub struct DiffStats {
+    pub total: usize,
+    /// Pixels with at least one channel differing by more than the tolerance.
+    pub mismatched: usize,
+    pub max_channel_diff: u8,
+}
+
+impl DiffStats {
+    pub fn passed(&self) -> bool {
+        self.mismatched == 0
+    }
+}
+
+/// Compares two equally sized RGBA8 buffers channel by channel.
+pub fn compare_rgba(actual: &[u8], expected: &[u8], tolerance: u8) -> DiffStats {
+    let mut stats = DiffStats {
+        total: actual.len().min(expected.len()) / 4,
+        mismatched: 0,
+        max_channel_diff: 0,
+    };
+
+    for (a, e) in actual.chunks_exact(4).zip(expected.chunks_exact(4)) {
+        let diff = a
+            .iter()
+            .zip(e)
+            .map(|(&x, &y)| x.abs_diff(y))
+            .max()
+            .unwrap_or(0);
+
+        stats.max_channel_diff = stats.max_channel_diff.max(diff);
+        if diff > tolerance {
+            stats.mismatched += 1;
+        }
+    }
+
+    stats
+}
+
+pub fn load_rgba(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let img = image::open(path)
+        .with_context(|| format!("Failed to read golden image: {}", path.display()))?
+        .to_rgba8();
+    Ok((img.width(), img.height(), img.into_raw()))
+}
+
+/// Checks `frame` against the golden image at `golden`.
+///
+/// With `update == true` the golden is (re)written instead. On mismatch the
+/// actual frame is saved next to the golden as `<name>.actual.png`.
+pub fn check_golden(
+    frame: &CapturedFrame,
+    golden: &Path,
+    tolerance: u8,
+    update: bool,
+) -> Result<()> {
+    if update {
+        if let Some(dir) = golden.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        frame.save(golden)?;
+        log::info!("Golden image written: {}", golden.display());
+        return Ok(());
+    }
+
+    let (w, h, expected) = load_rgba(golden)?;
+    let actual_path = golden.with_extension("actual.png");
+
+    if (w, h) != (frame.width, frame.height) {
+        frame.save(&actual_path)?;
+        anyhow::bail!(
+            "golden size mismatch: expected {}x{}, got {}x{} (actual saved to {})",
+            w,
+            h,
+            frame.width,
+            frame.height,
+            actual_path.display()
+        );
+    }
+
+    let stats = compare_rgba(&frame.rgba, &expected, tolerance);
+    if !stats.passed() {
+        frame.save(&actual_path)?;
+        anyhow::bail!(
+            "golden mismatch for {}: {}/{} pixels over tolerance {} (max diff {}), actual saved to {}",
+            golden.display(),
+            stats.mismatched,
+            stats.total,
+            tolerance,
+            stats.max_channel_diff,
+            actual_path.display()
+        );
+    }
+
+    log::info!(
+        "Golden OK: {} (max diff {})",
+        golden.display(),
+        stats.max_channel_diff
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn frame(width: u32, height: u32, rgba: [u8; 4]) -> CapturedFrame {
+        CapturedFrame {
+            width,
+            height,
+            rgba: rgba.repeat((width * height) as usize),
+        }
+    }
+
+    /// A fresh directory for one test's golden files.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("golden-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn compare_counts_pixels_over_tolerance() {
+        let expected = [10, 20, 30, 255, 10, 20, 30, 255];
+        let same = compare_rgba(&expected, &expected, 0);
+        assert!(same.passed());
+        assert_eq!((same.total, same.max_channel_diff), (2, 0));
+
+        let actual = [12, 20, 30, 255, 10, 20, 40, 255];
+        let stats = compare_rgba(&actual, &expected, 2);
+        assert_eq!((stats.mismatched, stats.max_channel_diff), (1, 10));
+        assert!(compare_rgba(&actual, &expected, 10).passed());
+    }
+
+    #[test]
+    fn identical_frame_matches_golden() {
+        let golden = scratch_dir("identical").join("scene.png");
+        let reference = frame(4, 3, [200, 100, 50, 255]);
+        check_golden(&reference, &golden, 0, true).unwrap();
+        assert!(golden.exists());
+
+        check_golden(&reference, &golden, 0, false).unwrap();
+        assert!(!golden.with_extension("actual.png").exists());
+    }
+
+    #[test]
+    fn small_differences_are_within_tolerance() {
+        let golden = scratch_dir("tolerance").join("scene.png");
+        check_golden(&frame(4, 3, [200, 100, 50, 255]), &golden, 0, true).unwrap();
+
+        check_golden(&frame(4, 3, [202, 99, 50, 255]), &golden, 2, false).unwrap();
+    }
+
+    #[test]
+    fn differences_over_tolerance_fail_and_save_the_actual_frame() {
+        let golden = scratch_dir("over").join("scene.png");
+        check_golden(&frame(4, 3, [200, 100, 50, 255]), &golden, 0, true).unwrap();
+
+        let err = check_golden(&frame(4, 3, [210, 100, 50, 255]), &golden, 2, false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("12/12 pixels"), "{}", err);
+        assert!(golden.with_extension("actual.png").exists());
+    }
+
+    #[test]
+    fn size_mismatch_fails() {
+        let golden = scratch_dir("size").join("scene.png");
+        check_golden(&frame(4, 3, [200, 100, 50, 255]), &golden, 0, true).unwrap();
+
+        let err = check_golden(&frame(3, 4, [200, 100, 50, 255]), &golden, 255, false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("size mismatch"), "{}", err);
+        assert!(golden.with_extension("actual.png").exists());
+    }
+}
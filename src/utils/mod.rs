@@ -2,3 +2,4 @@ pub mod file_io;
 pub mod vk_check;
 pub mod logger;
 pub mod config;
+pub mod golden;
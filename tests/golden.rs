@@ -0,0 +1,45 @@
+//! Golden-image regression tests.
+//!
+//! These render the default `Game` scene headless and compare the last frame
+//! against `tests/golden/*.png`. They need a Vulkan implementation (lavapipe
+//! works), so they are ignored by default:
+//!
+//!     cargo test --release -- --ignored
+//!
+//! To refresh a golden after an intended visual change:
+//!
+//!     cargo run --release -- --headless --frames 3 \
+//!         --golden tests/golden/default_scene.png --update-golden
+
+use std::process::Command;
+
+const TOLERANCE: &str = "2";
+
+fn render_and_compare(golden: &str, frames: &str) {
+    let out = Command::new(env!("CARGO_BIN_EXE_vulkan-test"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "--headless",
+            "--frames",
+            frames,
+            "--golden",
+            golden,
+            "--tolerance",
+            TOLERANCE,
+        ])
+        .output()
+        .expect("failed to launch vulkan-test");
+
+    assert!(
+        out.status.success(),
+        "golden check failed for {}:\n{}",
+        golden,
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+#[test]
+#[ignore = "needs a Vulkan device (lavapipe is fine)"]
+fn default_scene_matches_golden() {
+    render_and_compare("tests/golden/default_scene.png", "3");
+}